@@ -1,11 +1,12 @@
 use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
-use clap::{arg, Parser, Subcommand};
+use clap::{Parser, Subcommand};
 use cosmos_core::config::Config;
 use cosmos_core::galaxy::{Galaxy, SyncLevel};
-use cosmos_core::installer::{install_star, uninstall_star};
+use cosmos_core::installer::{install_star, plan_star, uninstall_star};
 use cosmos_core::resolver;
+use cosmos_core::sbom::{self, Component, SbomFile, SbomFormat};
 use cosmos_core::star::fetch_star;
 use cosmos_universe::{SystemInfo, Universe};
 
@@ -49,6 +50,10 @@ enum Commands {
         /// Root directory for installation (default: /)
         #[arg(long)]
         root: Option<String>,
+
+        /// Show what would be installed and changed without touching the root
+        #[arg(long)]
+        dry_run: bool,
     },
 
     /// Uninstall a star
@@ -59,6 +64,10 @@ enum Commands {
         /// Root directory for uninstallation (default: /)
         #[arg(long)]
         root: Option<String>,
+
+        /// Also remove system users and groups the star created
+        #[arg(long)]
+        remove_users: bool,
     },
 
     /// Update a star
@@ -99,6 +108,21 @@ enum Commands {
         term: String,
     },
 
+    /// Write a software bill of materials for every installed star
+    Sbom {
+        /// Root directory to describe (default: /)
+        #[arg(long)]
+        root: Option<String>,
+
+        /// Output format: spdx or cyclonedx
+        #[arg(long, default_value = "spdx")]
+        format: String,
+
+        /// File to write (defaults to stdout)
+        #[arg(long)]
+        output: Option<String>,
+    },
+
     /// Initialize the cosmos config and universe
     Init {
         #[arg(long)]
@@ -129,11 +153,19 @@ enum Commands {
     },
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn main() {
+    // print errors with Display so Nova diagnostics stay readable
+    if let Err(e) = run() {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
 
     match cli.command {
-        Commands::Install { name, constellation, offline, root } => {
+        Commands::Install { name, constellation, offline, root, dry_run } => {
             let root_path = Path::new(root.as_deref().unwrap_or("/"));
             let config_path = root_path.join("etc/cosmos/config.toml");
             let universe_path = root_path.join("var/lib/cosmos/universe.toml");
@@ -148,6 +180,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             let galaxies = cosmos_core::galaxy::Galaxy::load_all_from_config(&config, offline)?;
 
+            let mut targets = vec![];
             if let Some(path) = constellation {
                 let constellation = cosmos_core::constellation::Constellation::from_file(&path)?;
 
@@ -165,31 +198,53 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                         .ok_or_else(|| format!("❌ Star '{}' not found in any Galaxy", member))?;
 
                     let star = cosmos_core::star::fetch_star(galaxy, &star.name, &config, offline)?;
-                    install_star(&star, galaxy, &mut universe, &galaxies, &config, offline)?;
+                    targets.push((star, galaxy));
                 }
             } else if let Some(name) = name {
                 let (star, galaxy) = cosmos_core::resolver::find_star(&galaxies, &name, "*")
                     .ok_or_else(|| format!("❌ Star '{}' not found in any Galaxy", name))?;
 
                 let star = cosmos_core::star::fetch_star(galaxy, &star.name, &config, offline)?;
-                install_star(&star, galaxy, &mut universe, &galaxies, &config, offline)?;
+                targets.push((star, galaxy));
             } else {
                 eprintln!("❌ Must provide a star name or --constellation file");
             }
 
+            if dry_run {
+                let mut plans = vec![];
+                for (star, galaxy) in &targets {
+                    plan_star(star, galaxy, &universe, &galaxies, &config, offline, &mut plans)?;
+                }
+                for plan in plans {
+                    println!("📝 {} {}", plan.name, plan.version);
+                    if plan.operations.is_empty() {
+                        println!("  (nothing to do)");
+                    }
+                    for operation in plan.operations {
+                        println!("  - {}", operation);
+                    }
+                }
+                println!("✅ Dry run complete. Nothing was installed.");
+                return Ok(());
+            }
+
+            for (star, galaxy) in &targets {
+                install_star(star, galaxy, &mut universe, &galaxies, &config, offline)?;
+            }
+
             if let Some(root_override) = &root {
                 config.install_dir = root_override.clone();
-                universe.save(&format!("{}/var/lib/cosmos/universe.toml", root_override))?;
+                universe.save(format!("{}/var/lib/cosmos/universe.toml", root_override))?;
             } else {
                 universe.save("/var/lib/cosmos/universe.toml")?;
             }
         }
 
-        Commands::Uninstall { name, root } => {
+        Commands::Uninstall { name, root, remove_users } => {
             let root_path = Path::new(root.as_deref().unwrap_or("/"));
             let universe_path = root_path.join("var/lib/cosmos/universe.toml");
             let mut universe = Universe::load(universe_path.to_str().unwrap())?;
-            uninstall_star(&name, &mut universe, root_path)?;
+            uninstall_star(&name, &mut universe, root_path, remove_users)?;
             universe.save(&universe_path)?;
         }
 
@@ -260,7 +315,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             for galaxy in galaxies {
                 for star in galaxy.stars.values() {
                     if star.name.contains(&term)
-                        || star.description.as_ref().map_or(false, |d| d.contains(&term))
+                        || star.description.as_ref().is_some_and(|d| d.contains(&term))
                     {
                         println!("⭐ {} [{}] ({})", star.name, star.version, galaxy.name);
                     }
@@ -268,6 +323,68 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
         }
 
+        Commands::Sbom { root, format, output } => {
+            let format: SbomFormat = format.parse().map_err(|e| format!("❌ {}", e))?;
+            let root_path = Path::new(root.as_deref().unwrap_or("/"));
+            let config_path = root_path.join("etc/cosmos/config.toml");
+            let universe = Universe::load(root_path.join("var/lib/cosmos/universe.toml"))?;
+
+            // galaxy metadata fills in licenses, authors and sources where the
+            // installed version is still the one the galaxy knows about; without
+            // it stars are listed by name and version only
+            let galaxies = if config_path.exists() {
+                Config::from_file(config_path.to_str().unwrap())
+                    .map_err(|e| e.to_string())
+                    .and_then(|config| Galaxy::load_all_from_config(&config, true).map_err(|e| e.to_string()))
+                    .unwrap_or_else(|e| {
+                        eprintln!("⚠️  Could not load Galaxy metadata ({}); listing stars by name and version only", e);
+                        vec![]
+                    })
+            } else {
+                vec![]
+            };
+
+            let mut names: Vec<&String> = universe.installed.keys().collect();
+            names.sort();
+            let mut components = vec![];
+            for name in names {
+                let installed = &universe.installed[name];
+                let known = galaxies
+                    .iter()
+                    .filter_map(|galaxy| galaxy.get_star(name))
+                    .find(|star| star.version == installed.version);
+                let mut component = match known {
+                    Some(star) => Component::from_star(star),
+                    None => Component { name: name.clone(), version: Some(installed.version.clone()), ..Component::default() },
+                };
+                component.version = Some(installed.version.clone());
+
+                // files removed since the install are not part of the system any more
+                let mut files: Vec<SbomFile> = installed
+                    .files
+                    .iter()
+                    .filter_map(|file| {
+                        let on_disk = root_path.join(file.trim_start_matches('/'));
+                        on_disk.is_file().then(|| SbomFile::hash(file, &on_disk).ok()).flatten()
+                    })
+                    .collect();
+                files.sort();
+                component.files = files;
+                components.push(component);
+            }
+
+            let name = format!("cosmos-{}", universe.system.arch);
+            let tool = format!("cosmos-{}", env!("CARGO_PKG_VERSION"));
+            let document = sbom::render(&name, &tool, &components, format);
+            match output {
+                Some(output) => {
+                    fs::write(&output, document + "\n")?;
+                    println!("📝 Wrote SBOM for {} installed star(s) to {}", components.len(), output);
+                }
+                None => println!("{}", document),
+            }
+        }
+
         Commands::Init { root } => {
             let root_path = Path::new(root.as_deref().unwrap_or("/"));
             let config_path = root_path.join("etc/cosmos/config.toml");
@@ -6,7 +6,9 @@ use std::fs;
 pub struct Config {
     pub galaxies: HashMap<String, String>, // name → url or path
     pub install_dir: String,
-    pub cache_dir: String
+    pub cache_dir: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub init_system: Option<String>, // openrc, runit or s6; detected when unset
     // TODO (Phase 2/3): strict_mode: bool, // for GalaxyMeta version mismatch
 }
 
@@ -14,13 +16,13 @@ impl Config {
     pub fn from_file(path: &str) -> Result<Self, std::io::Error> {
         let content = fs::read_to_string(path)?;
         let parsed: Self = toml::from_str(&content)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+            .map_err(std::io::Error::other)?;
         Ok(parsed)
     }
 
     pub fn save(&self, path: &str) -> Result<(), std::io::Error> {
         let content = toml::to_string(self)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+            .map_err(std::io::Error::other)?;
         fs::write(path, content)?;
         Ok(())
     }
@@ -32,6 +34,7 @@ impl Config {
             galaxies,
             install_dir: "/".to_string(),
             cache_dir: "/var/cache/cosmos".to_string(),
+            init_system: None,
         };
         default_config.save(path)
     }
@@ -9,7 +9,7 @@ impl Constellation {
     pub fn from_file(path: &str) -> Result<Self, std::io::Error> {
         let content = std::fs::read_to_string(path)?;
         let parsed: Self = toml::from_str(&content)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+            .map_err(std::io::Error::other)?;
         Ok(parsed)
     }
 
@@ -39,11 +39,8 @@ pub enum CosmosError {
 
     #[error("Invalid checksum: {0}")]
     InvalidChecksum(String),
-}
 
-impl From<NovaError> for CosmosError {
-    fn from(e: NovaError) -> Self {
-        CosmosError::ScriptFailed(format!("nova: {:?}", e))
-    }
+    #[error("Nova script failed: {0}")]
+    Nova(#[from] NovaError),
 }
 
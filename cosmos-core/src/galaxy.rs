@@ -8,12 +8,21 @@ use thiserror::Error;
 use crate::config::Config;
 use crate::error::CosmosError;
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Galaxy {
     pub name: String,
     pub url: Option<String>,
     pub stars: HashMap<String, Star>, // name → star
     pub checksums: Option<HashMap<String, String>>, // name → checksum
+    #[serde(default)]
+    pub nova_lib: HashMap<String, NovaLibModule>, // module name → module
+}
+
+/// A shared Nova module shipped in the galaxy's `nova-lib/` directory.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NovaLibModule {
+    pub version: String,
+    pub checksum: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -23,6 +32,10 @@ pub struct GalaxyMeta {
     pub version: Option<String>, // e.g. "2025.01.01"
     pub stars: Option<HashMap<String, String>>, // name → version
     pub checksums: Option<HashMap<String, String>>, // name → checksum
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nova_lib: Option<HashMap<String, NovaLibModule>>, // module name → module
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub multi_version: Option<bool>, // keep superseded versions in stars/<name>/ and packages/
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -40,6 +53,8 @@ pub enum GalaxyError {
     Toml(toml::de::Error),
     #[error("Unsupported URL: {0}")]
     UnsupportedUrl(String),
+    #[error("Invalid Nova module name: {0:?}")]
+    InvalidModule(String),
 }
 
 impl From<std::io::Error> for GalaxyError {
@@ -54,6 +69,20 @@ impl From<toml::de::Error> for GalaxyError {
     }
 }
 
+/// Relative paths of the declared `nova-lib/` modules. Names are checked
+/// before any of them is turned into a URL or a path in the cache.
+fn nova_lib_files(modules: Option<&HashMap<String, NovaLibModule>>) -> Result<Vec<PathBuf>, GalaxyError> {
+    let mut names: Vec<&String> = modules.iter().flat_map(|m| m.keys()).collect();
+    names.sort();
+    names
+        .into_iter()
+        .map(|name| match nova::is_valid_module_name(name) {
+            true => Ok(nova::module_file(name)),
+            false => Err(GalaxyError::InvalidModule(name.clone())),
+        })
+        .collect()
+}
+
 impl Galaxy {
     pub fn new(name: &str, url: &str) -> Self {
         Self {
@@ -61,6 +90,7 @@ impl Galaxy {
             url: Some(url.to_string()),
             stars: HashMap::new(),
             checksums: None,
+            nova_lib: HashMap::new(),
         }
     }
 
@@ -128,7 +158,7 @@ impl Galaxy {
     pub fn load(galaxy_path: &Path, url: Option<String>, offline: bool) -> Result<Galaxy, GalaxyError> {
         let name = galaxy_path.file_name()
             .and_then(|n| n.to_str())
-            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "Invalid galaxy folder name"))?
+            .ok_or_else(|| std::io::Error::other("Invalid galaxy folder name"))?
             .to_string();
 
         let meta_path = galaxy_path.join("meta.toml");
@@ -194,6 +224,7 @@ impl Galaxy {
             url,
             stars,
             checksums: meta.checksums,
+            nova_lib: meta.nova_lib.unwrap_or_default(),
         })
     }
 
@@ -210,7 +241,7 @@ impl Galaxy {
         cache_dir: &Path,
         level: SyncLevel,
     ) -> Result<(), GalaxyError> {
-        if !cosmos_transport::supports_url(url.clone()) {
+        if !cosmos_transport::supports_url(url) {
             return Err(GalaxyError::UnsupportedUrl(url.to_string()));
         }
 
@@ -237,6 +268,15 @@ impl Galaxy {
         let meta_content = fs::read_to_string(&meta_dest)?;
         let meta: GalaxyMeta = toml::from_str(&meta_content)?;
 
+        for relative in nova_lib_files(meta.nova_lib.as_ref())? {
+            let module_url = format!("{}/nova-lib/{}", url.trim_end_matches('/'), relative.display());
+            let module_dest = galaxy_cache_dir.join("nova-lib").join(&relative);
+            if let Some(parent) = module_dest.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            Self::download_file(&module_url, &module_dest)?;
+        }
+
         let star_names: Vec<String> = meta.stars
             .map(|m| m.keys().cloned().collect())
             .unwrap_or_default();
@@ -292,10 +332,25 @@ impl Galaxy {
     }
 
     pub fn resolve_source_path(&self, source: &str, config: &Config) -> Result<PathBuf, CosmosError> {
-        if source.starts_with("file://") {
-            return Ok(Path::new(&source[7..]).to_path_buf());
+        if let Some(stripped) = source.strip_prefix("file://") {
+            return Ok(Path::new(stripped).to_path_buf());
         }
 
+        Ok(self.root_dir(config)?.join(source))
+    }
+
+    /// Paths and checksums of the Nova modules this galaxy declares, ready to
+    /// hand to `NovaRuntime::module`.
+    pub fn nova_modules(&self, config: &Config) -> Result<Vec<(String, PathBuf, Option<String>)>, CosmosError> {
+        let lib_dir = self.root_dir(config)?.join("nova-lib");
+        Ok(self.nova_lib.iter()
+            .map(|(name, module)| {
+                (name.clone(), lib_dir.join(nova::module_file(name)), module.checksum.clone())
+            })
+            .collect())
+    }
+
+    fn root_dir(&self, config: &Config) -> Result<PathBuf, CosmosError> {
         let root = if self.is_local() {
             let url = self.url.as_ref().ok_or_else(|| CosmosError::MissingField(format!(
                 "Local Galaxy '{}' missing URL. Define its path in [galaxies] config.",
@@ -306,6 +361,33 @@ impl Galaxy {
             Path::new(&config.cache_dir).join("galaxies").join(&self.name)
         };
 
-        Ok(root.join(source))
+        Ok(root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn modules(names: &[&str]) -> HashMap<String, NovaLibModule> {
+        names
+            .iter()
+            .map(|name| (name.to_string(), NovaLibModule { version: "1.0.0".to_string(), checksum: None }))
+            .collect()
+    }
+
+    #[test]
+    fn nova_lib_files_follow_module_names() {
+        let files = nova_lib_files(Some(&modules(&["cosmos.users", "util"]))).unwrap();
+        assert_eq!(files, vec![PathBuf::from("cosmos/users.lua"), PathBuf::from("util.lua")]);
+        assert!(nova_lib_files(None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn nova_lib_files_refuse_paths() {
+        for name in ["../../etc/cron.d/x", "/etc/passwd", "a..b", "", "a/b"] {
+            let result = nova_lib_files(Some(&modules(&["ok", name])));
+            assert!(matches!(result, Err(GalaxyError::InvalidModule(n)) if n == name), "{:?} accepted", name);
+        }
     }
 }
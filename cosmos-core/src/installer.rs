@@ -1,13 +1,15 @@
+use std::collections::HashMap;
 use std::fs;
 use std::io::Seek;
-use std::path::Path;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
 use flate2::read::GzDecoder;
-use fs_extra::copy_items;
-use fs_extra::dir::CopyOptions;
 use tar::Archive;
 use crate::{star::Star, galaxy::Galaxy, config::Config, universe::record_install, error::CosmosError, resolver};
 
 use cosmos_universe::Universe;
+use nova::{Capability, InstallRecord, NovaRuntime, Operation};
 use crate::resolver::calculate_checksum;
 
 pub fn install_star(
@@ -35,10 +37,230 @@ pub fn install_star(
 
     if star.star_type.as_deref() == Some("nebula") || star.star_type.as_deref() == Some("meta") {
         println!("🌀 Nebula '{}' does not extract files or run scripts. Installation has been logged", star.name);
-        record_install(universe, star, vec![]);
+        record_install(universe, star, InstallRecord::default());
         return Ok(());
     }
 
+    let tarball_path = resolve_tarball(star, origin, config, offline)?;
+
+    let temp_dir = tempfile::tempdir()?;
+    let extracted = extract_star(temp_dir.path(), &tarball_path)?;
+    let mut record = InstallRecord::default();
+
+    star.validate_checksums(temp_dir.path())
+        .map_err(|e| CosmosError::ChecksumFailed(format!("Checksum validation failed: {}", e)))?;
+
+    apply_declarations(star, Path::new(&config.install_dir), &mut record)?;
+
+    if let Some(script) = &star.install_script {
+        let full_script = temp_dir.path().join(script.trim_start_matches("./"));
+
+        if script.ends_with(".lua") || script.ends_with(".nova") {
+            println!("🔧 Running Nova install script: {}", script);
+            let installed = install_runtime(star, temp_dir.path(), origin, universe, config)?
+                .run(full_script.to_str().unwrap())?;
+            record.extend(installed);
+        } else {
+            println!("🔧 Running shell install script: {}", script);
+            run_install_script(full_script.to_str().unwrap(), temp_dir.path())?;
+            record.files = extracted.iter().map(|entry| entry.path.clone()).collect();
+        }
+    } else {
+        let source_dir = temp_dir.path().join("files");
+        if source_dir.exists() {
+            println!("📁 No install script. Copying files/* to {}", config.install_dir);
+            let metadata: HashMap<PathBuf, ExtractedEntry> = extracted
+                .into_iter()
+                .map(|entry| (PathBuf::from(entry.path.trim_start_matches('/')), entry))
+                .collect();
+
+            let mut copier = PreservingCopy { metadata, can_chown: true };
+            copier.copy_tree(&source_dir, &source_dir, Path::new(&config.install_dir), &mut record.files)?;
+        } else {
+            println!("⚠️  No install script and no files/ directory. Nothing to do.");
+        }
+    }
+
+    record_install(universe, star, record);
+    println!("✅ Installed: {}", star.name);
+    Ok(())
+}
+
+/// The Nova runtime install scripts run in: account and service builtins on,
+/// `require` limited to the star's own modules and its galaxy's `nova-lib/`,
+/// and the star's metadata plus any previously installed config files known.
+fn install_runtime(
+    star: &Star,
+    extraction_root: &Path,
+    origin: &Galaxy,
+    universe: &Universe,
+    config: &Config,
+) -> Result<NovaRuntime, CosmosError> {
+    let mut runtime = NovaRuntime::new(extraction_root, Path::new(&config.install_dir))
+        .with_capability(Capability::Users)
+        .with_capability(Capability::Services)
+        .init_system(config.init_system.as_deref())
+        .metadata("name", &star.name)
+        .metadata("version", &star.version)
+        .metadata("arch", &universe.system.arch);
+
+    for (key, value) in [("description", &star.description), ("license", &star.license), ("type", &star.star_type)] {
+        if let Some(value) = value {
+            runtime = runtime.metadata(key, value);
+        }
+    }
+
+    if let Some(previous) = universe.installed.get(&star.name) {
+        for config_file in &previous.config_files {
+            runtime = runtime.installed_config(&config_file.path, &config_file.checksum);
+        }
+    }
+
+    for (name, path, checksum) in origin.nova_modules(config)? {
+        runtime = runtime.module(&name, &path, checksum.as_deref());
+    }
+    Ok(runtime)
+}
+
+/// What installing a star would do, as reported by `cosmos install --dry-run`.
+#[derive(Debug, Clone)]
+pub struct StarPlan {
+    pub name: String,
+    pub version: String,
+    pub operations: Vec<Operation>,
+}
+
+/// Work out what `install_star` would do for `star` and any missing
+/// dependencies without touching the install root or the Universe.
+pub fn plan_star(
+    star: &Star,
+    origin: &Galaxy,
+    universe: &Universe,
+    galaxies: &[Galaxy],
+    config: &Config,
+    offline: bool,
+    plans: &mut Vec<StarPlan>,
+) -> Result<(), CosmosError> {
+    if plans.iter().any(|plan| plan.name == star.name) {
+        return Ok(());
+    }
+
+    for (dep_name, constraint) in star.get_dependencies() {
+        if universe.satisfies(&dep_name, &constraint) {
+            continue;
+        }
+
+        let (dep_star, dep_galaxy) = resolver::find_star(galaxies, &dep_name, &constraint)
+            .ok_or_else(|| CosmosError::DependencyError(format!(
+                "Dependency '{}' not found in any Galaxy", dep_name
+            )))?;
+
+        plan_star(dep_star, dep_galaxy, universe, galaxies, config, offline, plans)?;
+    }
+
+    let mut operations = vec![];
+
+    if star.star_type.as_deref() != Some("nebula") && star.star_type.as_deref() != Some("meta") {
+        let install_root = Path::new(&config.install_dir);
+        let tarball_path = resolve_tarball(star, origin, config, offline)?;
+        let temp_dir = tempfile::tempdir()?;
+        extract_star(temp_dir.path(), &tarball_path)?;
+
+        star.validate_checksums(temp_dir.path())
+            .map_err(|e| CosmosError::ChecksumFailed(format!("Checksum validation failed: {}", e)))?;
+
+        operations.extend(plan_declarations(star, install_root)?);
+
+        if let Some(script) = &star.install_script {
+            let full_script = temp_dir.path().join(script.trim_start_matches("./"));
+            if script.ends_with(".lua") || script.ends_with(".nova") {
+                let planned = install_runtime(star, temp_dir.path(), origin, universe, config)?
+                    .plan(full_script.to_str().unwrap())?;
+                operations.extend(planned);
+            } else {
+                operations.push(Operation::Run {
+                    command: vec!["sh".to_string(), "-c".to_string(), script.clone()],
+                });
+            }
+        } else {
+            let source_dir = temp_dir.path().join("files");
+            let mut files = vec![];
+            if source_dir.exists() {
+                list_files(&source_dir, &source_dir, &mut files)?;
+            }
+            operations.extend(files.into_iter().map(|file| Operation::Copy {
+                to: format!("/{}", file),
+                from: file,
+            }));
+        }
+    }
+
+    plans.push(StarPlan {
+        name: star.name.clone(),
+        version: star.version.clone(),
+        operations,
+    });
+    Ok(())
+}
+
+fn plan_declarations(star: &Star, install_root: &Path) -> Result<Vec<Operation>, CosmosError> {
+    let mut operations = vec![];
+
+    for group in star.groups.iter().flatten() {
+        if !nova::users::group_exists(install_root, &group.name)? {
+            operations.push(Operation::AddGroup { name: group.name.clone() });
+        }
+    }
+
+    for user in star.users.iter().flatten() {
+        if user.group.is_none() && !nova::users::group_exists(install_root, &user.name)? {
+            operations.push(Operation::AddGroup { name: user.name.clone() });
+        }
+        if !nova::users::user_exists(install_root, &user.name)? {
+            operations.push(Operation::AddUser { name: user.name.clone() });
+        }
+        for group in user.groups.iter().flatten() {
+            operations.push(Operation::AddUserToGroup { user: user.name.clone(), group: group.clone() });
+        }
+    }
+
+    for dir in star.directories.iter().flatten() {
+        declared_path(install_root, &dir.path)?;
+        operations.push(Operation::Mkdir { path: dir.path.clone() });
+        if let Some(mode) = dir.mode {
+            operations.push(Operation::Chmod { path: dir.path.clone(), mode });
+        }
+        if let Some((user, group)) = dir.owner_parts() {
+            operations.push(Operation::Chown {
+                path: dir.path.clone(),
+                user: Some(user.to_string()),
+                group: group.map(|g| g.to_string()),
+            });
+        }
+    }
+
+    Ok(operations)
+}
+
+fn list_files(root: &Path, dir: &Path, files: &mut Vec<String>) -> Result<(), CosmosError> {
+    let mut entries = fs::read_dir(dir)?
+        .map(|res| res.map(|e| e.path()))
+        .collect::<Result<Vec<_>, std::io::Error>>()?;
+    entries.sort();
+
+    for path in entries {
+        if fs::symlink_metadata(&path)?.is_dir() {
+            list_files(root, &path, files)?;
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            files.push(relative.to_string_lossy().to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Find the star's tarball in the cache, a local galaxy or a remote source,
+/// downloading it if needed, and verify it against the galaxy checksum.
+fn resolve_tarball(star: &Star, origin: &Galaxy, config: &Config, offline: bool) -> Result<PathBuf, CosmosError> {
     let filename = format!("{}-{}.tar.gz", star.name, star.version);
 
     let mut tarball_path = Path::new(&config.cache_dir)
@@ -124,77 +346,227 @@ pub fn install_star(
         println!("⚠️ No checksum validation for '{}'", filename);
     }
 
-    let temp_dir = tempfile::tempdir()?;
-    let extracted_files = extract_star(temp_dir.path(), &tarball_path)?;
-    let mut installed_files: Vec<String> = vec![];
+    Ok(tarball_path)
+}
 
-    star.validate_checksums(temp_dir.path())
-        .map_err(|e| CosmosError::ChecksumFailed(format!("Checksum validation failed: {}", e)))?;
+/// Where a `[[directories]]` path lives inside `install_root`. Stars come
+/// from galaxies, so `..` is refused here and not only by `stellar validate`.
+fn declared_path(install_root: &Path, path: &str) -> Result<PathBuf, CosmosError> {
+    let relative = Path::new(path.trim_start_matches('/'));
+    if relative.components().any(|c| !matches!(c, std::path::Component::Normal(_))) {
+        return Err(CosmosError::SecurityError(format!("Illegal directory path: {}", path)));
+    }
+    Ok(install_root.join(relative))
+}
 
-    if let Some(script) = &star.install_script {
-        let full_script = temp_dir.path().join(script);
+/// Create the `[[groups]]`, `[[users]]` and `[[directories]]` declared in
+/// star.toml. Runs before any files are copied or scripts are executed.
+fn apply_declarations(star: &Star, install_root: &Path, record: &mut InstallRecord) -> Result<(), CosmosError> {
+    for group in star.groups.iter().flatten() {
+        let change = nova::users::add_group(install_root, &group.name, group.gid)?;
+        if change.created {
+            println!("👥 Created group {} ({})", group.name, change.id);
+            record.groups.push(group.name.clone());
+        }
+    }
 
-        if script.ends_with(".lua") || script.ends_with(".nova") {
-            println!("🔧 Running Nova install script: {}", script);
-            nova::run_nova_script(full_script.to_str().unwrap(), temp_dir.path(), Path::new(&config.install_dir), &mut installed_files)?;
-        } else {
-            println!("🔧 Running shell install script: {}", script);
-            run_install_script(full_script.to_str().unwrap(), temp_dir.path())?;
-            installed_files = extracted_files.clone();
+    for user in star.users.iter().flatten() {
+        if user.group.is_none() {
+            let change = nova::users::add_group(install_root, &user.name, None)?;
+            if change.created {
+                record.groups.push(user.name.clone());
+            }
         }
-    } else {
-        let source_dir = temp_dir.path().join("files");
-        if source_dir.exists() {
-            println!("📁 No install script. Copying files/* to {}", config.install_dir);
-            let mut options = CopyOptions::new();
-            options.overwrite = true;
-            options.copy_inside = true;
-
-            let entries = fs::read_dir(&source_dir)?
-                .map(|res| res.map(|e| e.path()))
-                .collect::<Result<Vec<_>, std::io::Error>>()?;
-
-            copy_items(&entries, Path::new(&config.install_dir), &options)
-                .map_err(|e| CosmosError::CopyFailed(format!("Failed to copy files: {}", e)))?;
-
-            for entry in entries {
-                let path = entry;
-                let relative_path = path.strip_prefix(&source_dir)
-                    .map_err(|e| CosmosError::CopyFailed(format!("Failed to strip prefix: {}", e)))?;
-                let full_path = Path::new(&config.install_dir).join(relative_path);
-                installed_files.push(format!("/{}", full_path.to_string_lossy()));
+
+        let spec = nova::users::UserSpec {
+            uid: user.uid,
+            group: user.group.clone(),
+            home: user.home.clone(),
+            shell: user.shell.clone(),
+            gecos: user.gecos.clone(),
+        };
+        let change = nova::users::add_user(install_root, &user.name, &spec)?;
+        if change.created {
+            println!("👤 Created user {} ({})", user.name, change.id);
+            record.users.push(user.name.clone());
+        }
+
+        for group in user.groups.iter().flatten() {
+            nova::users::add_user_to_group(install_root, &user.name, group)?;
+        }
+    }
+
+    for dir in star.directories.iter().flatten() {
+        let full_path = declared_path(install_root, &dir.path)?;
+        fs::create_dir_all(&full_path)?;
+
+        if let Some(mode) = dir.mode {
+            fs::set_permissions(&full_path, fs::Permissions::from_mode(mode))?;
+        }
+
+        if let Some((user, group)) = dir.owner_parts() {
+            let uid = nova::users::lookup_uid(install_root, user)?
+                .ok_or_else(|| CosmosError::MissingField(format!("Unknown user '{}' for {}", user, dir.path)))?;
+            let gid = match group {
+                Some(group) => Some(nova::users::lookup_gid(install_root, group)?
+                    .ok_or_else(|| CosmosError::MissingField(format!("Unknown group '{}' for {}", group, dir.path)))?),
+                None => None,
+            };
+
+            match std::os::unix::fs::chown(&full_path, Some(uid), gid) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+                    eprintln!("⚠️  Could not change owner of {} (not running as root?)", full_path.display());
+                }
+                Err(e) => return Err(e.into()),
             }
-        } else {
-            println!("⚠️  No install script and no files/ directory. Nothing to do.");
         }
+
+        println!("📁 Prepared directory {}", dir.path);
     }
 
-    record_install(universe, star, installed_files);
-    println!("✅ Installed: {}", star.name);
     Ok(())
 }
 
+/// A tarball member along with the ownership, mode and mtime stored in its header.
+#[derive(Debug, Clone)]
+pub struct ExtractedEntry {
+    pub path: String,
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub mtime: u64,
+}
+
 pub fn extract_star(
     temp_dir: &Path,
     tarball_path: &Path
-) -> Result<Vec<String>, CosmosError> {
+) -> Result<Vec<ExtractedEntry>, CosmosError> {
     let mut file = fs::File::open(tarball_path)?;
     file.rewind()?;
     let tar = GzDecoder::new(file);
     let mut archive = Archive::new(tar);
 
-    let mut installed_files = vec![];
+    let mut extracted = vec![];
     for entry in archive.entries()? {
         let mut entry = entry?;
         let path = entry.path()?.to_path_buf();
         let full_path = temp_dir.join(path.clone());
+        let header = entry.header();
+        let mode = header.mode()?;
+        let uid = header.uid()? as u32;
+        let gid = header.gid()? as u32;
+        let mtime = header.mtime()?;
         entry.unpack(&full_path)?;
-        installed_files.push(
-            format!("/{}", path.to_string_lossy().trim_start_matches("./"))
-        );
+        extracted.push(ExtractedEntry {
+            path: format!("/{}", path.to_string_lossy().trim_start_matches("./")),
+            mode,
+            uid,
+            gid,
+            mtime,
+        });
+    }
+
+    Ok(extracted)
+}
+
+/// Make way for a file or symlink at `dest`. An empty directory in the way
+/// is removed; a populated one is left alone and reported.
+fn clear_dest(dest: &Path) -> Result<(), CosmosError> {
+    match dest.symlink_metadata() {
+        Ok(meta) if meta.is_dir() => fs::remove_dir(dest).map_err(|e| {
+            CosmosError::CopyFailed(format!("Cannot replace directory {} with a file: {}", dest.display(), e))
+        }),
+        Ok(_) => Ok(fs::remove_file(dest)?),
+        Err(_) => Ok(()),
     }
+}
 
-    Ok(installed_files)
+/// Copies an extracted `files/` tree into the install root, restoring the
+/// mode, owner and mtime recorded in the tarball instead of the host defaults.
+struct PreservingCopy {
+    metadata: HashMap<PathBuf, ExtractedEntry>,
+    can_chown: bool,
+}
+
+impl PreservingCopy {
+    fn copy_tree(
+        &mut self,
+        files_root: &Path,
+        dir: &Path,
+        install_root: &Path,
+        installed: &mut Vec<String>,
+    ) -> Result<(), CosmosError> {
+        let mut entries = fs::read_dir(dir)?
+            .map(|res| res.map(|e| e.path()))
+            .collect::<Result<Vec<_>, std::io::Error>>()?;
+        entries.sort();
+
+        for source in entries {
+            let relative = source.strip_prefix(files_root)
+                .map_err(|e| CosmosError::CopyFailed(format!("Failed to strip prefix: {}", e)))?;
+            let dest = install_root.join(relative);
+            let meta = self.metadata.get(&Path::new("files").join(relative)).cloned();
+            let file_type = fs::symlink_metadata(&source)?.file_type();
+
+            if file_type.is_symlink() {
+                let target = fs::read_link(&source)?;
+                clear_dest(&dest)?;
+                std::os::unix::fs::symlink(&target, &dest)?;
+                if let Some(meta) = &meta {
+                    self.chown(&dest, meta)?;
+                }
+                installed.push(format!("/{}", relative.to_string_lossy()));
+            } else if file_type.is_dir() {
+                // only directories we create take the tarball's metadata;
+                // existing ones like /usr keep what the system gave them
+                if !dest.exists() {
+                    fs::create_dir_all(&dest)?;
+                    if let Some(meta) = &meta {
+                        fs::set_permissions(&dest, fs::Permissions::from_mode(meta.mode))?;
+                        self.chown(&dest, meta)?;
+                    }
+                }
+                self.copy_tree(files_root, &source, install_root, installed)?;
+            } else {
+                if let Some(parent) = dest.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                clear_dest(&dest)?;
+                fs::copy(&source, &dest)?;
+                if let Some(meta) = &meta {
+                    // the mtime needs a writable handle, and chown clears setuid
+                    // bits, so the tarball's mode is applied last
+                    fs::set_permissions(&dest, fs::Permissions::from_mode(0o600))?;
+                    fs::File::options()
+                        .write(true)
+                        .open(&dest)?
+                        .set_modified(UNIX_EPOCH + Duration::from_secs(meta.mtime))?;
+                    self.chown(&dest, meta)?;
+                    fs::set_permissions(&dest, fs::Permissions::from_mode(meta.mode))?;
+                }
+                installed.push(format!("/{}", relative.to_string_lossy()));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn chown(&mut self, path: &Path, meta: &ExtractedEntry) -> Result<(), CosmosError> {
+        if !self.can_chown {
+            return Ok(());
+        }
+
+        match std::os::unix::fs::lchown(path, Some(meta.uid), Some(meta.gid)) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+                eprintln!("⚠️  Cannot preserve file ownership (not running as root?). Files will be owned by the current user.");
+                self.can_chown = false;
+                Ok(())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
 }
 
 fn run_install_script(script: &str, temp_dir: &Path) -> Result<(), CosmosError> {
@@ -211,7 +583,12 @@ fn run_install_script(script: &str, temp_dir: &Path) -> Result<(), CosmosError>
     Ok(())
 }
 
-pub fn uninstall_star(name: &str, universe: &mut Universe, install_root: &Path) -> Result<(), CosmosError> {
+pub fn uninstall_star(
+    name: &str,
+    universe: &mut Universe,
+    install_root: &Path,
+    remove_accounts: bool,
+) -> Result<(), CosmosError> {
     let installed = universe.installed.get(name)
         .ok_or_else(|| CosmosError::MissingField(format!("Star '{}' is not installed", name)))?;
 
@@ -219,7 +596,15 @@ pub fn uninstall_star(name: &str, universe: &mut Universe, install_root: &Path)
 
     for file in &installed.files {
         let path = install_root.join(&file[1..]);
-        if path.exists() {
+        let config_file = installed.config_files.iter().find(|config| &config.path == file);
+        if let Some(config_file) = config_file {
+            if path.exists() && calculate_checksum(&path)? != config_file.checksum {
+                println!("  - Kept modified config file {}", path.display());
+                continue;
+            }
+        }
+        // symlink_metadata so links whose target is already gone are removed too
+        if path.symlink_metadata().is_ok() {
             println!("  - Removing {}", path.display());
             fs::remove_file(path)?;
         } else {
@@ -227,7 +612,86 @@ pub fn uninstall_star(name: &str, universe: &mut Universe, install_root: &Path)
         }
     }
 
+    for link in &installed.service_links {
+        println!("  - Disabling service link {}", link);
+        nova::services::remove_link(install_root, link)?;
+    }
+
+    if remove_accounts {
+        for user in &installed.users {
+            println!("  - Removing user {}", user);
+            nova::users::remove_user(install_root, user)?;
+        }
+        for group in &installed.groups {
+            println!("  - Removing group {}", group);
+            nova::users::remove_group(install_root, group)?;
+        }
+    } else {
+        for user in &installed.users {
+            println!("  - Kept user {} (use --remove-users to remove it)", user);
+        }
+        for group in &installed.groups {
+            println!("  - Kept group {} (use --remove-users to remove it)", group);
+        }
+    }
+
     universe.installed.remove(name);
     println!("❌ Uninstalled: {}", name);
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str, mode: u32, mtime: u64) -> (PathBuf, ExtractedEntry) {
+        let entry = ExtractedEntry { path: format!("/{}", path), mode, uid: 0, gid: 0, mtime };
+        (Path::new("files").join(path), entry)
+    }
+
+    #[test]
+    fn copies_keep_read_only_modes_and_mtimes() {
+        let temp = tempfile::tempdir().unwrap();
+        let (files, root) = (temp.path().join("files"), temp.path().join("root"));
+        fs::create_dir_all(files.join("usr/bin")).unwrap();
+        fs::create_dir_all(&root).unwrap();
+        fs::write(files.join("usr/bin/tool"), "#!/bin/sh\n").unwrap();
+        fs::set_permissions(files.join("usr/bin/tool"), fs::Permissions::from_mode(0o555)).unwrap();
+
+        let mut copier = PreservingCopy { metadata: HashMap::from([entry("usr/bin/tool", 0o4555, 1_000_000)]), can_chown: false };
+        let mut installed = vec![];
+        copier.copy_tree(&files, &files, &root, &mut installed).unwrap();
+
+        let meta = fs::metadata(root.join("usr/bin/tool")).unwrap();
+        assert_eq!(meta.permissions().mode() & 0o7777, 0o4555);
+        assert_eq!(meta.modified().unwrap(), UNIX_EPOCH + Duration::from_secs(1_000_000));
+        assert_eq!(installed, vec!["/usr/bin/tool".to_string()]);
+    }
+
+    #[test]
+    fn symlinks_replace_empty_directories_only() {
+        let temp = tempfile::tempdir().unwrap();
+        let (files, root) = (temp.path().join("files"), temp.path().join("root"));
+        fs::create_dir_all(&files).unwrap();
+        std::os::unix::fs::symlink("usr/lib", files.join("lib")).unwrap();
+        fs::create_dir_all(root.join("lib")).unwrap();
+
+        let mut copier = PreservingCopy { metadata: HashMap::new(), can_chown: false };
+        copier.copy_tree(&files, &files, &root, &mut vec![]).unwrap();
+        assert!(root.join("lib").symlink_metadata().unwrap().file_type().is_symlink());
+
+        fs::remove_file(root.join("lib")).unwrap();
+        fs::create_dir_all(root.join("lib")).unwrap();
+        fs::write(root.join("lib/keep"), "").unwrap();
+        assert!(copier.copy_tree(&files, &files, &root, &mut vec![]).is_err());
+        assert!(root.join("lib/keep").exists());
+    }
+
+    #[test]
+    fn declared_paths_stay_inside_the_root() {
+        let root = Path::new("/mnt/root");
+        assert_eq!(declared_path(root, "/var/lib/web").unwrap(), Path::new("/mnt/root/var/lib/web"));
+        assert!(declared_path(root, "/var/../../etc").is_err());
+        assert!(declared_path(root, "../outside").is_err());
+    }
+}
@@ -6,6 +6,7 @@ pub mod galaxy;
 pub mod star;
 pub mod universe;
 pub mod resolver;
+pub mod sbom;
 
 #[cfg(feature = "ffi")]
 pub mod ffi;
\ No newline at end of file
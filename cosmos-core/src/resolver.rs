@@ -3,7 +3,11 @@ use crate::galaxy::Galaxy;
 use crate::star::{compare_versions, Star};
 use crate::error::CosmosError;
 
+/// A bare version is a minimum; `=1.2.0` pins that exact version.
 pub fn satisfies_constraint(installed: &str, constraint: &str) -> Result<bool, CosmosError> {
+    if let Some(exact) = constraint.strip_prefix('=') {
+        return Ok(compare_versions(installed, exact.trim())? == std::cmp::Ordering::Equal);
+    }
     let comparison = compare_versions(installed, constraint)?;
     match comparison {
         std::cmp::Ordering::Less => Ok(false),
@@ -71,4 +75,18 @@ pub fn calculate_checksum(file_path: &Path) -> Result<String, std::io::Error> {
     }
 
     Ok(format!("{:x}", hasher.finalize()))
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bare_versions_are_minimums_and_equals_pins() {
+        assert!(satisfies_constraint("1.2.0", "1.2.0").unwrap());
+        assert!(satisfies_constraint("1.3.0", "1.2.0").unwrap());
+        assert!(!satisfies_constraint("1.1.0", "1.2.0").unwrap());
+        assert!(satisfies_constraint("1.2.0", "=1.2.0").unwrap());
+        assert!(!satisfies_constraint("1.3.0", "=1.2.0").unwrap());
+        assert!(satisfies_constraint("1.2.0", "=x").is_err());
+    }
+}
@@ -0,0 +1,445 @@
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+use std::str::FromStr;
+use chrono::{TimeZone, Utc};
+use serde_json::{json, Map, Value};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use crate::star::Star;
+
+/// The SBOM formats `stellar sbom` and `cosmos sbom` write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SbomFormat {
+    Spdx,      // SPDX 2.3 JSON
+    CycloneDx, // CycloneDX 1.5 JSON
+}
+
+impl FromStr for SbomFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "spdx" => Ok(SbomFormat::Spdx),
+            "cyclonedx" | "cdx" => Ok(SbomFormat::CycloneDx),
+            _ => Err(format!("Unknown SBOM format '{}'. Expected spdx or cyclonedx", s)),
+        }
+    }
+}
+
+/// A file a component installs, with whichever digests are known for it.
+/// Files hashed on disk have both; files known from a star's `checksums` only
+/// have sha256.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SbomFile {
+    pub path: String, // install path
+    pub sha256: Option<String>,
+    pub sha1: Option<String>,
+}
+
+impl SbomFile {
+    /// Hash `on_disk`, which is installed at `path`.
+    pub fn hash(path: &str, on_disk: &Path) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(on_disk)?);
+        let (mut sha256, mut sha1) = (Sha256::new(), Sha1::new());
+        let mut buffer = [0u8; 8192];
+        loop {
+            let read = reader.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            sha256.update(&buffer[..read]);
+            sha1.update(&buffer[..read]);
+        }
+        Ok(SbomFile {
+            path: path.to_string(),
+            sha256: Some(format!("{:x}", sha256.finalize())),
+            sha1: Some(format!("{:x}", sha1.finalize())),
+        })
+    }
+}
+
+/// One package in a bill of materials. Components without a version are
+/// dependencies known only by name; they are listed but not described.
+#[derive(Debug, Clone, Default)]
+pub struct Component {
+    pub name: String,
+    pub version: Option<String>,
+    pub description: Option<String>,
+    pub license: Option<String>,
+    pub authors: Vec<(String, String)>,         // name, email
+    pub sources: Vec<(String, Option<String>)>, // url, sha256
+    pub files: Vec<SbomFile>,
+    pub dependencies: Vec<String>,              // names of other components
+}
+
+impl Component {
+    /// A component for `star`. Files come from its `checksums`, if any.
+    pub fn from_star(star: &Star) -> Self {
+        let mut authors: Vec<(String, String)> = star.authors.iter().map(|(n, e)| (n.clone(), e.clone())).collect();
+        authors.sort();
+        let mut files: Vec<SbomFile> = star
+            .checksums
+            .iter()
+            .flatten()
+            .map(|(path, sum)| SbomFile {
+                path: format!("/{}", path.trim_start_matches('/')),
+                sha256: Some(sum.clone()),
+                sha1: None,
+            })
+            .collect();
+        files.sort();
+        let mut dependencies: Vec<String> = star.dependencies.iter().flatten().map(|(name, _)| name.clone()).collect();
+        dependencies.sort();
+
+        Component {
+            name: star.name.clone(),
+            version: Some(star.version.clone()),
+            description: star.description.clone(),
+            license: star.license.clone(),
+            authors,
+            sources: star.sources.iter().flatten().map(|s| (s.url.clone(), s.sha256.clone())).collect(),
+            files,
+            dependencies,
+        }
+    }
+
+    fn bom_ref(&self) -> String {
+        match &self.version {
+            Some(version) => format!("{}@{}", self.name, version),
+            None => self.name.clone(),
+        }
+    }
+}
+
+/// An RFC 3339 UTC timestamp: `SOURCE_DATE_EPOCH` if set, else now.
+fn created_at() -> String {
+    let epoch = std::env::var("SOURCE_DATE_EPOCH").ok().and_then(|value| value.trim().parse().ok());
+    let time = epoch.and_then(|secs| Utc.timestamp_opt(secs, 0).single()).unwrap_or_else(Utc::now);
+    time.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+/// A digest of everything in the document, for identifiers that only change with it.
+fn digest(name: &str, created: &str, components: &[Component]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(name);
+    hasher.update(created);
+    for component in components {
+        hasher.update(component.bom_ref());
+        for file in &component.files {
+            hasher.update(&file.path);
+            hasher.update(file.sha256.as_deref().unwrap_or_default());
+        }
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// Characters SPDX allows in an element ID.
+fn spdx_id(kind: &str, name: &str) -> String {
+    let name: String = name.chars().map(|c| if c.is_ascii_alphanumeric() || c == '.' { c } else { '-' }).collect();
+    format!("SPDXRef-{}-{}", kind, name)
+}
+
+/// The SPDX package verification code: the SHA1 of the files' sorted,
+/// concatenated SHA1 digests.
+fn verification_code(sha1s: &[&str]) -> String {
+    let mut sums: Vec<String> = sha1s.iter().map(|sum| sum.to_lowercase()).collect();
+    sums.sort();
+    format!("{:x}", Sha1::digest(sums.concat()))
+}
+
+fn spdx(name: &str, tool: &str, created: &str, components: &[Component]) -> Value {
+    let mut packages = vec![];
+    let mut files = vec![];
+    let mut relationships = vec![];
+
+    for component in components {
+        let id = spdx_id("Package", &component.name);
+        let mut package = Map::new();
+        package.insert("SPDXID".into(), json!(id));
+        package.insert("name".into(), json!(component.name));
+        if let Some(version) = &component.version {
+            package.insert("versionInfo".into(), json!(version));
+            relationships.push(json!({
+                "spdxElementId": "SPDXRef-DOCUMENT",
+                "relationshipType": "DESCRIBES",
+                "relatedSpdxElement": id,
+            }));
+        }
+        let supplier = component
+            .authors
+            .first()
+            .map_or("NOASSERTION".to_string(), |(name, email)| format!("Person: {} ({})", name, email));
+        package.insert("supplier".into(), json!(supplier));
+        let download = component.sources.first().map_or("NOASSERTION", |(url, _)| url.as_str());
+        package.insert("downloadLocation".into(), json!(download));
+        // SPDX wants a SHA1 for every file of an analyzed package, so files
+        // known only by sha256 are left out rather than listed incompletely
+        let sha1s: Option<Vec<&str>> = component.files.iter().map(|file| file.sha1.as_deref()).collect();
+        let analyzed = sha1s.filter(|sums| !sums.is_empty());
+        package.insert("filesAnalyzed".into(), json!(analyzed.is_some()));
+        if let Some(sums) = &analyzed {
+            package.insert("packageVerificationCode".into(), json!({ "packageVerificationCodeValue": verification_code(sums) }));
+        }
+        package.insert("licenseConcluded".into(), json!("NOASSERTION"));
+        package.insert("licenseDeclared".into(), json!(component.license.as_deref().unwrap_or("NOASSERTION")));
+        package.insert("copyrightText".into(), json!("NOASSERTION"));
+        if let Some(description) = &component.description {
+            package.insert("description".into(), json!(description));
+        }
+        packages.push(Value::Object(package));
+
+        for (n, (url, sha256)) in component.sources.iter().enumerate() {
+            let source_id = spdx_id("Source", &format!("{}-{}", component.name, n + 1));
+            let checksums: Vec<Value> = sha256.iter().map(|sum| json!({ "algorithm": "SHA256", "checksumValue": sum })).collect();
+            packages.push(json!({
+                "SPDXID": source_id,
+                "name": url.trim_end_matches('/').rsplit('/').next().unwrap_or(url),
+                "downloadLocation": url,
+                "filesAnalyzed": false,
+                "checksums": checksums,
+                "licenseConcluded": "NOASSERTION",
+                "licenseDeclared": "NOASSERTION",
+                "copyrightText": "NOASSERTION",
+            }));
+            relationships.push(json!({ "spdxElementId": id, "relationshipType": "GENERATED_FROM", "relatedSpdxElement": source_id }));
+        }
+
+        let analyzed_files = if analyzed.is_some() { component.files.as_slice() } else { &[] };
+        for (n, file) in analyzed_files.iter().enumerate() {
+            let file_id = spdx_id("File", &format!("{}-{}", component.name, n + 1));
+            let checksums: Vec<Value> = [("SHA1", &file.sha1), ("SHA256", &file.sha256)]
+                .into_iter()
+                .filter_map(|(algorithm, sum)| sum.as_ref().map(|sum| json!({ "algorithm": algorithm, "checksumValue": sum })))
+                .collect();
+            files.push(json!({
+                "SPDXID": file_id,
+                "fileName": format!(".{}", file.path),
+                "checksums": checksums,
+                "licenseConcluded": "NOASSERTION",
+                "copyrightText": "NOASSERTION",
+            }));
+            relationships.push(json!({ "spdxElementId": id, "relationshipType": "CONTAINS", "relatedSpdxElement": file_id }));
+        }
+
+        for dependency in &component.dependencies {
+            if components.iter().any(|c| &c.name == dependency) {
+                relationships.push(json!({
+                    "spdxElementId": id,
+                    "relationshipType": "DEPENDS_ON",
+                    "relatedSpdxElement": spdx_id("Package", dependency),
+                }));
+            }
+        }
+    }
+
+    json!({
+        "spdxVersion": "SPDX-2.3",
+        "dataLicense": "CC0-1.0",
+        "SPDXID": "SPDXRef-DOCUMENT",
+        "name": name,
+        "documentNamespace": format!("https://cosmos-pkg.org/spdx/{}-{}", name, &digest(name, created, components)[..16]),
+        "creationInfo": { "created": created, "creators": [format!("Tool: {}", tool)] },
+        "packages": packages,
+        "files": files,
+        "relationships": relationships,
+    })
+}
+
+fn cyclonedx(name: &str, tool: &str, created: &str, components: &[Component]) -> Value {
+    let hashes = |sha256: &Option<String>| -> Vec<Value> { sha256.iter().map(|sum| json!({ "alg": "SHA-256", "content": sum })).collect() };
+    let file_hashes = |file: &SbomFile| -> Vec<Value> {
+        [("SHA-1", &file.sha1), ("SHA-256", &file.sha256)]
+            .into_iter()
+            .filter_map(|(alg, sum)| sum.as_ref().map(|sum| json!({ "alg": alg, "content": sum })))
+            .collect()
+    };
+
+    let mut entries = vec![];
+    let mut dependencies = vec![];
+    for component in components {
+        let mut entry = Map::new();
+        entry.insert("type".into(), json!("application"));
+        entry.insert("bom-ref".into(), json!(component.bom_ref()));
+        entry.insert("name".into(), json!(component.name));
+        if let Some(version) = &component.version {
+            entry.insert("version".into(), json!(version));
+        }
+        if let Some(description) = &component.description {
+            entry.insert("description".into(), json!(description));
+        }
+        if !component.authors.is_empty() {
+            let authors: Vec<String> = component.authors.iter().map(|(name, email)| format!("{} <{}>", name, email)).collect();
+            entry.insert("author".into(), json!(authors.join(", ")));
+        }
+        if let Some(license) = &component.license {
+            entry.insert("licenses".into(), json!([{ "expression": license }]));
+        }
+        if !component.sources.is_empty() {
+            let references: Vec<Value> = component
+                .sources
+                .iter()
+                .map(|(url, sha256)| json!({ "type": "distribution", "url": url, "hashes": hashes(sha256) }))
+                .collect();
+            entry.insert("externalReferences".into(), json!(references));
+        }
+        if !component.files.is_empty() {
+            let files: Vec<Value> = component
+                .files
+                .iter()
+                .map(|file| {
+                    json!({
+                        "type": "file",
+                        "bom-ref": format!("{}:{}", component.bom_ref(), file.path),
+                        "name": file.path,
+                        "hashes": file_hashes(file),
+                    })
+                })
+                .collect();
+            entry.insert("components".into(), json!(files));
+        }
+        entries.push(Value::Object(entry));
+
+        let depends_on: Vec<String> = component
+            .dependencies
+            .iter()
+            .filter_map(|dependency| components.iter().find(|c| &c.name == dependency))
+            .map(Component::bom_ref)
+            .collect();
+        dependencies.push(json!({ "ref": component.bom_ref(), "dependsOn": depends_on }));
+    }
+
+    let id = digest(name, created, components);
+    let (tool_name, tool_version) = tool.split_once('-').unwrap_or((tool, ""));
+    json!({
+        "bomFormat": "CycloneDX",
+        "specVersion": "1.5",
+        "serialNumber": format!("urn:uuid:{}-{}-4{}-8{}-{}", &id[..8], &id[8..12], &id[13..16], &id[17..20], &id[20..32]),
+        "version": 1,
+        "metadata": {
+            "timestamp": created,
+            "tools": { "components": [{ "type": "application", "name": tool_name, "version": tool_version }] },
+            "component": { "type": "application", "bom-ref": "document", "name": name },
+        },
+        "components": entries,
+        "dependencies": dependencies,
+    })
+}
+
+/// Render `components` as a JSON SBOM document called `name`, created by
+/// `tool` (`name-version`). The creation time is `SOURCE_DATE_EPOCH` when
+/// set, so the same inputs give the same document.
+pub fn render(name: &str, tool: &str, components: &[Component], format: SbomFormat) -> String {
+    let created = created_at();
+    let document = match format {
+        SbomFormat::Spdx => spdx(name, tool, &created, components),
+        SbomFormat::CycloneDx => cyclonedx(name, tool, &created, components),
+    };
+    // a Value always serializes
+    serde_json::to_string_pretty(&document).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CREATED: &str = "2025-01-01T00:00:00Z";
+
+    fn component(files: Vec<SbomFile>) -> Component {
+        Component {
+            name: "hello".to_string(),
+            version: Some("1.0.0".to_string()),
+            license: Some("MIT".to_string()),
+            authors: vec![("Ada".to_string(), "ada@example.org".to_string())],
+            sources: vec![("https://example.org/hello-1.0.0.tar.gz".to_string(), Some("ab".repeat(32)))],
+            files,
+            dependencies: vec!["musl".to_string()],
+            ..Component::default()
+        }
+    }
+
+    fn musl() -> Component {
+        Component { name: "musl".to_string(), ..Component::default() }
+    }
+
+    fn hashed(path: &str, content: &str) -> SbomFile {
+        let temp = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(temp.path(), content).unwrap();
+        SbomFile::hash(path, temp.path()).unwrap()
+    }
+
+    #[test]
+    fn files_are_hashed_with_both_digests() {
+        let file = hashed("/usr/bin/hello", "hello\n");
+        assert_eq!(file.sha256.as_deref(), Some("5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03"));
+        assert_eq!(file.sha1.as_deref(), Some("f572d396fae9206628714fb2ce00f72e94f2258f"));
+    }
+
+    #[test]
+    fn spdx_analyzes_packages_whose_files_have_sha1() {
+        let files = vec![hashed("/usr/bin/hello", "hello\n"), hashed("/usr/share/doc/hello", "doc\n")];
+        let mut sums: Vec<&str> = files.iter().map(|f| f.sha1.as_deref().unwrap()).collect();
+        sums.sort();
+        let expected_code = format!("{:x}", Sha1::digest(sums.concat()));
+
+        let document = spdx("hello-1.0.0", "stellar-1.0.1", CREATED, &[component(files), musl()]);
+        let package = &document["packages"][0];
+        assert_eq!(package["filesAnalyzed"], json!(true));
+        assert_eq!(package["packageVerificationCode"]["packageVerificationCodeValue"], json!(expected_code));
+
+        let files = document["files"].as_array().unwrap();
+        assert_eq!(files.len(), 2);
+        let algorithms: Vec<&Value> = files[0]["checksums"].as_array().unwrap().iter().map(|c| &c["algorithm"]).collect();
+        assert_eq!(algorithms, vec!["SHA1", "SHA256"]);
+        assert_eq!(files[0]["fileName"], json!("./usr/bin/hello"));
+
+        let kinds: Vec<&str> = document["relationships"].as_array().unwrap().iter().map(|r| r["relationshipType"].as_str().unwrap()).collect();
+        assert_eq!(kinds.iter().filter(|k| **k == "CONTAINS").count(), 2);
+        assert!(kinds.contains(&"DEPENDS_ON") && kinds.contains(&"GENERATED_FROM") && kinds.contains(&"DESCRIBES"));
+    }
+
+    #[test]
+    fn spdx_leaves_out_files_without_sha1() {
+        let file = SbomFile { path: "/usr/bin/hello".to_string(), sha256: Some("cd".repeat(32)), sha1: None };
+        let document = spdx("hello-1.0.0", "stellar-1.0.1", CREATED, &[component(vec![file])]);
+        assert_eq!(document["packages"][0]["filesAnalyzed"], json!(false));
+        assert!(document["packages"][0].get("packageVerificationCode").is_none());
+        assert!(document["files"].as_array().unwrap().is_empty());
+    }
+
+    #[test]
+    fn cyclonedx_lists_components_files_and_dependencies() {
+        let document = cyclonedx("hello-1.0.0", "stellar-1.0.1", CREATED, &[component(vec![hashed("/usr/bin/hello", "hello\n")]), musl()]);
+        assert_eq!(document["specVersion"], json!("1.5"));
+        assert_eq!(document["metadata"]["tools"]["components"][0]["version"], json!("1.0.1"));
+
+        let hello = &document["components"][0];
+        assert_eq!(hello["bom-ref"], json!("hello@1.0.0"));
+        assert_eq!(hello["licenses"][0]["expression"], json!("MIT"));
+        assert_eq!(hello["components"][0]["hashes"].as_array().unwrap().len(), 2);
+        assert_eq!(document["dependencies"][0]["dependsOn"], json!(["musl"]));
+
+        let serial = document["serialNumber"].as_str().unwrap();
+        assert!(serial.starts_with("urn:uuid:") && serial.len() == "urn:uuid:".len() + 36);
+    }
+
+    #[test]
+    fn documents_only_change_with_their_inputs() {
+        let components = [component(vec![hashed("/usr/bin/hello", "hello\n")])];
+        let a = spdx("hello-1.0.0", "stellar-1.0.1", CREATED, &components);
+        assert_eq!(a, spdx("hello-1.0.0", "stellar-1.0.1", CREATED, &components));
+        let changed = [component(vec![hashed("/usr/bin/hello", "hello, world\n")])];
+        assert_ne!(a["documentNamespace"], spdx("hello-1.0.0", "stellar-1.0.1", CREATED, &changed)["documentNamespace"]);
+    }
+
+    #[test]
+    fn render_writes_json_in_either_format() {
+        for format in ["spdx", "cyclonedx"] {
+            let document = render("hello-1.0.0", "stellar-1.0.1", &[component(vec![])], format.parse().unwrap());
+            let value: Value = serde_json::from_str(&document).unwrap();
+            assert!(value.get("spdxVersion").is_some() || value.get("bomFormat").is_some());
+        }
+        assert!("cdx".parse::<SbomFormat>().is_ok());
+        assert!("swid".parse::<SbomFormat>().is_err());
+    }
+}
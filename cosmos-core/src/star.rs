@@ -20,14 +20,182 @@ pub struct Star {
     pub dependencies: Option<HashMap<String, String>>,
     pub install_script: Option<String>,
     pub source: Option<String>,
-    pub checksums: Option<HashMap<String, String>>
+    pub checksums: Option<HashMap<String, String>>,
+    pub users: Option<Vec<StarUser>>,
+    pub groups: Option<Vec<StarGroup>>,
+    pub directories: Option<Vec<StarDirectory>>,
+    pub sources: Option<Vec<StarSource>>,
+    pub provides: Option<Vec<String>>, // shared libraries shipped, filled in by `stellar build-star`
+    #[serde(rename = "static")]
+    pub static_linked: Option<bool>,
+    pub split: Option<Vec<StarSplit>>,
+    pub postprocess: Option<StarPostProcess>,
+}
+
+/// A `[[sources]]` entry: an upstream file fetched, verified and unpacked
+/// into the build directory by `stellar build-star`.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct StarSource {
+    pub url: String,
+    pub sha256: Option<String>,
+    pub filename: Option<String>, // defaults to the last segment of the URL
+    pub extract: Option<bool>,    // defaults to true for archives
+}
+
+impl StarSource {
+    pub fn filename(&self) -> String {
+        self.filename.clone().unwrap_or_else(|| {
+            self.url
+                .trim_end_matches('/')
+                .rsplit('/')
+                .next()
+                .unwrap_or("source")
+                .to_string()
+        })
+    }
+
+    pub fn should_extract(&self) -> bool {
+        self.extract.unwrap_or_else(|| {
+            let name = self.filename();
+            [".tar", ".tar.gz", ".tgz", ".tar.xz", ".txz", ".tar.zst", ".tzst", ".zip"]
+                .iter()
+                .any(|ext| name.ends_with(ext))
+        })
+    }
+}
+
+/// A `[[split]]` entry: another star packaged from the same build, taking the
+/// staged files that match its globs.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct StarSplit {
+    pub name: String,
+    pub description: Option<String>,
+    pub dependencies: Option<HashMap<String, String>>,
+    pub files: Vec<String>, // globs over install paths, e.g. "/usr/include/**"
+}
+
+/// The `[postprocess]` table: what `stellar build-star` does to the staged
+/// files before packaging them. Every step but `debug` is on by default once
+/// the table is present; stars without it are packaged as staged.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct StarPostProcess {
+    pub strip: Option<bool>,           // strip ELF files with `--strip-unneeded`
+    pub debug: Option<bool>,           // keep the stripped debug info in a `<name>-debug` star
+    pub compress_man: Option<bool>,    // gzip man pages
+    pub remove_la: Option<bool>,       // drop libtool `.la` archives
+    pub fix_permissions: Option<bool>, // clear group/world write, make executables executable
+}
+
+impl StarPostProcess {
+    pub fn strip(&self) -> bool {
+        self.strip.unwrap_or(true)
+    }
+
+    pub fn debug(&self) -> bool {
+        self.strip() && self.debug.unwrap_or(false)
+    }
+
+    pub fn compress_man(&self) -> bool {
+        self.compress_man.unwrap_or(true)
+    }
+
+    pub fn remove_la(&self) -> bool {
+        self.remove_la.unwrap_or(true)
+    }
+
+    pub fn fix_permissions(&self) -> bool {
+        self.fix_permissions.unwrap_or(true)
+    }
+}
+
+/// A `[[users]]` entry: a system user created before files are installed.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct StarUser {
+    pub name: String,
+    pub uid: Option<u32>,
+    pub group: Option<String>,
+    pub home: Option<String>,
+    pub shell: Option<String>,
+    pub gecos: Option<String>,
+    pub groups: Option<Vec<String>>, // supplementary groups
+}
+
+/// A `[[groups]]` entry: a system group created before files are installed.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct StarGroup {
+    pub name: String,
+    pub gid: Option<u32>,
+}
+
+/// A `[[directories]]` entry: a directory created with the given mode and owner.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct StarDirectory {
+    pub path: String,
+    #[serde(default, with = "octal_mode", skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>,     // an octal string such as "0750"
+    pub owner: Option<String>, // "user" or "user:group"
+}
+
+/// Modes are written as octal strings, since a decimal `755` in TOML would
+/// silently mean 0o1363. TOML integers are refused: once parsed, `755` and
+/// `0o755` can no longer be told apart.
+mod octal_mode {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Octal(String),
+        Number(u32),
+    }
+
+    fn parse(value: &str) -> Result<u32, String> {
+        let digits = value.trim().trim_start_matches("0o");
+        let mode = u32::from_str_radix(digits, 8).map_err(|_| format!("Invalid mode '{}'. Expected octal, e.g. \"0755\"", value))?;
+        check(mode)
+    }
+
+    fn check(mode: u32) -> Result<u32, String> {
+        if mode > 0o7777 {
+            return Err(format!("Invalid mode {:o}. Modes cannot exceed 7777", mode));
+        }
+        Ok(mode)
+    }
+
+    pub fn serialize<S: Serializer>(mode: &Option<u32>, serializer: S) -> Result<S::Ok, S::Error> {
+        match mode {
+            Some(mode) => serializer.serialize_str(&format!("{:04o}", mode)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u32>, D::Error> {
+        match Option::<Raw>::deserialize(deserializer)? {
+            Some(Raw::Octal(value)) => parse(&value).map(Some).map_err(de::Error::custom),
+            Some(Raw::Number(mode)) => Err(de::Error::custom(format!(
+                "Invalid mode {}. Write modes as octal strings, e.g. \"0755\"",
+                mode
+            ))),
+            None => Ok(None),
+        }
+    }
+}
+
+impl StarDirectory {
+    /// Split `owner` into its user and optional group parts.
+    pub fn owner_parts(&self) -> Option<(&str, Option<&str>)> {
+        self.owner.as_deref().map(|owner| match owner.split_once(':') {
+            Some((user, group)) => (user, Some(group)),
+            None => (owner, None),
+        })
+    }
 }
 
 impl Star {
     pub fn from_file(path: &str) -> Result<Self, std::io::Error> {
         let content = std::fs::read_to_string(path)?;
         let parsed: Self = toml::from_str(&content)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+            .map_err(std::io::Error::other)?;
         Ok(parsed)
     }
 
@@ -159,4 +327,39 @@ pub fn fetch_star(
     fs::write(&star_path, &content)?;
 
     Ok(toml::from_str(&content)?)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn directory(toml_str: &str) -> Result<StarDirectory, toml::de::Error> {
+        toml::from_str(toml_str)
+    }
+
+    #[test]
+    fn directory_modes_are_octal() {
+        assert_eq!(directory("path = \"/srv\"\nmode = \"0750\"").unwrap().mode, Some(0o750));
+        assert_eq!(directory("path = \"/srv\"\nmode = \"1777\"").unwrap().mode, Some(0o1777));
+        assert_eq!(directory("path = \"/srv\"").unwrap().mode, None);
+    }
+
+    #[test]
+    fn directory_modes_out_of_range_are_rejected() {
+        assert!(directory("path = \"/srv\"\nmode = \"0789\"").is_err());
+        assert!(directory("path = \"/srv\"\nmode = \"17777\"").is_err());
+    }
+
+    #[test]
+    fn directory_modes_must_be_strings() {
+        assert!(directory("path = \"/srv\"\nmode = 755").is_err());
+        assert!(directory("path = \"/srv\"\nmode = 0o755").is_err());
+        assert!(directory("path = \"/srv\"\nmode = 8000").is_err());
+    }
+
+    #[test]
+    fn directory_modes_serialize_as_octal_strings() {
+        let dir = StarDirectory { path: "/srv".into(), mode: Some(0o750), owner: None };
+        assert_eq!(toml::to_string(&dir).unwrap(), "path = \"/srv\"\nmode = \"0750\"\n");
+    }
+}
@@ -1,11 +1,29 @@
-use cosmos_universe::{Universe, InstalledStar};
+use cosmos_universe::{Universe, InstalledStar, InstalledDirectory, InstalledConfigFile};
+use nova::InstallRecord;
 use crate::star::Star;
 
-pub fn record_install(universe: &mut Universe, star: &Star, files: Vec<String>) {
+pub fn record_install(universe: &mut Universe, star: &Star, record: InstallRecord) {
+    let directories = star.directories
+        .as_ref()
+        .map(|dirs| dirs.iter().map(|dir| InstalledDirectory {
+            path: dir.path.clone(),
+            mode: dir.mode,
+            owner: dir.owner.clone(),
+        }).collect())
+        .unwrap_or_default();
+
     let installed = InstalledStar {
         name: star.name.clone(),
         version: star.version.clone(),
-        files,
+        files: record.files,
+        users: record.users,
+        groups: record.groups,
+        directories,
+        service_links: record.service_links,
+        config_files: record.config_files
+            .into_iter()
+            .map(|config| InstalledConfigFile { path: config.path, checksum: config.checksum })
+            .collect(),
     };
     universe.installed.insert(star.name.clone(), installed);
 }
@@ -2,7 +2,7 @@ use crate::TransportError;
 use std::io::Read;
 
 pub fn pull(url: &str) -> Result<Vec<u8>, TransportError> {
-    let response = ureq::get(&url).call()
+    let response = ureq::get(url).call()
         .map_err(|e| TransportError::DownloadFailed(format!("{}: {}", url, e)))?;
 
     if response.status() != 200 {
@@ -12,9 +12,9 @@ pub fn pull(url: &str) -> Result<Vec<u8>, TransportError> {
         )))?;
     }
 
-    let bytes = response.into_reader().bytes()
-        .collect::<Result<Vec<_>, _>>()
+    let mut bytes = Vec::new();
+    response.into_reader().read_to_end(&mut bytes)
         .map_err(|e| TransportError::DownloadFailed(format!("Failed to read response: {}", e)))?;
 
     Ok(bytes)
-}
\ No newline at end of file
+}
@@ -21,6 +21,34 @@ pub struct InstalledStar {
     pub version: String,
     pub files: Vec<String>,
     pub name: String,
+    /// System users created by the star's install script
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub users: Vec<String>,
+    /// System groups created by the star's install script
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<String>,
+    /// Directories declared in the star's `[[directories]]` table
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub directories: Vec<InstalledDirectory>,
+    /// Init system links or markers created by `service_enable`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub service_links: Vec<String>,
+    /// Config files rendered by `template`, with the checksum they were installed with
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub config_files: Vec<InstalledConfigFile>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct InstalledDirectory {
+    pub path: String,
+    pub mode: Option<u32>,
+    pub owner: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct InstalledConfigFile {
+    pub path: String,
+    pub checksum: String,
 }
 
 impl Universe {
@@ -35,7 +63,7 @@ impl Universe {
     /// Save the universe to a TOML file
     pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
         let toml = toml::to_string_pretty(self)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+            .map_err(std::io::Error::other)?;
         fs::write(path, toml)?;
         Ok(())
     }
@@ -53,6 +81,11 @@ impl Universe {
                 name: name.to_string(),
                 version: version.to_string(),
                 files,
+                users: vec![],
+                groups: vec![],
+                directories: vec![],
+                service_links: vec![],
+                config_files: vec![],
             },
         );
     }
@@ -0,0 +1,172 @@
+use flate2::read::GzDecoder;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use tar::Archive;
+
+/// Archive formats `extract` understands, told apart by their magic bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Gzip,
+    Xz,
+    Zstd,
+    Zip,
+    Tar,
+}
+
+fn detect(path: &Path) -> io::Result<Format> {
+    let mut magic = [0u8; 6];
+    let read = File::open(path)?.read(&mut magic)?;
+    let magic = &magic[..read];
+
+    Ok(if magic.starts_with(&[0x1f, 0x8b]) {
+        Format::Gzip
+    } else if magic.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+        Format::Xz
+    } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        Format::Zstd
+    } else if magic.starts_with(b"PK\x03\x04") {
+        Format::Zip
+    } else {
+        Format::Tar
+    })
+}
+
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut hasher = Sha256::new();
+    io::copy(&mut reader, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Unpack `archive` into `dest`, returning how many entries were written.
+/// Entries that would land outside `dest` are refused.
+pub fn extract(archive: &Path, dest: &Path) -> io::Result<usize> {
+    fs::create_dir_all(dest)?;
+    let file = BufReader::new(File::open(archive)?);
+
+    match detect(archive)? {
+        Format::Gzip => unpack_tar(GzDecoder::new(file), dest),
+        Format::Xz => unpack_xz(file, dest),
+        Format::Zstd => {
+            let decoder = ruzstd::decoding::StreamingDecoder::new(file)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("zstd: {}", e)))?;
+            unpack_tar(decoder, dest)
+        }
+        Format::Zip => unpack_zip(File::open(archive)?, dest),
+        Format::Tar => unpack_tar(file, dest),
+    }
+}
+
+/// lzma-rs only decompresses into a writer, so it runs on its own thread and
+/// feeds tar through a pipe instead of holding the whole tarball in memory.
+fn unpack_xz<R: io::BufRead + Send + 'static>(mut file: R, dest: &Path) -> io::Result<usize> {
+    let (mut reader, mut writer) = io::pipe()?;
+    let decoder = std::thread::spawn(move || lzma_rs::xz_decompress(&mut file, &mut writer));
+
+    let unpacked = unpack_tar(&mut reader, dest);
+    // tar stops at its end marker; drain the padding so the decoder can finish
+    let drained = io::copy(&mut reader, &mut io::sink());
+    drop(reader);
+    let decoded = decoder
+        .join()
+        .map_err(|_| io::Error::other("xz: decoder panicked"))?
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("xz: {:?}", e)));
+
+    let count = unpacked?;
+    decoded?;
+    drained?;
+    Ok(count)
+}
+
+fn unpack_tar<R: Read>(reader: R, dest: &Path) -> io::Result<usize> {
+    let mut archive = Archive::new(reader);
+    let mut count = 0;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        // unpack_in refuses absolute paths and `..`
+        if entry.unpack_in(dest)? {
+            count += 1;
+        } else {
+            println!("⚠️  Skipped unsafe archive entry: {}", entry.path()?.display());
+        }
+    }
+    Ok(count)
+}
+
+fn unpack_zip(file: File, dest: &Path) -> io::Result<usize> {
+    let mut archive = zip::ZipArchive::new(file)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("zip: {}", e)))?;
+    let mut count = 0;
+
+    for i in 0..archive.len() {
+        let mut entry = archive
+            .by_index(i)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("zip: {}", e)))?;
+        let Some(relative) = entry.enclosed_name() else {
+            println!("⚠️  Skipped unsafe archive entry: {}", entry.name());
+            continue;
+        };
+        let target = dest.join(relative);
+
+        if entry.is_dir() {
+            fs::create_dir_all(&target)?;
+        } else {
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            io::copy(&mut entry, &mut File::create(&target)?)?;
+            if let Some(mode) = entry.unix_mode() {
+                fs::set_permissions(&target, fs::Permissions::from_mode(mode & 0o7777))?;
+            }
+        }
+        count += 1;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tar_xz(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(vec![]);
+        for (path, data) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, path, *data).unwrap();
+        }
+        let tarball = builder.into_inner().unwrap();
+        let mut compressed = vec![];
+        lzma_rs::xz_compress(&mut tarball.as_slice(), &mut compressed).unwrap();
+        compressed
+    }
+
+    #[test]
+    fn extracts_xz_through_a_pipe() {
+        let temp = tempfile::tempdir().unwrap();
+        let big = vec![b'x'; 1 << 20];
+        let archive = temp.path().join("src.tar.xz");
+        fs::write(&archive, tar_xz(&[("src/README", b"hello"), ("src/big", &big)])).unwrap();
+
+        let dest = temp.path().join("out");
+        assert_eq!(extract(&archive, &dest).unwrap(), 2);
+        assert_eq!(fs::read(dest.join("src/README")).unwrap(), b"hello");
+        assert_eq!(fs::read(dest.join("src/big")).unwrap().len(), big.len());
+    }
+
+    #[test]
+    fn corrupt_xz_is_an_error() {
+        let temp = tempfile::tempdir().unwrap();
+        let mut data = tar_xz(&[("a", b"a")]);
+        let len = data.len();
+        data.truncate(len - 8);
+        let archive = temp.path().join("bad.tar.xz");
+        fs::write(&archive, data).unwrap();
+        assert!(extract(&archive, &temp.path().join("out")).is_err());
+    }
+}
@@ -0,0 +1,220 @@
+use mlua::{Error as LuaError, Value};
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum NovaError {
+    Io(io::Error),
+    Lua(LuaError),
+    MissingEntryFunction(String),
+    Script(Box<ScriptError>),
+}
+
+/// A failure inside a Nova script, located as precisely as we can manage.
+#[derive(Debug, Clone, Default)]
+pub struct ScriptError {
+    pub script: String,
+    pub line: Option<u32>,
+    pub builtin: Option<String>,
+    pub args: Vec<String>,
+    pub message: String,
+    pub stdout: Option<String>,
+    pub stderr: Option<String>,
+}
+
+/// Raised by `run()` when a command exits unsuccessfully.
+#[derive(Debug)]
+pub struct CommandFailed {
+    pub command: Vec<String>,
+    pub code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Attached to errors coming out of a builtin so the failing call and its
+/// arguments survive the trip back through Lua.
+#[derive(Debug)]
+pub struct BuiltinError {
+    pub builtin: String,
+    pub args: Vec<String>,
+    pub line: Option<u32>,
+    pub cause: LuaError,
+}
+
+impl fmt::Display for CommandFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.code {
+            Some(code) => write!(f, "Command {:?} exited with status {}", self.command, code),
+            None => write!(f, "Command {:?} was terminated by a signal", self.command),
+        }
+    }
+}
+
+impl std::error::Error for CommandFailed {}
+
+impl fmt::Display for BuiltinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}({}): {}", self.builtin, self.args.join(", "), root_message(&self.cause))
+    }
+}
+
+impl std::error::Error for BuiltinError {}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => writeln!(f, "{}:{}", self.script, line)?,
+            None => writeln!(f, "{}", self.script)?,
+        }
+        if let Some(builtin) = &self.builtin {
+            writeln!(f, "  in {}({})", builtin, self.args.join(", "))?;
+        }
+        write!(f, "  error: {}", self.message)?;
+        for (label, output) in [("stdout", &self.stdout), ("stderr", &self.stderr)] {
+            if let Some(output) = output.as_deref().map(str::trim_end).filter(|o| !o.is_empty()) {
+                write!(f, "\n  {}:", label)?;
+                for line in output.lines() {
+                    write!(f, "\n    | {}", line)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for NovaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NovaError::Io(e) => write!(f, "IO error: {}", e),
+            NovaError::Lua(e) => write!(f, "Lua error: {}", e),
+            NovaError::MissingEntryFunction(name) => write!(f, "Script does not define a {}() function", name),
+            NovaError::Script(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for NovaError {}
+
+impl From<io::Error> for NovaError {
+    fn from(e: io::Error) -> Self {
+        NovaError::Io(e)
+    }
+}
+
+impl From<LuaError> for NovaError {
+    fn from(e: LuaError) -> Self {
+        NovaError::Lua(e)
+    }
+}
+
+/// Render a builtin argument the way it would appear in the script.
+pub(crate) fn describe_value(value: &Value) -> String {
+    match value {
+        Value::Nil => "nil".to_string(),
+        Value::Boolean(b) => b.to_string(),
+        Value::Integer(i) => i.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => format!("{:?}", s.to_string_lossy()),
+        Value::Table(_) => "{...}".to_string(),
+        other => other.type_name().to_string(),
+    }
+}
+
+/// Peel callback wrappers off a Lua error to get at what actually went wrong.
+fn innermost(err: &LuaError) -> &LuaError {
+    match err {
+        LuaError::CallbackError { cause, .. } => innermost(cause),
+        LuaError::WithContext { cause, .. } => innermost(cause),
+        other => other,
+    }
+}
+
+fn root_message(err: &LuaError) -> String {
+    match innermost(err) {
+        LuaError::RuntimeError(msg) => msg
+            .split("\nstack traceback:")
+            .next()
+            .unwrap_or(msg)
+            .to_string(),
+        LuaError::ExternalError(e) => {
+            if let Some(builtin) = e.downcast_ref::<BuiltinError>() {
+                root_message(&builtin.cause)
+            } else {
+                e.to_string()
+            }
+        }
+        other => other.to_string(),
+    }
+}
+
+fn find_external<T: std::error::Error + 'static>(err: &LuaError) -> Option<&T> {
+    match innermost(err) {
+        LuaError::ExternalError(e) => {
+            if let Some(found) = e.downcast_ref::<T>() {
+                return Some(found);
+            }
+            e.downcast_ref::<BuiltinError>().and_then(|b| find_external(&b.cause))
+        }
+        _ => None,
+    }
+}
+
+/// The name scripts are loaded under, so Lua's messages point at the file.
+pub(crate) fn chunk_name(script_path: &str) -> String {
+    Path::new(script_path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| script_path.to_string())
+}
+
+/// Find the first `<script>:<line>:` marker Lua left in a message or traceback.
+fn line_in(text: &str, script: &str) -> Option<u32> {
+    let marker = format!("{}:", script);
+    text.match_indices(&marker).find_map(|(idx, _)| {
+        let rest = &text[idx + marker.len()..];
+        let digits: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+        if rest[digits.len()..].starts_with(':') {
+            digits.parse().ok()
+        } else {
+            None
+        }
+    })
+}
+
+/// Turn an error raised while running `script` into a located [`ScriptError`].
+pub(crate) fn script_error(script: &str, err: LuaError) -> NovaError {
+    let mut located = ScriptError {
+        script: script.to_string(),
+        message: root_message(&err),
+        ..Default::default()
+    };
+
+    if let Some(builtin) = find_external::<BuiltinError>(&err) {
+        located.builtin = Some(builtin.builtin.clone());
+        located.args = builtin.args.clone();
+        located.line = builtin.line;
+    }
+
+    if let Some(failed) = find_external::<CommandFailed>(&err) {
+        located.stdout = Some(failed.stdout.clone());
+        located.stderr = Some(failed.stderr.clone());
+    }
+
+    if located.line.is_none() {
+        let chunk = chunk_name(script);
+        located.line = match &err {
+            LuaError::CallbackError { traceback, .. } => line_in(traceback, &chunk),
+            _ => None,
+        }
+        .or_else(|| line_in(&err.to_string(), &chunk));
+    }
+
+    // the location is already in the header, don't repeat it in the message
+    if let Some(line) = located.line {
+        let prefix = format!("{}:{}: ", chunk_name(script), line);
+        located.message = located.message.replacen(&prefix, "", 1);
+    }
+
+    NovaError::Script(Box::new(located))
+}
@@ -0,0 +1,98 @@
+use mlua::{Error as LuaError, Function, Lua, Table, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const LOADED_KEY: &str = "nova.loaded";
+
+/// A module the runtime is allowed to load, with the checksum it must match.
+#[derive(Debug, Clone)]
+pub(crate) struct Module {
+    pub path: PathBuf,
+    pub checksum: Option<String>,
+}
+
+/// Where `require` may look. Directories are searched first, in order, then
+/// the explicitly registered modules. The host `package.path` never is.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ModuleSearch {
+    pub dirs: Vec<PathBuf>,
+    pub modules: HashMap<String, Module>,
+}
+
+/// Module names are dotted identifiers: `cosmos.users` lives at `cosmos/users.lua`.
+pub fn is_valid_module_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.split('.').all(|part| {
+            !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        })
+}
+
+/// Relative path of a module inside a `nova-lib/` directory.
+pub fn module_file(name: &str) -> PathBuf {
+    PathBuf::from(format!("{}.lua", name.replace('.', "/")))
+}
+
+impl ModuleSearch {
+    fn find(&self, name: &str) -> Option<Module> {
+        let relative = module_file(name);
+        self.dirs
+            .iter()
+            .map(|dir| dir.join(&relative))
+            .find(|path| path.is_file())
+            .map(|path| Module { path, checksum: None })
+            .or_else(|| self.modules.get(name).cloned())
+    }
+
+    fn load(&self, lua: &Lua, name: &str) -> Result<Value, LuaError> {
+        if !is_valid_module_name(name) {
+            return Err(LuaError::external(format!("Invalid module name: {:?}", name)));
+        }
+
+        let module = self.find(name).ok_or_else(|| {
+            LuaError::external(format!("Module '{}' not found in nova-lib", name))
+        })?;
+
+        let source = fs::read(&module.path)?;
+        if let Some(expected) = &module.checksum {
+            let actual = format!("{:x}", Sha256::digest(&source));
+            if !actual.eq_ignore_ascii_case(expected) {
+                return Err(LuaError::external(format!(
+                    "Checksum mismatch for module '{}': expected {}, got {}",
+                    name, expected, actual
+                )));
+            }
+        }
+
+        let chunk_name = format!("@nova-lib/{}", module_file(name).display());
+        let value: Value = lua.load(source).set_name(chunk_name).call(name)?;
+        Ok(match value {
+            Value::Nil => Value::Boolean(true),
+            other => other,
+        })
+    }
+}
+
+/// Replace `require` with one that only sees the runtime's module search.
+pub(crate) fn register_require(lua: &Lua, search: ModuleSearch) -> Result<(), LuaError> {
+    lua.set_named_registry_value(LOADED_KEY, lua.create_table()?)?;
+
+    let require: Function = lua.create_function(move |lua, name: String| {
+        let loaded: Table = lua.named_registry_value(LOADED_KEY)?;
+        if let Some(value) = loaded.get::<Option<Value>>(name.as_str())? {
+            return Ok(value);
+        }
+
+        let value = search.load(lua, &name)?;
+        loaded.set(name.as_str(), value.clone())?;
+        Ok(value)
+    })?;
+
+    lua.globals().set("require", require)
+}
+
+/// The `nova-lib/` directory bundled next to a star's scripts.
+pub(crate) fn bundled_dir(extraction_root: &Path) -> PathBuf {
+    extraction_root.join("nova-lib")
+}
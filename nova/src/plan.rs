@@ -0,0 +1,98 @@
+use std::collections::HashSet;
+use std::fmt;
+
+/// A change a script would make, captured by a dry run instead of performed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operation {
+    Copy { from: String, to: String },
+    Template { from: String, to: String },
+    Symlink { target: String, link: String },
+    Mkdir { path: String },
+    Chmod { path: String, mode: u32 },
+    Chown { path: String, user: Option<String>, group: Option<String> },
+    Run { command: Vec<String> },
+    Extract { archive: String, dest: String },
+    Fetch { url: String, dest: String },
+    AddGroup { name: String },
+    AddUser { name: String },
+    AddUserToGroup { user: String, group: String },
+    ServiceEnable { name: String },
+    ServiceDisable { name: String },
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operation::Copy { from, to } => write!(f, "copy files/{} → {}", from, to),
+            Operation::Template { from, to } => write!(f, "render files/{} → {}", from, to),
+            Operation::Symlink { target, link } => write!(f, "symlink {} → {}", link, target),
+            Operation::Mkdir { path } => write!(f, "mkdir {}", path),
+            Operation::Chmod { path, mode } => write!(f, "chmod {:o} {}", mode, path),
+            Operation::Chown { path, user, group } => write!(
+                f,
+                "chown {}:{} {}",
+                user.as_deref().unwrap_or("-"),
+                group.as_deref().unwrap_or("-"),
+                path
+            ),
+            Operation::Run { command } => write!(f, "run {}", command.join(" ")),
+            Operation::Extract { archive, dest } => write!(f, "extract {} → {}", archive, dest),
+            Operation::Fetch { url, dest } => write!(f, "fetch {} → {}", url, dest),
+            Operation::AddGroup { name } => write!(f, "add group {}", name),
+            Operation::AddUser { name } => write!(f, "add user {}", name),
+            Operation::AddUserToGroup { user, group } => write!(f, "add user {} to group {}", user, group),
+            Operation::ServiceEnable { name } => write!(f, "enable service {}", name),
+            Operation::ServiceDisable { name } => write!(f, "disable service {}", name),
+        }
+    }
+}
+
+/// Operations recorded so far, plus the virtual overlay that lets predicates
+/// such as `exists` see what earlier planned operations would have created.
+#[derive(Debug, Default)]
+pub(crate) struct Plan {
+    pub operations: Vec<Operation>,
+    pub paths: HashSet<String>,
+    pub users: HashSet<String>,
+    pub groups: HashSet<String>,
+}
+
+impl Plan {
+    pub fn push(&mut self, operation: Operation) {
+        match &operation {
+            Operation::Copy { to, .. } | Operation::Template { to, .. } => self.add_path(to),
+            Operation::Symlink { link, .. } => self.add_path(link),
+            Operation::Mkdir { path } => self.add_path(path),
+            Operation::AddGroup { name } => {
+                self.groups.insert(name.clone());
+            }
+            Operation::AddUser { name } => {
+                self.users.insert(name.clone());
+            }
+            _ => {}
+        }
+        self.operations.push(operation);
+    }
+
+    /// Record `path` and every parent directory as present in the overlay.
+    fn add_path(&mut self, path: &str) {
+        let mut current = normalize(path);
+        while !current.is_empty() {
+            self.paths.insert(current.clone());
+            current = match current.rfind('/') {
+                Some(idx) => current[..idx].to_string(),
+                None => String::new(),
+            };
+        }
+    }
+
+    pub fn has_path(&self, path: &str) -> bool {
+        self.paths.contains(&normalize(path))
+    }
+}
+
+fn normalize(path: &str) -> String {
+    format!("/{}", path.trim_start_matches("./").trim_matches('/'))
+        .trim_end_matches('/')
+        .to_string()
+}
@@ -0,0 +1,1050 @@
+use mlua::{FromLuaMulti, Function, IntoLuaMulti, Lua, LuaOptions, MultiValue, StdLib, Table, Value, Error as LuaError};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::rc::Rc;
+use std::thread;
+
+use crate::archive;
+use crate::modules::{self, Module, ModuleSearch};
+use crate::template::{self, ConfigOutcome};
+use crate::error::{chunk_name, describe_value, script_error, CommandFailed, BuiltinError, NovaError};
+use crate::plan::{Operation, Plan};
+use crate::{services, users, ConfigFile, InstallRecord};
+
+/// Groups of builtins a runtime can expose to scripts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// `copy`, `template`, `symlink`, `mkdir`, `chmod`, `chown` and `exists`
+    Fs,
+    /// `run`
+    Process,
+    /// `add_user`, `add_group`, `user_exists` and `add_user_to_group`
+    Users,
+    /// `service_enable` and `service_disable`
+    Services,
+    /// `sha256`, `verify` and `extract`, confined to the extraction root
+    Archive,
+    /// `fetch`, confined to the extraction root. Off unless explicitly enabled.
+    Net,
+    /// Lua's `io` and `package` libraries, `dofile`, `loadfile` and the whole
+    /// of `os`, as in Nova 1.0. Without it scripts only get the side-effect-free
+    /// parts of `os` and can read no files except through `require`.
+    Stdlib,
+}
+
+type Registrar = Box<dyn Fn(&Lua) -> Result<Function, LuaError>>;
+
+/// Builder for a Nova runtime. Cosmos, stellar and embedders all start here:
+///
+/// ```no_run
+/// use nova::{Capability, NovaRuntime};
+/// use std::path::Path;
+///
+/// let record = NovaRuntime::new(Path::new("/tmp/star"), Path::new("/"))
+///     .with_capability(Capability::Users)
+///     .builtin("hello", |_, name: String| Ok(format!("hello {}", name)))
+///     .entry("install")
+///     .run("/tmp/star/install.lua")?;
+/// # Ok::<(), nova::NovaError>(())
+/// ```
+pub struct NovaRuntime {
+    extraction_root: PathBuf,
+    files_dir: PathBuf,
+    install_root: PathBuf,
+    capabilities: HashSet<Capability>,
+    init_system: Option<String>,
+    builtins: Vec<(String, Registrar)>,
+    modules: ModuleSearch,
+    metadata: BTreeMap<String, String>,
+    globals: BTreeMap<String, String>,
+    installed_config: HashMap<String, String>,
+    entry: String,
+    allow_missing_entry: bool,
+    dry_run: bool,
+}
+
+/// A live Lua state built from a [`NovaRuntime`], for callers that need more
+/// than a single script run.
+pub struct NovaSession {
+    lua: Lua,
+    record: Rc<RefCell<InstallRecord>>,
+    plan: Rc<RefCell<Plan>>,
+    entry: String,
+    allow_missing_entry: bool,
+}
+
+impl NovaRuntime {
+    /// A runtime with the `Fs`, `Process` and `Stdlib` capabilities that calls `install()`.
+    /// `require` looks in the star's own `nova-lib/` directory first.
+    pub fn new(extraction_root: &Path, install_root: &Path) -> Self {
+        Self {
+            modules: ModuleSearch {
+                dirs: vec![modules::bundled_dir(extraction_root)],
+                ..Default::default()
+            },
+            metadata: BTreeMap::new(),
+            globals: BTreeMap::new(),
+            installed_config: HashMap::new(),
+            extraction_root: extraction_root.to_path_buf(),
+            files_dir: extraction_root.join("files"),
+            install_root: install_root.to_path_buf(),
+            capabilities: [Capability::Fs, Capability::Process, Capability::Stdlib].into_iter().collect(),
+            init_system: None,
+            builtins: vec![],
+            entry: "install".to_string(),
+            allow_missing_entry: false,
+            dry_run: false,
+        }
+    }
+
+    pub fn with_capability(mut self, capability: Capability) -> Self {
+        self.capabilities.insert(capability);
+        self
+    }
+
+    pub fn without_capability(mut self, capability: Capability) -> Self {
+        self.capabilities.remove(&capability);
+        self
+    }
+
+    pub fn has_capability(&self, capability: Capability) -> bool {
+        self.capabilities.contains(&capability)
+    }
+
+    /// Init system used by the `Services` builtins; detected from the install root when unset.
+    pub fn init_system(mut self, init_system: Option<&str>) -> Self {
+        self.init_system = init_system.map(|s| s.to_string());
+        self
+    }
+
+    /// Register an extra builtin implemented in Rust.
+    pub fn builtin<A, R, F>(mut self, name: &str, func: F) -> Self
+    where
+        A: FromLuaMulti,
+        R: IntoLuaMulti,
+        F: Fn(&Lua, A) -> Result<R, LuaError> + 'static,
+    {
+        let func = Rc::new(func);
+        let registrar: Registrar = Box::new(move |lua| {
+            let func = Rc::clone(&func);
+            lua.create_function(move |lua, args: A| func(lua, args))
+        });
+        self.builtins.push((name.to_string(), registrar));
+        self
+    }
+
+    /// Let `require` load any module found under `dir`.
+    pub fn module_dir(mut self, dir: &Path) -> Self {
+        self.modules.dirs.push(dir.to_path_buf());
+        self
+    }
+
+    /// Let `require(name)` load `path`, refusing it unless it matches `checksum`.
+    pub fn module(mut self, name: &str, path: &Path, checksum: Option<&str>) -> Self {
+        self.modules.modules.insert(name.to_string(), Module {
+            path: path.to_path_buf(),
+            checksum: checksum.map(|c| c.to_string()),
+        });
+        self
+    }
+
+    /// Where `copy` and `template` read from. Defaults to `files/` in the extraction root.
+    pub fn files_dir(mut self, dir: &Path) -> Self {
+        self.files_dir = dir.to_path_buf();
+        self
+    }
+
+    /// Set a string global, such as `destdir` for build scripts.
+    pub fn global(mut self, name: &str, value: &str) -> Self {
+        self.globals.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Add a field to the `star` table that scripts and templates can read.
+    pub fn metadata(mut self, key: &str, value: &str) -> Self {
+        self.metadata.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Checksum `template` wrote for a config file in the previous install.
+    /// A file that no longer matches it was edited and will not be overwritten.
+    pub fn installed_config(mut self, path: &str, checksum: &str) -> Self {
+        self.installed_config.insert(normalize_recorded(path), checksum.to_string());
+        self
+    }
+
+    /// The global function called after the script has been loaded.
+    pub fn entry(mut self, name: &str) -> Self {
+        self.entry = name.to_string();
+        self
+    }
+
+    /// Skip, rather than fail, when the script does not define the entry function.
+    pub fn allow_missing_entry(mut self) -> Self {
+        self.allow_missing_entry = true;
+        self
+    }
+
+    /// Record what builtins would do instead of doing it. Nothing under the
+    /// install root is touched and `exists` answers from a virtual overlay.
+    pub fn dry_run(mut self) -> Self {
+        self.dry_run = true;
+        self
+    }
+
+    /// Dry-run `script_path` and return the operations it would perform.
+    pub fn plan(&self, script_path: &str) -> Result<Vec<Operation>, NovaError> {
+        let session = self.build_session(true)?;
+        session.exec_file(script_path)?;
+        session.call_entry(script_path)?;
+        Ok(session.operations())
+    }
+
+    /// Load `script_path`, call the entry function and return what it changed.
+    pub fn run(&self, script_path: &str) -> Result<InstallRecord, NovaError> {
+        let session = self.session()?;
+        session.exec_file(script_path)?;
+        session.call_entry(script_path)?;
+        Ok(session.record())
+    }
+
+    /// Build a Lua state with every enabled builtin registered.
+    pub fn session(&self) -> Result<NovaSession, NovaError> {
+        self.build_session(self.dry_run)
+    }
+
+    fn build_session(&self, dry_run: bool) -> Result<NovaSession, NovaError> {
+        // dry runs never get io or the rest of os, since they would bypass the
+        // plan; either way require only sees nova-lib modules
+        let stdlib = !dry_run && self.has_capability(Capability::Stdlib);
+        let libs = if stdlib { StdLib::ALL_SAFE } else { StdLib::ALL_SAFE ^ StdLib::IO ^ StdLib::PACKAGE };
+        let lua = Lua::new_with(libs, LuaOptions::default())?;
+        let record = Rc::new(RefCell::new(InstallRecord::default()));
+
+        let globals = lua.globals();
+        globals.set("install_root", self.install_root.display().to_string())?;
+        globals.set("star", lua.create_table_from(self.metadata.clone())?)?;
+        for (name, value) in &self.globals {
+            globals.set(name.as_str(), value.as_str())?;
+        }
+        if !stdlib {
+            // load stays: it only compiles strings the script already has
+            for name in ["dofile", "loadfile"] {
+                globals.set(name, Value::Nil)?;
+            }
+            if let Ok(os) = globals.get::<Table>("os") {
+                for name in ["execute", "exit", "remove", "rename", "tmpname", "setlocale"] {
+                    os.set(name, Value::Nil)?;
+                }
+            }
+        }
+
+        modules::register_require(&lua, self.modules.clone())?;
+
+        let plan = Rc::new(RefCell::new(Plan::default()));
+        let mut names = vec!["require".to_string()];
+        if dry_run {
+            names.extend(self.register_planned(&lua, &plan)?);
+        } else {
+            if self.has_capability(Capability::Process) {
+                names.extend(self.register_process(&lua)?);
+            }
+            if self.has_capability(Capability::Fs) {
+                names.extend(self.register_fs(&lua, &record)?);
+            }
+            if self.has_capability(Capability::Users) {
+                names.extend(self.register_users(&lua, &record)?);
+            }
+            if self.has_capability(Capability::Services) {
+                names.extend(self.register_services(&lua, &record)?);
+            }
+            if self.has_capability(Capability::Archive) {
+                names.extend(self.register_archive(&lua)?);
+            }
+            if self.has_capability(Capability::Net) {
+                names.extend(self.register_net(&lua)?);
+            }
+        }
+        for (name, registrar) in &self.builtins {
+            globals.set(name.as_str(), registrar(&lua)?)?;
+            names.push(name.clone());
+        }
+
+        annotate_builtins(&lua, &names)?;
+
+        Ok(NovaSession {
+            lua,
+            record,
+            plan,
+            entry: self.entry.clone(),
+            allow_missing_entry: self.allow_missing_entry,
+        })
+    }
+
+    /// Variables every template sees: the `star` table plus `install_root`.
+    fn template_context(&self) -> HashMap<String, String> {
+        let mut context: HashMap<String, String> = self.metadata.clone().into_iter().collect();
+        context.insert("install_root".to_string(), self.install_root.display().to_string());
+        context
+    }
+
+    fn register_process(&self, lua: &Lua) -> Result<Vec<String>, LuaError> {
+        let extract_root = self.extraction_root.clone();
+        let run = lua.create_function(move |_, args: Vec<String>| {
+            if args.is_empty() {
+                return Err(LuaError::external("run() requires at least one argument"));
+            }
+            let mut cmd = Command::new(&args[0]);
+            if args.len() > 1 {
+                cmd.args(&args[1..]);
+            }
+            cmd.current_dir(&extract_root);
+            run_command(cmd, args)
+        })?;
+
+        lua.globals().set("run", run)?;
+        Ok(vec!["run".to_string()])
+    }
+
+    fn register_fs(&self, lua: &Lua, record: &Rc<RefCell<InstallRecord>>) -> Result<Vec<String>, LuaError> {
+        let globals = lua.globals();
+
+        let copy = {
+            let install_root = self.install_root.clone();
+            let files_dir = self.files_dir.clone();
+            let record = Rc::clone(record);
+            lua.create_function(move |_, (from, to): (String, String)| {
+                let full_from = files_dir.join(&from);
+                let full_to = within_root(&install_root, &to);
+
+                if !full_from.exists() {
+                    return Err(LuaError::external(format!("Source file does not exist: {}", full_from.display())));
+                }
+
+                fs::create_dir_all(full_to.parent().unwrap_or_else(|| Path::new("/")))?;
+                fs::copy(&full_from, &full_to)?;
+
+                record.borrow_mut().files.push(normalize_recorded(&to));
+                Ok(())
+            })?
+        };
+
+        let template = {
+            let install_root = self.install_root.clone();
+            let context = self.template_context();
+            let source = self.files_dir.clone();
+            let installed_config = self.installed_config.clone();
+            let record = Rc::clone(record);
+            lua.create_function(move |_, (from, to, vars): (String, String, Option<Table>)| {
+                let full_from = source.join(&from);
+                let rendered = render_file(&full_from, &context, vars)?;
+                let full_to = within_root(&install_root, &to);
+                let recorded_path = normalize_recorded(&to);
+
+                let pristine = installed_config.get(&recorded_path).map(String::as_str);
+                let outcome = template::install_config(&full_to, rendered.as_bytes(), pristine)?;
+                let mode = fs::metadata(&full_from)?.permissions().mode();
+
+                let mut record = record.borrow_mut();
+                match outcome {
+                    ConfigOutcome::KeptModified(new_path) => {
+                        println!("⚠️  Kept modified {}, new version saved as {}.cosmos-new", recorded_path, recorded_path);
+                        fs::set_permissions(&new_path, fs::Permissions::from_mode(mode))?;
+                        record.files.push(format!("{}.cosmos-new", recorded_path));
+                    }
+                    ConfigOutcome::Written => {
+                        fs::set_permissions(&full_to, fs::Permissions::from_mode(mode))?;
+                    }
+                    ConfigOutcome::Unchanged => {}
+                }
+                record.files.push(recorded_path.clone());
+                record.config_files.push(ConfigFile {
+                    path: recorded_path,
+                    checksum: template::checksum(rendered.as_bytes()),
+                });
+                Ok(())
+            })?
+        };
+
+        let symlink = {
+            let install_root = self.install_root.clone();
+            let record = Rc::clone(record);
+            lua.create_function(move |_, (target, linkname): (String, String)| {
+                let full_link = within_root(&install_root, &linkname);
+                let _ = fs::remove_file(&full_link);
+                std::os::unix::fs::symlink(&target, &full_link)?;
+                record.borrow_mut().files.push(normalize_recorded(&linkname));
+                Ok(())
+            })?
+        };
+
+        let mkdir = {
+            let install_root = self.install_root.clone();
+            lua.create_function(move |_, path: String| {
+                let full_path = within_root(&install_root, &path);
+                fs::create_dir_all(&full_path)?;
+                Ok(())
+            })?
+        };
+
+        let chmod = {
+            let install_root = self.install_root.clone();
+            lua.create_function(move |_, (path, mode): (String, u32)| {
+                let full_path = within_root(&install_root, &path);
+                let mut perms = fs::metadata(&full_path)?.permissions();
+                perms.set_mode(mode);
+                fs::set_permissions(&full_path, perms)?;
+                Ok(())
+            })?
+        };
+
+        let exists = {
+            let install_root = self.install_root.clone();
+            lua.create_function(move |_, path: String| {
+                let full_path = within_root(&install_root, &path);
+                Ok(full_path.exists())
+            })?
+        };
+
+        let chown = {
+            let install_root = self.install_root.clone();
+            lua.create_function(move |_, (path, user, group): (String, Value, Value)| {
+                let full_path = within_root(&install_root, &path);
+                let uid = id_arg(user)?
+                    .map(|user| users::resolve_uid(&install_root, &user))
+                    .transpose()?;
+                let gid = id_arg(group)?
+                    .map(|group| users::resolve_gid(&install_root, &group))
+                    .transpose()?;
+                std::os::unix::fs::lchown(&full_path, uid, gid)?;
+                Ok(())
+            })?
+        };
+
+        globals.set("copy", copy)?;
+        globals.set("template", template)?;
+        globals.set("symlink", symlink)?;
+        globals.set("mkdir", mkdir)?;
+        globals.set("chmod", chmod)?;
+        globals.set("exists", exists)?;
+        globals.set("chown", chown)?;
+        Ok(names(&["copy", "template", "symlink", "mkdir", "chmod", "exists", "chown"]))
+    }
+
+    fn register_users(&self, lua: &Lua, record: &Rc<RefCell<InstallRecord>>) -> Result<Vec<String>, LuaError> {
+        let globals = lua.globals();
+
+        let add_group = {
+            let install_root = self.install_root.clone();
+            let record = Rc::clone(record);
+            lua.create_function(move |_, (name, gid): (String, Option<u32>)| {
+                let change = users::add_group(&install_root, &name, gid)?;
+                if change.created {
+                    record.borrow_mut().groups.push(name);
+                }
+                Ok(change.id)
+            })?
+        };
+
+        let add_user = {
+            let install_root = self.install_root.clone();
+            let record = Rc::clone(record);
+            lua.create_function(move |_, (name, opts): (String, Option<Table>)| {
+                let mut spec = users::UserSpec::default();
+                if let Some(opts) = opts {
+                    spec.uid = opts.get("uid")?;
+                    spec.group = opts.get("group")?;
+                    spec.home = opts.get("home")?;
+                    spec.shell = opts.get("shell")?;
+                    spec.gecos = opts.get("gecos")?;
+                }
+
+                // without an explicit group, users get a matching group of their own
+                if spec.group.is_none() {
+                    let change = users::add_group(&install_root, &name, None)?;
+                    if change.created {
+                        record.borrow_mut().groups.push(name.clone());
+                    }
+                }
+
+                let change = users::add_user(&install_root, &name, &spec)?;
+                if change.created {
+                    record.borrow_mut().users.push(name);
+                }
+                Ok(change.id)
+            })?
+        };
+
+        let user_exists = {
+            let install_root = self.install_root.clone();
+            lua.create_function(move |_, name: String| {
+                Ok(users::user_exists(&install_root, &name)?)
+            })?
+        };
+
+        let add_user_to_group = {
+            let install_root = self.install_root.clone();
+            lua.create_function(move |_, (user, group): (String, String)| {
+                users::add_user_to_group(&install_root, &user, &group)?;
+                Ok(())
+            })?
+        };
+
+        globals.set("add_group", add_group)?;
+        globals.set("add_user", add_user)?;
+        globals.set("user_exists", user_exists)?;
+        globals.set("add_user_to_group", add_user_to_group)?;
+        Ok(names(&["add_group", "add_user", "user_exists", "add_user_to_group"]))
+    }
+
+    fn register_services(&self, lua: &Lua, record: &Rc<RefCell<InstallRecord>>) -> Result<Vec<String>, LuaError> {
+        let globals = lua.globals();
+
+        let service_enable = {
+            let install_root = self.install_root.clone();
+            let init_name = self.init_system.clone();
+            let record = Rc::clone(record);
+            lua.create_function(move |_, name: String| {
+                let init = services::resolve_init(&install_root, init_name.as_deref())?;
+                let link = services::enable(&install_root, init, &name)?;
+                let mut record = record.borrow_mut();
+                if !record.service_links.contains(&link) {
+                    record.service_links.push(link);
+                }
+                Ok(())
+            })?
+        };
+
+        let service_disable = {
+            let install_root = self.install_root.clone();
+            let init_name = self.init_system.clone();
+            let record = Rc::clone(record);
+            lua.create_function(move |_, name: String| {
+                let init = services::resolve_init(&install_root, init_name.as_deref())?;
+                if let Some(link) = services::disable(&install_root, init, &name)? {
+                    record.borrow_mut().service_links.retain(|l| *l != link);
+                }
+                Ok(())
+            })?
+        };
+
+        globals.set("service_enable", service_enable)?;
+        globals.set("service_disable", service_disable)?;
+        Ok(names(&["service_enable", "service_disable"]))
+    }
+
+    /// `sha256` and `verify`, which only read, work the same in real and dry runs.
+    fn register_checksums(&self, lua: &Lua) -> Result<Vec<String>, LuaError> {
+        let globals = lua.globals();
+
+        let sha256 = {
+            let build_root = self.extraction_root.clone();
+            lua.create_function(move |_, path: String| {
+                Ok(archive::sha256_file(&within_build(&build_root, &path)?)?)
+            })?
+        };
+
+        let verify = {
+            let build_root = self.extraction_root.clone();
+            lua.create_function(move |_, (path, expected): (String, String)| {
+                let actual = archive::sha256_file(&within_build(&build_root, &path)?)?;
+                if !actual.eq_ignore_ascii_case(expected.trim()) {
+                    return Err(LuaError::external(format!(
+                        "Checksum mismatch for {}: expected {}, got {}", path, expected, actual
+                    )));
+                }
+                Ok(true)
+            })?
+        };
+
+        globals.set("sha256", sha256)?;
+        globals.set("verify", verify)?;
+        Ok(names(&["sha256", "verify"]))
+    }
+
+    fn register_archive(&self, lua: &Lua) -> Result<Vec<String>, LuaError> {
+        let mut registered = self.register_checksums(lua)?;
+
+        let extract = {
+            let build_root = self.extraction_root.clone();
+            lua.create_function(move |_, (from, to): (String, String)| {
+                let source = within_build(&build_root, &from)?;
+                let dest = within_build(&build_root, &to)?;
+                println!("📦 Extracting {} → {}", from, to);
+                Ok(archive::extract(&source, &dest)?)
+            })?
+        };
+
+        lua.globals().set("extract", extract)?;
+        registered.push("extract".to_string());
+        Ok(registered)
+    }
+
+    fn register_net(&self, lua: &Lua) -> Result<Vec<String>, LuaError> {
+        let build_root = self.extraction_root.clone();
+        let fetch = lua.create_function(move |_, (url, to): (String, String)| {
+            let dest = within_build(&build_root, &to)?;
+            println!("📥 Fetching {}", url);
+            let bytes = cosmos_transport::fetch_bytes(&url).map_err(LuaError::external)?;
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&dest, &bytes)?;
+            Ok(bytes.len())
+        })?;
+
+        lua.globals().set("fetch", fetch)?;
+        Ok(vec!["fetch".to_string()])
+    }
+}
+
+impl NovaRuntime {
+    /// Register recording versions of every enabled builtin for dry runs.
+    fn register_planned(&self, lua: &Lua, plan: &Rc<RefCell<Plan>>) -> Result<Vec<String>, LuaError> {
+        let globals = lua.globals();
+        let mut registered = vec![];
+
+        if self.has_capability(Capability::Process) {
+            let plan = Rc::clone(plan);
+            globals.set("run", lua.create_function(move |_, command: Vec<String>| {
+                if command.is_empty() {
+                    return Err(LuaError::external("run() requires at least one argument"));
+                }
+                plan.borrow_mut().push(Operation::Run { command });
+                Ok(0)
+            })?)?;
+            registered.push("run".to_string());
+        }
+
+        if self.has_capability(Capability::Fs) {
+            let copy = {
+                let plan = Rc::clone(plan);
+                let files_dir = self.files_dir.clone();
+                lua.create_function(move |_, (from, to): (String, String)| {
+                    let full_from = files_dir.join(&from);
+                    if !full_from.exists() {
+                        return Err(LuaError::external(format!("Source file does not exist: {}", full_from.display())));
+                    }
+                    plan.borrow_mut().push(Operation::Copy { from, to });
+                    Ok(())
+                })?
+            };
+
+            let template = {
+                let plan = Rc::clone(plan);
+                let context = self.template_context();
+                let source = self.files_dir.clone();
+                lua.create_function(move |_, (from, to, vars): (String, String, Option<Table>)| {
+                    // render anyway so undefined variables show up in the plan
+                    render_file(&source.join(&from), &context, vars)?;
+                    plan.borrow_mut().push(Operation::Template { from, to });
+                    Ok(())
+                })?
+            };
+
+            let symlink = {
+                let plan = Rc::clone(plan);
+                lua.create_function(move |_, (target, link): (String, String)| {
+                    plan.borrow_mut().push(Operation::Symlink { target, link });
+                    Ok(())
+                })?
+            };
+
+            let mkdir = {
+                let plan = Rc::clone(plan);
+                lua.create_function(move |_, path: String| {
+                    plan.borrow_mut().push(Operation::Mkdir { path });
+                    Ok(())
+                })?
+            };
+
+            let chmod = {
+                let plan = Rc::clone(plan);
+                lua.create_function(move |_, (path, mode): (String, u32)| {
+                    plan.borrow_mut().push(Operation::Chmod { path, mode });
+                    Ok(())
+                })?
+            };
+
+            let chown = {
+                let plan = Rc::clone(plan);
+                lua.create_function(move |_, (path, user, group): (String, Value, Value)| {
+                    let user = id_arg(user)?;
+                    let group = id_arg(group)?;
+                    plan.borrow_mut().push(Operation::Chown { path, user, group });
+                    Ok(())
+                })?
+            };
+
+            let exists = {
+                let plan = Rc::clone(plan);
+                let install_root = self.install_root.clone();
+                lua.create_function(move |_, path: String| {
+                    Ok(plan.borrow().has_path(&path) || within_root(&install_root, &path).exists())
+                })?
+            };
+
+            globals.set("copy", copy)?;
+            globals.set("template", template)?;
+            globals.set("symlink", symlink)?;
+            globals.set("mkdir", mkdir)?;
+            globals.set("chmod", chmod)?;
+            globals.set("chown", chown)?;
+            globals.set("exists", exists)?;
+            registered.extend(names(&["copy", "template", "symlink", "mkdir", "chmod", "chown", "exists"]));
+        }
+
+        if self.has_capability(Capability::Users) {
+            let add_group = {
+                let plan = Rc::clone(plan);
+                let install_root = self.install_root.clone();
+                lua.create_function(move |_, (name, gid): (String, Option<u32>)| {
+                    if let Some(existing) = users::lookup_gid(&install_root, &name)? {
+                        return Ok(existing);
+                    }
+                    plan.borrow_mut().push(Operation::AddGroup { name });
+                    Ok(gid.unwrap_or(0))
+                })?
+            };
+
+            let add_user = {
+                let plan = Rc::clone(plan);
+                let install_root = self.install_root.clone();
+                lua.create_function(move |_, (name, opts): (String, Option<Table>)| {
+                    if let Some(existing) = users::lookup_uid(&install_root, &name)? {
+                        return Ok(existing);
+                    }
+                    let group: Option<String> = opts.as_ref().map(|o| o.get("group")).transpose()?.flatten();
+                    let uid: Option<u32> = opts.as_ref().map(|o| o.get("uid")).transpose()?.flatten();
+                    let mut plan = plan.borrow_mut();
+                    if group.is_none() && !users::group_exists(&install_root, &name)? && !plan.groups.contains(&name) {
+                        plan.push(Operation::AddGroup { name: name.clone() });
+                    }
+                    plan.push(Operation::AddUser { name });
+                    Ok(uid.unwrap_or(0))
+                })?
+            };
+
+            let user_exists = {
+                let plan = Rc::clone(plan);
+                let install_root = self.install_root.clone();
+                lua.create_function(move |_, name: String| {
+                    Ok(plan.borrow().users.contains(&name) || users::user_exists(&install_root, &name)?)
+                })?
+            };
+
+            let add_user_to_group = {
+                let plan = Rc::clone(plan);
+                lua.create_function(move |_, (user, group): (String, String)| {
+                    plan.borrow_mut().push(Operation::AddUserToGroup { user, group });
+                    Ok(())
+                })?
+            };
+
+            globals.set("add_group", add_group)?;
+            globals.set("add_user", add_user)?;
+            globals.set("user_exists", user_exists)?;
+            globals.set("add_user_to_group", add_user_to_group)?;
+            registered.extend(names(&["add_group", "add_user", "user_exists", "add_user_to_group"]));
+        }
+
+        if self.has_capability(Capability::Services) {
+            let service_enable = {
+                let plan = Rc::clone(plan);
+                lua.create_function(move |_, name: String| {
+                    plan.borrow_mut().push(Operation::ServiceEnable { name });
+                    Ok(())
+                })?
+            };
+
+            let service_disable = {
+                let plan = Rc::clone(plan);
+                lua.create_function(move |_, name: String| {
+                    plan.borrow_mut().push(Operation::ServiceDisable { name });
+                    Ok(())
+                })?
+            };
+
+            globals.set("service_enable", service_enable)?;
+            globals.set("service_disable", service_disable)?;
+            registered.extend(names(&["service_enable", "service_disable"]));
+        }
+
+        if self.has_capability(Capability::Archive) {
+            registered.extend(self.register_checksums(lua)?);
+
+            let plan = Rc::clone(plan);
+            let build_root = self.extraction_root.clone();
+            globals.set("extract", lua.create_function(move |_, (archive, dest): (String, String)| {
+                within_build(&build_root, &archive)?;
+                within_build(&build_root, &dest)?;
+                plan.borrow_mut().push(Operation::Extract { archive, dest });
+                Ok(0)
+            })?)?;
+            registered.push("extract".to_string());
+        }
+
+        if self.has_capability(Capability::Net) {
+            let plan = Rc::clone(plan);
+            let build_root = self.extraction_root.clone();
+            globals.set("fetch", lua.create_function(move |_, (url, dest): (String, String)| {
+                within_build(&build_root, &dest)?;
+                plan.borrow_mut().push(Operation::Fetch { url, dest });
+                Ok(0)
+            })?)?;
+            registered.push("fetch".to_string());
+        }
+
+        Ok(registered)
+    }
+}
+
+impl NovaSession {
+    pub fn lua(&self) -> &Lua {
+        &self.lua
+    }
+
+    /// Load and execute a script file in this session.
+    pub fn exec_file(&self, script_path: &str) -> Result<(), NovaError> {
+        let code = fs::read_to_string(script_path)?;
+        self.lua
+            .load(&code)
+            .set_name(format!("@{}", chunk_name(script_path)))
+            .exec()
+            .map_err(|e| script_error(script_path, e))
+    }
+
+    /// Call the runtime's entry function. `script_path` is only used to locate errors.
+    pub fn call_entry(&self, script_path: &str) -> Result<(), NovaError> {
+        match self.lua.globals().get::<Option<Function>>(self.entry.as_str())? {
+            Some(entry) => entry.call::<()>(()).map_err(|e| script_error(script_path, e)),
+            None if self.allow_missing_entry => {
+                println!("⚠️  No {}() defined in script. Skipping.", self.entry);
+                Ok(())
+            }
+            None => Err(NovaError::MissingEntryFunction(self.entry.clone())),
+        }
+    }
+
+    /// Evaluate a snippet typed interactively, returning its values when it is
+    /// an expression. Syntax errors come back as plain `NovaError::Lua` so a
+    /// caller can tell incomplete input apart and keep reading.
+    pub fn eval(&self, code: &str, name: &str) -> Result<MultiValue, NovaError> {
+        let chunk_name = format!("={}", name);
+        let function = match self.lua.load(format!("return {}", code)).set_name(&chunk_name).into_function() {
+            Ok(function) => function,
+            Err(_) => self.lua.load(code).set_name(&chunk_name).into_function()?,
+        };
+        function.call::<MultiValue>(()).map_err(|e| script_error(name, e))
+    }
+
+    /// What the session has changed so far.
+    pub fn record(&self) -> InstallRecord {
+        self.record.borrow().clone()
+    }
+
+    /// Operations recorded so far by a dry-run session.
+    pub fn operations(&self) -> Vec<Operation> {
+        self.plan.borrow().operations.clone()
+    }
+}
+
+fn names(list: &[&str]) -> Vec<String> {
+    list.iter().map(|s| s.to_string()).collect()
+}
+
+/// Accept a user or group given either as a name or as a number.
+fn id_arg(value: Value) -> Result<Option<String>, LuaError> {
+    match value {
+        Value::Nil => Ok(None),
+        Value::Integer(id) => Ok(Some(id.to_string())),
+        Value::String(name) => Ok(Some(name.to_str()?.to_string())),
+        other => Err(LuaError::external(format!("expected a name or id, got {}", other.type_name()))),
+    }
+}
+
+/// How much of each output stream a failed `run()` keeps for its error.
+const OUTPUT_TAIL: usize = 64 * 1024;
+
+/// Run a command with the terminal's stdin, echoing its output as it comes
+/// and keeping the tail of what it printed for diagnostics.
+fn run_command(mut cmd: Command, args: Vec<String>) -> Result<i32, LuaError> {
+    let mut child = cmd.stdin(Stdio::inherit()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+    let stdout = child.stdout.take().map(|pipe| thread::spawn(move || tee(pipe, io::stdout())));
+    let stderr = child.stderr.take().map(|pipe| thread::spawn(move || tee(pipe, io::stderr())));
+    let status = child.wait()?;
+    let stdout = join_tail(stdout);
+    let stderr = join_tail(stderr);
+
+    if !status.success() {
+        return Err(LuaError::external(CommandFailed {
+            command: args,
+            code: status.code(),
+            stdout: String::from_utf8_lossy(&stdout).to_string(),
+            stderr: String::from_utf8_lossy(&stderr).to_string(),
+        }));
+    }
+    Ok(status.code().unwrap_or(1))
+}
+
+/// Copy `from` into `to` until it closes, returning the last [`OUTPUT_TAIL`] bytes.
+fn tee(mut from: impl Read, mut to: impl Write) -> Vec<u8> {
+    let mut tail = Vec::new();
+    let mut buf = [0u8; 8192];
+    loop {
+        let n = match from.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(_) => break,
+        };
+        let _ = to.write_all(&buf[..n]).and_then(|_| to.flush());
+        tail.extend_from_slice(&buf[..n]);
+        if tail.len() > OUTPUT_TAIL {
+            tail.drain(..tail.len() - OUTPUT_TAIL);
+        }
+    }
+    tail
+}
+
+fn join_tail(handle: Option<thread::JoinHandle<Vec<u8>>>) -> Vec<u8> {
+    handle.and_then(|handle| handle.join().ok()).unwrap_or_default()
+}
+
+/// Wrap each named global so its failures report the builtin, its
+/// arguments and the script line that called it.
+fn annotate_builtins(lua: &Lua, names: &[String]) -> Result<(), LuaError> {
+    let globals = lua.globals();
+    for name in names {
+        let inner: Function = globals.get(name.as_str())?;
+        let builtin = name.clone();
+        let wrapped = lua.create_function(move |lua, args: MultiValue| {
+            inner.call::<MultiValue>(args.clone()).map_err(|cause| {
+                let line = lua
+                    .inspect_stack(1)
+                    .map(|frame| frame.curr_line())
+                    .filter(|line| *line > 0)
+                    .map(|line| line as u32);
+                LuaError::external(BuiltinError {
+                    builtin: builtin.clone(),
+                    args: args.iter().map(describe_value).collect(),
+                    line,
+                    cause,
+                })
+            })
+        })?;
+        globals.set(name.as_str(), wrapped)?;
+    }
+    Ok(())
+}
+
+/// Render a template from `files/`, with `vars` overriding the base context.
+fn render_file(path: &Path, context: &HashMap<String, String>, vars: Option<Table>) -> Result<String, LuaError> {
+    let source = fs::read_to_string(path)
+        .map_err(|e| LuaError::external(format!("Cannot read template {}: {}", path.display(), e)))?;
+
+    let mut vars_map = context.clone();
+    if let Some(vars) = vars {
+        for pair in vars.pairs::<String, Value>() {
+            let (key, value) = pair?;
+            let value = match value {
+                Value::String(s) => s.to_str()?.to_string(),
+                Value::Integer(i) => i.to_string(),
+                Value::Number(n) => n.to_string(),
+                Value::Boolean(b) => b.to_string(),
+                other => return Err(LuaError::external(format!(
+                    "Template variable '{}' must be a string, number or boolean, got {}", key, other.type_name()
+                ))),
+            };
+            vars_map.insert(key, value);
+        }
+    }
+
+    template::render(&source, &vars_map)
+        .map_err(|e| LuaError::external(format!("{}: {}", path.file_name().unwrap_or_default().to_string_lossy(), e)))
+}
+
+/// How install-root paths are written in the record: absolute, no `./`.
+fn normalize_recorded(path: &str) -> String {
+    format!("/{}", path.trim_start_matches("./").trim_start_matches('/'))
+}
+
+/// Resolve a path for the build-side builtins. Absolute paths are taken
+/// relative to `root` and `..` is refused, so nothing escapes it.
+fn within_build(root: &Path, requested: &str) -> Result<PathBuf, LuaError> {
+    let raw = Path::new(requested);
+    if raw.components().any(|c| matches!(c, std::path::Component::ParentDir)) {
+        return Err(LuaError::external(format!("Path escapes the build directory: {}", requested)));
+    }
+    Ok(within_root(root, requested))
+}
+
+pub(crate) fn within_root(root: &Path, requested: &str) -> PathBuf {
+    let raw = Path::new(requested);
+    if raw.is_absolute() {
+        root.join(raw.strip_prefix("/").unwrap_or(raw))
+    } else {
+        root.join(raw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn defined(session: &NovaSession, expr: &str) -> bool {
+        let values = session.eval(&format!("{} ~= nil", expr), "test").unwrap();
+        matches!(values.into_iter().next(), Some(Value::Boolean(true)))
+    }
+
+    #[test]
+    fn stdlib_is_on_by_default() {
+        let root = Path::new("/nonexistent");
+        let session = NovaRuntime::new(root, root).session().unwrap();
+        for expr in ["io", "io.open", "os.execute", "os.remove", "package", "dofile", "loadfile"] {
+            assert!(defined(&session, expr), "{} missing", expr);
+        }
+    }
+
+    #[test]
+    fn without_stdlib_only_harmless_os_remains() {
+        let root = Path::new("/nonexistent");
+        let runtime = NovaRuntime::new(root, root).without_capability(Capability::Stdlib);
+        for session in [runtime.session().unwrap(), NovaRuntime::new(root, root).build_session(true).unwrap()] {
+            for expr in ["io", "os.execute", "os.exit", "os.remove", "os.rename", "package", "dofile", "loadfile"] {
+                assert!(!defined(&session, expr), "{} exposed", expr);
+            }
+            for expr in ["os.time", "require", "load"] {
+                assert!(defined(&session, expr), "{} missing", expr);
+            }
+        }
+    }
+
+    #[test]
+    fn tee_echoes_everything_and_keeps_the_tail() {
+        let input: Vec<u8> = (0..OUTPUT_TAIL + 100).map(|i| (i % 251) as u8).collect();
+        let mut echoed = Vec::new();
+        let tail = tee(&input[..], &mut echoed);
+        assert_eq!(echoed, input);
+        assert_eq!(tail, &input[100..]);
+    }
+
+    #[test]
+    fn failed_commands_keep_their_output() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo out; echo err >&2; exit 3"]);
+        let err = run_command(cmd, vec!["sh".to_string()]).unwrap_err();
+        let LuaError::ExternalError(cause) = err else { panic!("unexpected error {:?}", err) };
+        let failed = cause.downcast_ref::<CommandFailed>().unwrap();
+        assert_eq!((failed.code, failed.stdout.as_str(), failed.stderr.as_str()), (Some(3), "out\n", "err\n"));
+
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "exit 0"]);
+        assert_eq!(run_command(cmd, vec!["sh".to_string()]).unwrap(), 0);
+    }
+}
@@ -0,0 +1,136 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Init systems Nova knows how to enable services for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InitSystem {
+    OpenRc,
+    Runit,
+    S6,
+}
+
+impl InitSystem {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "openrc" => Some(InitSystem::OpenRc),
+            "runit" => Some(InitSystem::Runit),
+            "s6" | "s6-rc" => Some(InitSystem::S6),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            InitSystem::OpenRc => "openrc",
+            InitSystem::Runit => "runit",
+            InitSystem::S6 => "s6",
+        }
+    }
+
+    /// Guess the init system from what is present in `root`.
+    pub fn detect(root: &Path) -> Option<Self> {
+        if root.join("etc/runlevels").is_dir() || root.join("sbin/openrc").exists() {
+            Some(InitSystem::OpenRc)
+        } else if root.join("etc/runit").is_dir() || root.join("etc/sv").is_dir() {
+            Some(InitSystem::Runit)
+        } else if root.join("etc/s6").is_dir() {
+            Some(InitSystem::S6)
+        } else {
+            None
+        }
+    }
+
+    /// Where the service definition for `name` must live.
+    fn definition(&self, name: &str) -> String {
+        match self {
+            InitSystem::OpenRc => format!("/etc/init.d/{}", name),
+            InitSystem::Runit => format!("/etc/sv/{}", name),
+            InitSystem::S6 => format!("/etc/s6/sv/{}", name),
+        }
+    }
+
+    /// The link or marker file that enables `name`.
+    fn enablement(&self, name: &str) -> String {
+        match self {
+            InitSystem::OpenRc => format!("/etc/runlevels/default/{}", name),
+            InitSystem::Runit => format!("/etc/runit/runsvdir/default/{}", name),
+            InitSystem::S6 => format!("/etc/s6/adminsv/default/contents.d/{}", name),
+        }
+    }
+}
+
+/// Resolve the init system: a configured name wins over detection.
+pub fn resolve_init(root: &Path, configured: Option<&str>) -> io::Result<InitSystem> {
+    if let Some(name) = configured {
+        return InitSystem::from_name(name).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("Unsupported init system: '{}'", name))
+        });
+    }
+
+    InitSystem::detect(root).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, "Could not detect an init system in the install root")
+    })
+}
+
+fn in_root(root: &Path, path: &str) -> PathBuf {
+    root.join(path.trim_start_matches('/'))
+}
+
+fn validate_service_name(name: &str) -> io::Result<()> {
+    if name.is_empty() || name.contains('/') || name == "." || name == ".." {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid service name: '{}'", name)));
+    }
+    Ok(())
+}
+
+/// Enable `name` for `init` inside `root`. Returns the root-relative path of
+/// the link or marker that was created, so it can be removed on uninstall.
+pub fn enable(root: &Path, init: InitSystem, name: &str) -> io::Result<String> {
+    validate_service_name(name)?;
+
+    let definition = init.definition(name);
+    if !in_root(root, &definition).exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Service '{}' has no {} definition at {}", name, init.name(), definition),
+        ));
+    }
+
+    let enablement = init.enablement(name);
+    let full_path = in_root(root, &enablement);
+    fs::create_dir_all(full_path.parent().unwrap_or(root))?;
+    if full_path.symlink_metadata().is_ok() {
+        fs::remove_file(&full_path)?;
+    }
+
+    match init {
+        InitSystem::OpenRc | InitSystem::Runit => std::os::unix::fs::symlink(&definition, &full_path)?,
+        InitSystem::S6 => fs::write(&full_path, "")?,
+    }
+
+    Ok(enablement)
+}
+
+/// Disable `name` for `init` inside `root`. Returns the path that was removed, if any.
+pub fn disable(root: &Path, init: InitSystem, name: &str) -> io::Result<Option<String>> {
+    validate_service_name(name)?;
+
+    let enablement = init.enablement(name);
+    let full_path = in_root(root, &enablement);
+    if full_path.symlink_metadata().is_ok() {
+        fs::remove_file(&full_path)?;
+        Ok(Some(enablement))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Remove a previously recorded enablement link or marker.
+pub fn remove_link(root: &Path, link: &str) -> io::Result<()> {
+    let full_path = in_root(root, link);
+    if full_path.symlink_metadata().is_ok() {
+        fs::remove_file(full_path)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,68 @@
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// What happened when a rendered config file was put in place.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum ConfigOutcome {
+    Written,
+    Unchanged,
+    /// The file on disk was edited since it was installed, so the new
+    /// version was written next to it instead.
+    KeptModified(PathBuf),
+}
+
+/// Substitute every `{{ name }}` in `template`. Unknown names are an error
+/// rather than silently rendering as empty.
+pub(crate) fn render(template: &str, vars: &HashMap<String, String>) -> Result<String, String> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let line = template[..template.len() - rest.len() + start].matches('\n').count() + 1;
+        let after = &rest[start + 2..];
+        let end = after
+            .find("}}")
+            .ok_or_else(|| format!("unterminated {{{{ on line {}", line))?;
+        let name = after[..end].trim();
+        let value = vars
+            .get(name)
+            .ok_or_else(|| format!("undefined variable '{}' on line {}", name, line))?;
+        out.push_str(value);
+        rest = &after[end + 2..];
+    }
+
+    out.push_str(rest);
+    Ok(out)
+}
+
+pub(crate) fn checksum(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+/// Write `content` to `dest` unless `dest` was changed since it was installed
+/// with checksum `pristine`. A modified file is left alone and the new
+/// content goes to `<dest>.cosmos-new`.
+pub(crate) fn install_config(dest: &Path, content: &[u8], pristine: Option<&str>) -> io::Result<ConfigOutcome> {
+    if let Ok(current) = fs::read(dest) {
+        if current == content {
+            return Ok(ConfigOutcome::Unchanged);
+        }
+        if pristine != Some(checksum(&current).as_str()) {
+            let mut new_path = dest.as_os_str().to_owned();
+            new_path.push(".cosmos-new");
+            let new_path = PathBuf::from(new_path);
+            fs::write(&new_path, content)?;
+            return Ok(ConfigOutcome::KeptModified(new_path));
+        }
+    }
+
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(dest, content)?;
+    Ok(ConfigOutcome::Written)
+}
@@ -0,0 +1,456 @@
+use std::fs;
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+/// First and last IDs handed out to system users and groups.
+const SYSTEM_ID_MIN: u32 = 100;
+const SYSTEM_ID_MAX: u32 = 999;
+
+/// Options accepted by `add_user`. Anything left as `None` gets a
+/// sensible default for a system account.
+#[derive(Debug, Default, Clone)]
+pub struct UserSpec {
+    pub uid: Option<u32>,
+    pub group: Option<String>,
+    pub home: Option<String>,
+    pub shell: Option<String>,
+    pub gecos: Option<String>,
+}
+
+/// Result of an account change. `created` is false when the account was
+/// already present, so callers only record what they actually added.
+#[derive(Debug, Clone, Copy)]
+pub struct AccountChange {
+    pub id: u32,
+    pub created: bool,
+}
+
+fn etc_file(root: &Path, name: &str) -> PathBuf {
+    root.join("etc").join(name)
+}
+
+/// One line of an account file. Comments and blank lines are kept as they
+/// are so rewriting the file only changes the entries that were touched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Line {
+    Entry(Vec<String>),
+    Verbatim(String),
+}
+
+impl Line {
+    fn fields(&self) -> Option<&Vec<String>> {
+        match self {
+            Line::Entry(fields) => Some(fields),
+            Line::Verbatim(_) => None,
+        }
+    }
+
+    fn fields_mut(&mut self) -> Option<&mut Vec<String>> {
+        match self {
+            Line::Entry(fields) => Some(fields),
+            Line::Verbatim(_) => None,
+        }
+    }
+
+    fn is_named(&self, name: &str) -> bool {
+        self.fields().and_then(|fields| fields.first()).is_some_and(|first| first == name)
+    }
+}
+
+fn parse_entries(content: &str) -> Vec<Line> {
+    content
+        .lines()
+        .map(|line| {
+            if line.trim().is_empty() || line.starts_with('#') {
+                Line::Verbatim(line.to_string())
+            } else {
+                Line::Entry(line.split(':').map(|s| s.to_string()).collect())
+            }
+        })
+        .collect()
+}
+
+fn render_entries(entries: &[Line]) -> String {
+    let mut content = entries
+        .iter()
+        .map(|line| match line {
+            Line::Entry(fields) => fields.join(":"),
+            Line::Verbatim(text) => text.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    if !content.is_empty() {
+        content.push('\n');
+    }
+    content
+}
+
+fn read_entries(path: &Path) -> io::Result<Vec<Line>> {
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    Ok(parse_entries(&fs::read_to_string(path)?))
+}
+
+/// Replace `path` with `content` by writing a sibling file and renaming it
+/// over the original, keeping the original permissions when there is one.
+fn write_atomic(path: &Path, content: &str, default_mode: u32) -> io::Result<()> {
+    let parent = path.parent().unwrap_or_else(|| Path::new("/"));
+    fs::create_dir_all(parent)?;
+
+    let mode = match fs::metadata(path) {
+        Ok(meta) => meta.permissions().mode(),
+        Err(_) => default_mode,
+    };
+
+    let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("file");
+    let tmp = parent.join(format!(".{}.cosmos-new", file_name));
+    fs::write(&tmp, content)?;
+    fs::set_permissions(&tmp, fs::Permissions::from_mode(mode))?;
+    fs::rename(&tmp, path)
+}
+
+fn write_entries(path: &Path, entries: &[Line], default_mode: u32) -> io::Result<()> {
+    write_atomic(path, &render_entries(entries), default_mode)
+}
+
+fn find_entry<'a>(entries: &'a [Line], name: &str) -> Option<&'a Vec<String>> {
+    entries.iter().find(|line| line.is_named(name)).and_then(Line::fields)
+}
+
+fn used_ids(entries: &[Line]) -> Vec<u32> {
+    entries
+        .iter()
+        .filter_map(|line| line.fields()?.get(2)?.parse().ok())
+        .collect()
+}
+
+/// Set `user` as a member of `group` in a group or gshadow file, whose
+/// member list is the fourth field in both.
+fn add_member(entries: &mut [Line], group: &str, user: &str) -> bool {
+    let Some(entry) = entries.iter_mut().find(|line| line.is_named(group)).and_then(Line::fields_mut) else {
+        return false;
+    };
+    while entry.len() < 4 {
+        entry.push(String::new());
+    }
+    let mut members: Vec<&str> = entry[3].split(',').filter(|m| !m.is_empty()).collect();
+    if !members.contains(&user) {
+        members.push(user);
+        entry[3] = members.join(",");
+    }
+    true
+}
+
+/// Drop `user` from every member list in a group or gshadow file.
+fn remove_member(entries: &mut [Line], user: &str) {
+    for entry in entries.iter_mut().filter_map(Line::fields_mut) {
+        if let Some(members) = entry.get_mut(3) {
+            *members = members
+                .split(',')
+                .filter(|m| !m.is_empty() && *m != user)
+                .collect::<Vec<_>>()
+                .join(",");
+        }
+    }
+}
+
+fn free_system_id(taken: &[u32]) -> io::Result<u32> {
+    (SYSTEM_ID_MIN..=SYSTEM_ID_MAX)
+        .rev()
+        .find(|id| !taken.contains(id))
+        .ok_or_else(|| io::Error::other("No free system IDs left"))
+}
+
+fn days_since_epoch() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() / 86400)
+        .unwrap_or(0)
+}
+
+/// Whether `name` is acceptable as a user or group name.
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 32
+        && !name.starts_with('-')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+}
+
+fn validate_name(name: &str) -> io::Result<()> {
+    if is_valid_name(name) {
+        Ok(())
+    } else {
+        Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid account name: '{}'", name)))
+    }
+}
+
+pub fn user_exists(root: &Path, name: &str) -> io::Result<bool> {
+    Ok(find_entry(&read_entries(&etc_file(root, "passwd"))?, name).is_some())
+}
+
+pub fn group_exists(root: &Path, name: &str) -> io::Result<bool> {
+    Ok(find_entry(&read_entries(&etc_file(root, "group"))?, name).is_some())
+}
+
+/// Resolve a user name to its UID using the passwd file inside `root`.
+pub fn lookup_uid(root: &Path, name: &str) -> io::Result<Option<u32>> {
+    let entries = read_entries(&etc_file(root, "passwd"))?;
+    Ok(find_entry(&entries, name).and_then(|fields| fields.get(2)).and_then(|id| id.parse().ok()))
+}
+
+/// Resolve a group name to its GID using the group file inside `root`.
+pub fn lookup_gid(root: &Path, name: &str) -> io::Result<Option<u32>> {
+    let entries = read_entries(&etc_file(root, "group"))?;
+    Ok(find_entry(&entries, name).and_then(|fields| fields.get(2)).and_then(|id| id.parse().ok()))
+}
+
+/// Resolve a user given by name or numeric UID against the passwd file inside `root`.
+pub fn resolve_uid(root: &Path, user: &str) -> io::Result<u32> {
+    if let Ok(uid) = user.parse() {
+        return Ok(uid);
+    }
+    lookup_uid(root, user)?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("User '{}' does not exist", user)))
+}
+
+/// Resolve a group given by name or numeric GID against the group file inside `root`.
+pub fn resolve_gid(root: &Path, group: &str) -> io::Result<u32> {
+    if let Ok(gid) = group.parse() {
+        return Ok(gid);
+    }
+    lookup_gid(root, group)?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("Group '{}' does not exist", group)))
+}
+
+/// Create a system group inside `root`, picking a free GID unless one is given.
+pub fn add_group(root: &Path, name: &str, gid: Option<u32>) -> io::Result<AccountChange> {
+    validate_name(name)?;
+    let group_path = etc_file(root, "group");
+    let mut entries = read_entries(&group_path)?;
+
+    if let Some(existing) = find_entry(&entries, name) {
+        let id = existing.get(2).and_then(|id| id.parse().ok()).unwrap_or(0);
+        return Ok(AccountChange { id, created: false });
+    }
+
+    let taken = used_ids(&entries);
+    let gid = match gid {
+        Some(gid) if taken.contains(&gid) => {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("GID {} is already in use", gid)));
+        }
+        Some(gid) => gid,
+        None => free_system_id(&taken)?,
+    };
+
+    let gshadow_path = etc_file(root, "gshadow");
+    let mut gshadow = read_entries(&gshadow_path)?;
+    gshadow.retain(|line| !line.is_named(name));
+    gshadow.push(Line::Entry(vec![name.to_string(), "!".to_string(), String::new(), String::new()]));
+
+    entries.push(Line::Entry(vec![name.to_string(), "x".to_string(), gid.to_string(), String::new()]));
+    write_entries(&gshadow_path, &gshadow, 0o600)?;
+    write_entries(&group_path, &entries, 0o644)?;
+    Ok(AccountChange { id: gid, created: true })
+}
+
+/// Create a system user inside `root`. The primary group must already exist
+/// when `spec.group` is set; otherwise a group named after the user is used.
+pub fn add_user(root: &Path, name: &str, spec: &UserSpec) -> io::Result<AccountChange> {
+    validate_name(name)?;
+    let passwd_path = etc_file(root, "passwd");
+    let shadow_path = etc_file(root, "shadow");
+    let mut passwd = read_entries(&passwd_path)?;
+
+    if let Some(existing) = find_entry(&passwd, name) {
+        let id = existing.get(2).and_then(|id| id.parse().ok()).unwrap_or(0);
+        return Ok(AccountChange { id, created: false });
+    }
+
+    let group_name = spec.group.clone().unwrap_or_else(|| name.to_string());
+    let gid = lookup_gid(root, &group_name)?.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, format!("Group '{}' does not exist", group_name))
+    })?;
+
+    let taken = used_ids(&passwd);
+    let uid = match spec.uid {
+        Some(uid) if taken.contains(&uid) => {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("UID {} is already in use", uid)));
+        }
+        Some(uid) => uid,
+        // keep uid == gid for per-user groups when that id is still free
+        None if !taken.contains(&gid) && (SYSTEM_ID_MIN..=SYSTEM_ID_MAX).contains(&gid) => gid,
+        None => free_system_id(&taken)?,
+    };
+
+    passwd.push(Line::Entry(vec![
+        name.to_string(),
+        "x".to_string(),
+        uid.to_string(),
+        gid.to_string(),
+        spec.gecos.clone().unwrap_or_default(),
+        spec.home.clone().unwrap_or_else(|| "/var/empty".to_string()),
+        spec.shell.clone().unwrap_or_else(|| "/sbin/nologin".to_string()),
+    ]));
+
+    let mut shadow = read_entries(&shadow_path)?;
+    shadow.retain(|line| !line.is_named(name));
+    shadow.push(Line::Entry(vec![
+        name.to_string(),
+        "!".to_string(),
+        days_since_epoch().to_string(),
+        "0".to_string(),
+        "99999".to_string(),
+        "7".to_string(),
+        String::new(),
+        String::new(),
+        String::new(),
+    ]));
+
+    write_entries(&shadow_path, &shadow, 0o600)?;
+    write_entries(&passwd_path, &passwd, 0o644)?;
+    Ok(AccountChange { id: uid, created: true })
+}
+
+/// Add `user` to the supplementary member list of `group`.
+pub fn add_user_to_group(root: &Path, user: &str, group: &str) -> io::Result<()> {
+    if !user_exists(root, user)? {
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("User '{}' does not exist", user)));
+    }
+
+    let group_path = etc_file(root, "group");
+    let mut entries = read_entries(&group_path)?;
+    if !add_member(&mut entries, group, user) {
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("Group '{}' does not exist", group)));
+    }
+
+    let gshadow_path = etc_file(root, "gshadow");
+    if gshadow_path.exists() {
+        let mut gshadow = read_entries(&gshadow_path)?;
+        if add_member(&mut gshadow, group, user) {
+            write_entries(&gshadow_path, &gshadow, 0o600)?;
+        }
+    }
+
+    write_entries(&group_path, &entries, 0o644)
+}
+
+/// Remove a user from passwd, shadow and every group member list.
+pub fn remove_user(root: &Path, name: &str) -> io::Result<()> {
+    let passwd_path = etc_file(root, "passwd");
+    if passwd_path.exists() {
+        let mut passwd = read_entries(&passwd_path)?;
+        passwd.retain(|line| !line.is_named(name));
+        write_entries(&passwd_path, &passwd, 0o644)?;
+    }
+
+    let shadow_path = etc_file(root, "shadow");
+    if shadow_path.exists() {
+        let mut shadow = read_entries(&shadow_path)?;
+        shadow.retain(|line| !line.is_named(name));
+        write_entries(&shadow_path, &shadow, 0o600)?;
+    }
+
+    for (file, mode) in [("group", 0o644), ("gshadow", 0o600)] {
+        let path = etc_file(root, file);
+        if path.exists() {
+            let mut entries = read_entries(&path)?;
+            remove_member(&mut entries, name);
+            write_entries(&path, &entries, mode)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Remove a group from the group and gshadow files inside `root`.
+pub fn remove_group(root: &Path, name: &str) -> io::Result<()> {
+    for (file, mode) in [("group", 0o644), ("gshadow", 0o600)] {
+        let path = etc_file(root, file);
+        if path.exists() {
+            let mut entries = read_entries(&path)?;
+            entries.retain(|line| !line.is_named(name));
+            write_entries(&path, &entries, mode)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn root_with(files: &[(&str, &str)]) -> tempfile::TempDir {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir_all(root.path().join("etc")).unwrap();
+        for (name, content) in files {
+            fs::write(etc_file(root.path(), name), content).unwrap();
+        }
+        root
+    }
+
+    fn read(root: &tempfile::TempDir, name: &str) -> String {
+        fs::read_to_string(etc_file(root.path(), name)).unwrap()
+    }
+
+    #[test]
+    fn entries_round_trip_verbatim() {
+        let content = "# system accounts\nroot:x:0:0:root:/root:/bin/sh\n\n# services\ndaemon:x:2:2::/:/sbin/nologin\n";
+        assert_eq!(render_entries(&parse_entries(content)), content);
+    }
+
+    #[test]
+    fn add_group_keeps_comments_and_updates_gshadow() {
+        let root = root_with(&[("group", "# groups\nroot:x:0:\n"), ("gshadow", "root:::\n")]);
+        let change = add_group(root.path(), "web", None).unwrap();
+        assert!(change.created);
+        assert_eq!(change.id, SYSTEM_ID_MAX);
+        assert_eq!(read(&root, "group"), "# groups\nroot:x:0:\nweb:x:999:\n");
+        assert_eq!(read(&root, "gshadow"), "root:::\nweb:!::\n");
+
+        let again = add_group(root.path(), "web", None).unwrap();
+        assert!(!again.created);
+        assert_eq!(again.id, 999);
+    }
+
+    #[test]
+    fn add_user_keeps_comments_and_reuses_group_id() {
+        let root = root_with(&[("passwd", "# users\nroot:x:0:0:root:/root:/bin/sh\n"), ("group", "root:x:0:\n")]);
+        add_group(root.path(), "web", None).unwrap();
+        let change = add_user(root.path(), "web", &UserSpec::default()).unwrap();
+        assert_eq!(change.id, 999);
+        assert_eq!(
+            read(&root, "passwd"),
+            "# users\nroot:x:0:0:root:/root:/bin/sh\nweb:x:999:999::/var/empty:/sbin/nologin\n"
+        );
+        assert!(read(&root, "shadow").starts_with("web:!:"));
+        assert!(add_user(root.path(), "nogroup", &UserSpec::default()).is_err());
+    }
+
+    #[test]
+    fn membership_is_kept_in_group_and_gshadow() {
+        let root = root_with(&[("group", "# groups\n"), ("passwd", "")]);
+        add_group(root.path(), "web", Some(500)).unwrap();
+        add_group(root.path(), "logs", Some(501)).unwrap();
+        add_user(root.path(), "web", &UserSpec::default()).unwrap();
+        add_user_to_group(root.path(), "web", "logs").unwrap();
+        add_user_to_group(root.path(), "web", "logs").unwrap();
+        assert!(read(&root, "group").contains("logs:x:501:web\n"));
+        assert!(read(&root, "gshadow").contains("logs:!::web\n"));
+
+        remove_user(root.path(), "web").unwrap();
+        remove_group(root.path(), "logs").unwrap();
+        assert_eq!(read(&root, "group"), "# groups\nweb:x:500:\n");
+        assert_eq!(read(&root, "gshadow"), "web:!::\n");
+        assert!(!user_exists(root.path(), "web").unwrap());
+    }
+
+    #[test]
+    fn rejects_bad_names_and_taken_ids() {
+        let root = root_with(&[("group", "root:x:0:\n")]);
+        assert!(add_group(root.path(), "-bad", None).is_err());
+        assert!(add_group(root.path(), "a/b", None).is_err());
+        assert!(add_group(root.path(), "wheel", Some(0)).is_err());
+    }
+}
@@ -0,0 +1,343 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::fs::{self, File};
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use cosmos_core::galaxy::Galaxy;
+use cosmos_core::star::Star;
+
+use crate::build_star::{self, BuiltStar};
+use crate::galaxy;
+use crate::prompt::Prompter;
+
+const CACHE_FILE: &str = ".build-cache.toml";
+
+/// `.build-cache.toml` in the star tree: what each star was last built from.
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct BuildCache {
+    #[serde(default)]
+    stars: BTreeMap<String, CachedBuild>, // star directory name → last build
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct CachedBuild {
+    inputs: String,       // sha256 over the star directory and its dependencies' inputs
+    outputs: Vec<String>, // star definitions written to dist/
+}
+
+/// One star directory of the tree.
+struct Job {
+    name: String, // directory name
+    dir: PathBuf,
+    star: Star,
+    deps: Vec<usize>, // jobs this one depends on
+}
+
+/// Hash everything in a star directory that can change its build: paths,
+/// modes, file contents and symlink targets. Fetched sources in `.cache/`
+/// and local `dist/` output are left out.
+fn hash_dir(dir: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    let mut hasher = Sha256::new();
+    let walker = walkdir::WalkDir::new(dir)
+        .min_depth(1)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| !(e.depth() == 1 && (e.file_name() == ".cache" || e.file_name() == "dist")));
+    for entry in walker {
+        let entry = entry?;
+        let metadata = entry.path().symlink_metadata()?;
+        hasher.update(entry.path().strip_prefix(dir)?.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update(metadata.permissions().mode().to_le_bytes());
+        if metadata.file_type().is_symlink() {
+            hasher.update(fs::read_link(entry.path())?.to_string_lossy().as_bytes());
+        } else if metadata.is_file() {
+            io::copy(&mut File::open(entry.path())?, &mut hasher)?;
+        }
+        hasher.update([0]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Every star directory directly below `root`, with the dependencies among
+/// them, splits' included. A dependency on a `[[split]]` output counts as one
+/// on its star.
+fn discover(root: &Path) -> Result<Vec<Job>, Box<dyn std::error::Error>> {
+    let mut dirs = fs::read_dir(root)?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.join("star.toml").is_file())
+        .collect::<Vec<_>>();
+    dirs.sort();
+
+    let mut jobs = vec![];
+    for dir in dirs {
+        let path = dir.join("star.toml");
+        let star: Star = toml::from_str(&fs::read_to_string(&path)?)
+            .map_err(|e| format!("❌ Error: Could not parse {}: {}", path.display(), e))?;
+        let name = dir.file_name().unwrap_or_default().to_string_lossy().to_string();
+        jobs.push(Job { name, dir, star, deps: vec![] });
+    }
+
+    let mut outputs = HashMap::new();
+    for (i, job) in jobs.iter().enumerate() {
+        let names = std::iter::once(&job.star.name).chain(job.star.split.iter().flatten().map(|s| &s.name));
+        for name in names {
+            if let Some(other) = outputs.insert(name.clone(), i) {
+                return Err(format!("❌ Error: {} and {} both build a star named {}", jobs[other].name, job.name, name).into());
+            }
+        }
+    }
+    for (i, job) in jobs.iter_mut().enumerate() {
+        let declared = job.star.split.iter().flatten().filter_map(|split| split.dependencies.as_ref());
+        let mut deps: Vec<usize> = job.star.dependencies.iter().chain(declared)
+            .flatten()
+            .filter_map(|(name, _)| outputs.get(name).copied())
+            .filter(|&dep| dep != i)
+            .collect();
+        deps.sort();
+        deps.dedup();
+        job.deps = deps;
+    }
+    Ok(jobs)
+}
+
+/// Jobs in an order where every job comes after its dependencies, or an
+/// error naming the stars caught in a cycle.
+fn build_order(jobs: &[Job]) -> Result<Vec<usize>, Box<dyn std::error::Error>> {
+    let mut waiting: Vec<usize> = jobs.iter().map(|job| job.deps.len()).collect();
+    let mut ready: VecDeque<usize> = (0..jobs.len()).filter(|&i| waiting[i] == 0).collect();
+    let mut order = vec![];
+    while let Some(i) = ready.pop_front() {
+        order.push(i);
+        for (j, job) in jobs.iter().enumerate() {
+            if job.deps.contains(&i) {
+                waiting[j] -= 1;
+                if waiting[j] == 0 {
+                    ready.push_back(j);
+                }
+            }
+        }
+    }
+    if order.len() < jobs.len() {
+        let stuck: Vec<&str> = (0..jobs.len()).filter(|i| !order.contains(i)).map(|i| jobs[i].name.as_str()).collect();
+        return Err(format!("❌ Error: Dependency cycle; these stars cannot be ordered: {}", stuck.join(", ")).into());
+    }
+    Ok(order)
+}
+
+/// Every star definition a build wrote to `dist`, splits included.
+fn outputs(built: &BuiltStar) -> Vec<Star> {
+    let mut stars: Vec<Star> = built.tarball.is_some().then(|| built.star.clone()).into_iter().collect();
+    stars.extend(built.splits.iter().flat_map(outputs));
+    stars
+}
+
+/// The star definitions a previous build left in `dist`, if all of them and
+/// their tarballs are still there.
+fn previous_outputs(dist: &Path, cached: &CachedBuild) -> Option<Vec<Star>> {
+    cached
+        .outputs
+        .iter()
+        .map(|name| {
+            let star: Star = toml::from_str(&fs::read_to_string(dist.join(format!("{}.toml", name))).ok()?).ok()?;
+            let tarball = star.source.as_deref().and_then(|source| source.rsplit('/').next());
+            tarball.is_none_or(|tarball| dist.join(tarball).exists()).then_some(star)
+        })
+        .collect()
+}
+
+/// Run one build job, turning a panic into a failed build so the scheduler
+/// still hears back from the worker.
+fn catch_panic<T>(job: impl FnOnce() -> Result<T, String>) -> Result<T, String> {
+    panic::catch_unwind(AssertUnwindSafe(job)).unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown cause".to_string());
+        Err(format!("build panicked: {}", message))
+    })
+}
+
+/// Build every star directory in `path` in dependency order, `jobs` at a
+/// time, into `path/dist`, then index the results into `galaxy_path`. Stars
+/// whose inputs hash the same as at their last build are reused. `keep_mtimes`
+/// is passed on to [`build_star::build`].
+pub fn build_galaxy(
+    path: &str,
+    galaxy_path: &str,
+    jobs: Option<usize>,
+    force: bool,
+    checksum: Option<bool>,
+    keep_mtimes: bool,
+    prompter: &Prompter,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let root = Path::new(path);
+    let dist = root.join("dist");
+    let include_checksum = prompter.confirm("🔍 Include checksums?", "--checksum or --no-checksum", checksum, true)?;
+    let workers = jobs.unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get())).max(1);
+
+    let stars = discover(root)?;
+    if stars.is_empty() {
+        return Err(format!("❌ Error: No star directories found in {}", root.display()).into());
+    }
+    let order = build_order(&stars)?;
+    println!("🌌 Building {} star(s) with {} job(s)", stars.len(), workers);
+
+    let cache_path = root.join(CACHE_FILE);
+    let mut cache: BuildCache = match fs::read_to_string(&cache_path) {
+        Ok(content) => toml::from_str(&content).unwrap_or_default(),
+        Err(_) => BuildCache::default(),
+    };
+
+    // a star's inputs cover its dependencies' inputs, so changes ripple down the graph
+    let epoch = std::env::var("SOURCE_DATE_EPOCH").unwrap_or_default();
+    let mut inputs = vec![String::new(); stars.len()];
+    for &i in &order {
+        let mut hasher = Sha256::new();
+        hasher.update(hash_dir(&stars[i].dir)?);
+        for &dep in &stars[i].deps {
+            hasher.update(&inputs[dep]);
+        }
+        hasher.update(format!("checksum={} epoch={} keep_mtimes={}", include_checksum, epoch, keep_mtimes));
+        inputs[i] = format!("{:x}", hasher.finalize());
+    }
+
+    let mut galaxies = build_star::target_galaxies(Some(galaxy_path))?;
+    // what this run builds provides libraries to the stars after it
+    galaxies.push(Galaxy {
+        name: "build-galaxy".to_string(),
+        url: None,
+        stars: HashMap::new(),
+        checksums: None,
+        nova_lib: HashMap::new(),
+    });
+    let built_galaxy = galaxies.len() - 1;
+
+    let mut waiting: Vec<usize> = stars.iter().map(|job| job.deps.len()).collect();
+    let mut ready: BTreeSet<usize> = (0..stars.len()).filter(|&i| waiting[i] == 0).collect();
+    let mut failed = BTreeSet::new();
+    let mut blocked = BTreeSet::new();
+    let mut up_to_date = BTreeSet::new();
+    let (mut built, mut in_flight) = (0, 0);
+    fs::create_dir_all(&dist)?;
+
+    thread::scope(|scope| -> Result<(), Box<dyn std::error::Error>> {
+        let (tx, rx) = mpsc::channel::<(usize, Result<Vec<Star>, String>)>();
+        loop {
+            while in_flight < workers {
+                let Some(i) = ready.pop_first() else { break };
+                let job = &stars[i];
+
+                let previous = cache.stars.get(&job.name).filter(|cached| !force && cached.inputs == inputs[i]);
+                if let Some(outputs) = previous.and_then(|cached| previous_outputs(&dist, cached)) {
+                    println!("⏩ {} is up to date", job.name);
+                    up_to_date.insert(i);
+                    tx.send((i, Ok(outputs)))?;
+                    in_flight += 1;
+                    continue;
+                }
+
+                if matches!(job.star.star_type.as_deref(), Some("nebula") | Some("meta")) {
+                    println!("🌀 {} has nothing to build; copying its definition", job.name);
+                    fs::copy(job.dir.join("star.toml"), dist.join(format!("{}.toml", job.star.name)))?;
+                    tx.send((i, Ok(vec![job.star.clone()])))?;
+                    in_flight += 1;
+                    continue;
+                }
+
+                println!("🔧 Building {}", job.name);
+                let tx = tx.clone();
+                let galaxies = galaxies.clone();
+                let (dir, dist) = (job.dir.clone(), dist.clone());
+                scope.spawn(move || {
+                    let result = catch_panic(|| {
+                        build_star::build(&dir, include_checksum, keep_mtimes, &dist, &galaxies)
+                            .map(|built| outputs(&built))
+                            .map_err(|e| e.to_string())
+                    });
+                    let _ = tx.send((i, result));
+                });
+                in_flight += 1;
+            }
+
+            if in_flight == 0 {
+                break;
+            }
+            // every worker reports, even when it panics, so a closed channel is a bug
+            let (i, result) = rx
+                .recv()
+                .map_err(|_| "❌ Error: A build worker stopped without reporting a result")?;
+            in_flight -= 1;
+            let job = &stars[i];
+
+            match result {
+                Ok(outputs) => {
+                    if !up_to_date.contains(&i) {
+                        built += 1;
+                    }
+                    cache.stars.insert(job.name.clone(), CachedBuild {
+                        inputs: inputs[i].clone(),
+                        outputs: outputs.iter().map(|star| star.name.clone()).collect(),
+                    });
+                    for star in outputs {
+                        galaxies[built_galaxy].stars.insert(star.name.clone(), star);
+                    }
+                    for (j, other) in stars.iter().enumerate() {
+                        if other.deps.contains(&i) {
+                            waiting[j] -= 1;
+                            if waiting[j] == 0 && !blocked.contains(&j) {
+                                ready.insert(j);
+                            }
+                        }
+                    }
+                }
+                Err(e) => {
+                    println!("❌ {} failed: {}", job.name, e.trim_start_matches("❌ Error: "));
+                    cache.stars.remove(&job.name);
+                    failed.insert(i);
+                    // everything depending on it, directly or not, waits forever
+                    let mut queue = vec![i];
+                    while let Some(k) = queue.pop() {
+                        for (j, other) in stars.iter().enumerate() {
+                            if other.deps.contains(&k) && blocked.insert(j) {
+                                println!("⏭️  Skipping {}: depends on {}", other.name, stars[k].name);
+                                queue.push(j);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    })?;
+
+    fs::write(&cache_path, toml::to_string_pretty(&cache)?)?;
+    println!("📊 Built {}, up to date {}, failed {}, skipped {}", built, up_to_date.len(), failed.len(), blocked.len());
+
+    if !failed.is_empty() {
+        return Err(format!("❌ Error: {} star(s) failed to build; the galaxy was not indexed.", failed.len()).into());
+    }
+    galaxy::index_galaxy(galaxy_path, Some(&dist.to_string_lossy()), Some(include_checksum), prompter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn panicking_jobs_become_failures() {
+        assert_eq!(catch_panic(|| Ok::<_, String>(1)), Ok(1));
+        assert_eq!(catch_panic(|| Err::<(), _>("no star.toml".to_string())), Err("no star.toml".to_string()));
+        let result: Result<(), String> = catch_panic(|| panic!("index out of bounds"));
+        assert_eq!(result, Err("build panicked: index out of bounds".to_string()));
+        let result: Result<(), String> = catch_panic(|| panic!("{} broke", "strip"));
+        assert_eq!(result, Err("build panicked: strip broke".to_string()));
+    }
+}
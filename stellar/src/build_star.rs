@@ -1,25 +1,72 @@
 use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::fs::File;
-use std::process;
-use tar::Builder;
-use flate2::write::GzEncoder;
-use flate2::Compression;
-use fs_extra::copy_items;
+use std::os::unix::fs::PermissionsExt;
+use tar::{Builder, EntryType, Header};
+use flate2::{Compression, GzBuilder};
+use filetime::FileTime;
 use tempfile::tempdir;
 use fs_extra::dir::CopyOptions;
+use cosmos_core::config::Config;
+use cosmos_core::galaxy::Galaxy;
 use cosmos_core::star::Star;
-use dialoguer::{Input};
+use crate::linkage;
+use crate::postprocess;
+use crate::split;
+use crate::prompt::Prompter;
+use crate::recipe;
 use cosmos_core::resolver::calculate_checksum;
+use nova::{Capability, NovaRuntime};
 
-pub fn build_star(path: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let dir = Path::new(path);
+/// What `build` produced: the star definition as written to the output
+/// directory, its tarball unless the star has nothing to package, and any
+/// split stars built alongside it.
+pub struct BuiltStar {
+    pub star: Star,
+    pub tarball: Option<PathBuf>,
+    pub splits: Vec<BuiltStar>, // outputs of `[[split]]`
+}
+
+pub fn build_star(
+    path: &str,
+    checksum: Option<bool>,
+    galaxy: Option<&str>,
+    keep_mtimes: bool,
+    prompter: &Prompter,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let include_checksum = prompter.confirm("🔍 Include checksum?", "--checksum or --no-checksum", checksum, true)?;
+
+    let galaxies = target_galaxies(galaxy)?;
+    build(Path::new(path), include_checksum, keep_mtimes, Path::new("dist"), &galaxies)?;
+    Ok(())
+}
+
+/// The galaxies whose stars can satisfy detected library dependencies: the
+/// one given with `--galaxy`, or the host's galaxies read from their local copies.
+pub fn target_galaxies(galaxy: Option<&str>) -> Result<Vec<Galaxy>, Box<dyn std::error::Error>> {
+    if let Some(path) = galaxy {
+        return Ok(vec![Galaxy::load(Path::new(path), None, true)?]);
+    }
+    match Config::from_file("/etc/cosmos/config.toml") {
+        Ok(host) => Ok(Galaxy::load_all_from_config(&host, true)?),
+        Err(_) => Ok(vec![]),
+    }
+}
+
+/// Build the star in `dir` into `out_dir` without asking anything. Library
+/// dependencies are looked up in `galaxies`.
+pub fn build(
+    dir: &Path,
+    include_checksum: bool,
+    keep_mtimes: bool,
+    out_dir: &Path,
+    galaxies: &[Galaxy],
+) -> Result<BuiltStar, Box<dyn std::error::Error>> {
     let star_path = dir.join("star.toml");
 
     if !star_path.exists() {
-        eprintln!("❌ Error: star.toml not found in {}", dir.display());
-        process::exit(1);
+        return Err(format!("❌ Error: star.toml not found in {}", dir.display()).into());
     }
 
     let star_str = fs::read_to_string(&star_path)?;
@@ -27,8 +74,7 @@ pub fn build_star(path: &str) -> Result<(), Box<dyn std::error::Error>> {
 
     // if is a nebula or meta, error
     if star.star_type.as_deref() == Some("nebula") || star.star_type.as_deref() == Some("meta") {
-        eprintln!("❌ Error: Nebula or meta stars cannot be built.");
-        process::exit(1);
+        return Err("❌ Error: Nebula or meta stars cannot be built.".into());
     }
 
     let install_lua = dir.join("install.lua");
@@ -37,8 +83,7 @@ pub fn build_star(path: &str) -> Result<(), Box<dyn std::error::Error>> {
     let mut install_script = star.install_script.clone();
     println!("🔍 Found star definition: {}", star_path.display());
     if install_lua.exists() && install_sh.exists() {
-        eprintln!("❌ Error: Both install.lua and install.sh exist. Please use only one.");
-        process::exit(1);
+        return Err("❌ Error: Both install.lua and install.sh exist. Please use only one.".into());
     }
     if install_lua.exists() {
         println!("🔍 Found Lua install script: {}", install_lua.display());
@@ -58,58 +103,81 @@ pub fn build_star(path: &str) -> Result<(), Box<dyn std::error::Error>> {
         println!("📁 Found files directory: {}", dir.join("files").display());
     }
 
-    // ask user if they want to include checksum
-    let include_checksum: bool = Input::new()
-        .with_prompt("🔍 Include checksum?")
-        .default(true)
-        .interact()?;
-
     let temp = tempdir()?;
     let staging = temp.path();
+    let staging_files_dir = staging.join("files");
 
-    if install_lua.exists() {
-        nova::run_nova_build_script(install_lua.to_str().unwrap(), staging, staging)
-            .map_err(|e| format!("Nova build error: {:?}", e))?;
+    if files_exists {
+        copy_tree(&dir.join("files"), &staging_files_dir)?;
     }
 
-    if files_exists {
-        let files_dir = dir.join("files");
-        let mut checksums: HashMap<String, String> = HashMap::new();
+    let nova_lib = dir.join("nova-lib");
+    // a recipe's install.lua only travels with the star if it also installs
+    let mut ship_install_lua = install_lua.exists();
+    let is_recipe = star.sources.as_ref().is_some_and(|sources| !sources.is_empty());
+    if is_recipe {
+        let build_dir = tempdir()?;
+        let has_install = recipe::build_recipe(dir, &star, build_dir.path(), &staging_files_dir)?;
+        if !has_install {
+            ship_install_lua = false;
+            install_script = star.install_script.clone();
+        }
+    } else if install_lua.exists() {
+        NovaRuntime::new(staging, staging)
+            .with_capability(Capability::Archive)
+            .with_capability(Capability::Net)
+            .module_dir(&nova_lib)
+            .entry("build")
+            .allow_missing_entry()
+            .run(install_lua.to_str().unwrap())
+            .map_err(|e| format!("Nova build error: {}", e))?;
+    }
 
-        let entries = fs::read_dir(&files_dir)?
-            .map(|res| res.map(|e| e.path()))
-            .collect::<Result<Vec<_>, std::io::Error>>()?;
+    // strip and tidy the staged files, keeping any debug info as a split of its own
+    let mut carved = star.clone();
+    if let Some(debug) = postprocess::run(&star, &staging_files_dir)? {
+        carved.split.get_or_insert_with(Vec::new).insert(0, debug);
+    }
 
-        if include_checksum {
-            for entry in walkdir::WalkDir::new(&files_dir).into_iter().filter_map(|e| e.ok()) {
-                let relative_path = entry.path().strip_prefix(&files_dir).unwrap();
-                if entry.file_type().is_file() {
-                    let checksum = calculate_checksum(entry.path())?;
-                    checksums.insert(relative_path.to_string_lossy().to_string(), checksum);
-                }
-            }
-            star.checksums = Some(checksums);
+    if ship_install_lua {
+        for (source, line, split) in split::carved_sources(&star, &fs::read_to_string(&install_lua)?) {
+            println!("⚠️  install.lua:{}: '{}' is moved into split '{}' and will be missing at install time", line, source, split);
         }
+    }
 
-        let mut opts = CopyOptions::new();
-        opts.copy_inside = true;
-        opts.overwrite = true;
+    // split outputs take their files out of the staging root before anything is hashed
+    let mut splits = split::carve(&carved, &staging_files_dir)?;
+    let files_exists = staging_files_dir.exists();
+
+    // outputs of one build can satisfy each other's libraries
+    let main_scan = linkage::scan(&staging_files_dir)?;
+    let split_scans = splits.iter().map(|s| linkage::scan(&s.files_dir())).collect::<Result<Vec<_>, _>>()?;
+    let mut siblings = vec![star.clone()];
+    siblings[0].provides = main_scan.provides();
+    for (output, scan) in splits.iter().zip(&split_scans) {
+        let mut sibling = output.star.clone();
+        sibling.provides = scan.provides();
+        siblings.push(sibling);
+    }
+    let sibling_names: Vec<String> = siblings.iter().map(|s| s.name.clone()).collect();
 
-        // PATCHED: copy to staging/files/
-        let staging_files_dir = staging.join("files");
-        fs::create_dir_all(&staging_files_dir)?;
-        copy_items(&entries, &staging_files_dir, &opts)?;
+    if files_exists {
+        linkage::check(&mut star, &main_scan, &siblings, galaxies);
+        if include_checksum {
+            star.checksums = Some(file_checksums(&staging_files_dir)?);
+        }
     }
+    split::pin_siblings(&mut star, &sibling_names);
 
     // if files, install.sh, or install.lua exist, source is set as there is a tarball
     // if is a nebula, tarball not needed
-    let needs_tarball = files_exists || install_lua.exists() || install_sh.exists();
+    let needs_tarball = files_exists || ship_install_lua || install_sh.exists();
 
     if needs_tarball {
         star.source = Some(format!("./packages/{}-{}.tar.gz", star.name, star.version));
     }
 
-    if install_lua.exists() {
+    if ship_install_lua {
         let target = staging.join("install.lua");
         println!("📁 Copying Lua script → {}", target.display());
         fs::copy(&install_lua, &target)?;
@@ -120,22 +188,188 @@ pub fn build_star(path: &str) -> Result<(), Box<dyn std::error::Error>> {
     }
     star.install_script = install_script;
 
+    // modules the scripts load with require() travel inside the tarball
+    if nova_lib.exists() {
+        println!("📁 Copying Nova modules → {}", staging.join("nova-lib").display());
+        let mut opts = CopyOptions::new();
+        opts.overwrite = true;
+        fs_extra::dir::copy(&nova_lib, staging, &opts)?;
+    }
+
+    let mut built_splits = vec![];
+    for (output, scan) in splits.iter_mut().zip(&split_scans) {
+        if !output.files_dir().exists() {
+            continue;
+        }
+        linkage::check(&mut output.star, scan, &siblings, galaxies);
+        split::pin_siblings(&mut output.star, &sibling_names);
+        if include_checksum {
+            output.star.checksums = Some(file_checksums(&output.files_dir())?);
+        }
+        built_splits.push(package(output.star.clone(), output.root(), out_dir, keep_mtimes)?);
+    }
+
     if needs_tarball {
-        fs::create_dir_all("dist")?;
-        let tar_path = format!("dist/{}-{}.tar.gz", star.name, star.version);
-        let tar_gz = File::create(&tar_path)?;
-        let enc = GzEncoder::new(tar_gz, Compression::default());
-        let mut tar = Builder::new(enc);
-        tar.append_dir_all(".", staging)?;
-        tar.finish()?;
-
-        // update star toml
-        let toml_str = toml::to_string_pretty(&star)?;
-        fs::write(format!("dist/{}.toml", star.name), toml_str)?;
-
-        println!("✅ Successfully built star package: {}", tar_path);
+        let mut built = package(star, staging, out_dir, keep_mtimes)?;
+        built.splits = built_splits;
+        Ok(built)
     } else {
         println!("✅ Successfully built star package: No tarball created.");
+        Ok(BuiltStar { star, tarball: None, splits: built_splits })
+    }
+}
+
+/// Copy `from` into `to`, keeping symlinks as symlinks and file modes as they are.
+pub fn copy_tree(from: &Path, to: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    fs::create_dir_all(to)?;
+    for entry in walkdir::WalkDir::new(from).min_depth(1).sort_by_file_name() {
+        let entry = entry?;
+        let dest = to.join(entry.path().strip_prefix(from)?);
+        if entry.path_is_symlink() {
+            std::os::unix::fs::symlink(fs::read_link(entry.path())?, &dest)?;
+        } else if entry.file_type().is_dir() {
+            fs::create_dir_all(&dest)?;
+            fs::set_permissions(&dest, entry.metadata()?.permissions())?;
+        } else {
+            fs::copy(entry.path(), &dest)?;
+        }
+        let mtime = FileTime::from_last_modification_time(&entry.path().symlink_metadata()?);
+        filetime::set_symlink_file_times(&dest, mtime, mtime)?;
+    }
+    // copying into a directory touched it, so restore directory mtimes last
+    for entry in walkdir::WalkDir::new(from).min_depth(1).contents_first(true) {
+        let entry = entry?;
+        if entry.file_type().is_dir() {
+            let mtime = FileTime::from_last_modification_time(&entry.metadata()?);
+            filetime::set_file_mtime(to.join(entry.path().strip_prefix(from)?), mtime)?;
+        }
+    }
+    Ok(())
+}
+
+/// Checksums of every regular file under `files_dir`, keyed by relative path.
+fn file_checksums(files_dir: &Path) -> Result<HashMap<String, String>, Box<dyn std::error::Error>> {
+    let mut checksums = HashMap::new();
+    for entry in walkdir::WalkDir::new(files_dir).into_iter().filter_map(|e| e.ok()) {
+        let relative_path = entry.path().strip_prefix(files_dir)?;
+        if entry.file_type().is_file() {
+            let checksum = calculate_checksum(entry.path())?;
+            checksums.insert(relative_path.to_string_lossy().to_string(), checksum);
+        }
+    }
+    Ok(checksums)
+}
+
+/// Write the tarball of `staging` and the star definition into `out_dir`.
+fn package(mut star: Star, staging: &Path, out_dir: &Path, keep_mtimes: bool) -> Result<BuiltStar, Box<dyn std::error::Error>> {
+    star.source = Some(format!("./packages/{}-{}.tar.gz", star.name, star.version));
+
+    fs::create_dir_all(out_dir)?;
+    let tar_path = out_dir.join(format!("{}-{}.tar.gz", star.name, star.version));
+    write_tarball(staging, &tar_path, keep_mtimes)?;
+
+    // update star toml
+    let toml_str = toml::to_string_pretty(&star)?;
+    fs::write(out_dir.join(format!("{}.toml", star.name)), toml_str)?;
+
+    println!("✅ Successfully built star package: {}", tar_path.display());
+    Ok(BuiltStar { star, tarball: Some(tar_path), splits: vec![] })
+}
+
+/// `SOURCE_DATE_EPOCH`, if set.
+fn source_date_epoch() -> Result<Option<u64>, Box<dyn std::error::Error>> {
+    match std::env::var("SOURCE_DATE_EPOCH") {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("❌ Error: SOURCE_DATE_EPOCH must be a number of seconds, got '{}'", value).into()),
+        Err(_) => Ok(None),
     }
+}
+
+/// The mtime stamped on a tarball entry: the file's own, clamped to
+/// `SOURCE_DATE_EPOCH` when set, otherwise 0. `keep_mtimes` keeps the file's
+/// own mtime when there is no epoch, at the cost of reproducibility.
+fn entry_mtime(file_mtime: u64, epoch: Option<u64>, keep_mtimes: bool) -> u64 {
+    match epoch {
+        Some(epoch) => file_mtime.min(epoch),
+        None if keep_mtimes => file_mtime,
+        None => 0,
+    }
+}
+
+/// Pack `staging` so the same inputs always give the same bytes: entries in
+/// sorted order, owned by 0:0, mtimes from [`entry_mtime`], and a gzip header
+/// without a name or timestamp.
+pub fn write_tarball(staging: &Path, tar_path: &Path, keep_mtimes: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let epoch = source_date_epoch()?;
+    let gz = GzBuilder::new()
+        .mtime(0)
+        .operating_system(255)
+        .write(File::create(tar_path)?, Compression::default());
+    let mut tar = Builder::new(gz);
+
+    for entry in walkdir::WalkDir::new(staging).min_depth(1).sort_by_file_name() {
+        let entry = entry?;
+        let relative = entry.path().strip_prefix(staging)?;
+        let metadata = entry.path().symlink_metadata()?;
+
+        let mut header = Header::new_gnu();
+        header.set_uid(0);
+        header.set_gid(0);
+        let file_mtime = FileTime::from_last_modification_time(&metadata).unix_seconds().max(0) as u64;
+        header.set_mtime(entry_mtime(file_mtime, epoch, keep_mtimes));
+        header.set_mode(metadata.permissions().mode() & 0o7777);
+
+        if metadata.file_type().is_symlink() {
+            header.set_entry_type(EntryType::Symlink);
+            header.set_size(0);
+            tar.append_link(&mut header, relative, fs::read_link(entry.path())?)?;
+        } else if metadata.is_dir() {
+            header.set_entry_type(EntryType::Directory);
+            header.set_size(0);
+            tar.append_data(&mut header, relative, std::io::empty())?;
+        } else {
+            header.set_entry_type(EntryType::Regular);
+            header.set_size(metadata.len());
+            tar.append_data(&mut header, relative, File::open(entry.path())?)?;
+        }
+    }
+
+    tar.into_inner()?.finish()?;
     Ok(())
-} 
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entry_mtimes_are_clamped_to_the_epoch() {
+        assert_eq!(entry_mtime(1_700_000_000, None, false), 0);
+        assert_eq!(entry_mtime(1_700_000_000, None, true), 1_700_000_000);
+        assert_eq!(entry_mtime(1_700_000_000, Some(1_600_000_000), false), 1_600_000_000);
+        assert_eq!(entry_mtime(1_500_000_000, Some(1_600_000_000), true), 1_500_000_000);
+    }
+
+    #[test]
+    fn copy_tree_keeps_mtimes() {
+        let temp = tempdir().unwrap();
+        let (from, to) = (temp.path().join("from"), temp.path().join("to"));
+        fs::create_dir_all(from.join("usr/bin")).unwrap();
+        fs::write(from.join("usr/bin/tool"), "#!/bin/sh\n").unwrap();
+        fs::set_permissions(from.join("usr/bin/tool"), fs::Permissions::from_mode(0o555)).unwrap();
+        std::os::unix::fs::symlink("tool", from.join("usr/bin/alias")).unwrap();
+        let old = FileTime::from_unix_time(1_000_000, 0);
+        filetime::set_file_mtime(from.join("usr/bin/tool"), old).unwrap();
+        filetime::set_file_mtime(from.join("usr/bin"), old).unwrap();
+
+        copy_tree(&from, &to).unwrap();
+        for path in ["usr/bin/tool", "usr/bin"] {
+            let meta = fs::metadata(to.join(path)).unwrap();
+            assert_eq!(FileTime::from_last_modification_time(&meta), old, "{}", path);
+        }
+        assert_eq!(fs::read_link(to.join("usr/bin/alias")).unwrap(), Path::new("tool"));
+    }
+}
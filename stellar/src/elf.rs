@@ -0,0 +1,234 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// The dynamic-linking facts stellar cares about in one ELF file.
+#[derive(Debug, Default)]
+pub struct ElfInfo {
+    pub interpreter: Option<String>,
+    pub needed: Vec<String>,
+    pub soname: Option<String>,
+}
+
+impl ElfInfo {
+    pub fn is_dynamic(&self) -> bool {
+        self.interpreter.is_some() || !self.needed.is_empty()
+    }
+}
+
+const PT_LOAD: u32 = 1;
+const PT_DYNAMIC: u32 = 2;
+const PT_INTERP: u32 = 3;
+const DT_NULL: u64 = 0;
+const DT_NEEDED: u64 = 1;
+const DT_STRTAB: u64 = 5;
+const DT_SONAME: u64 = 14;
+
+/// Reads fields of either width and byte order.
+struct Reader<'a> {
+    data: &'a [u8],
+    wide: bool,
+    little: bool,
+}
+
+impl Reader<'_> {
+    fn bytes<const N: usize>(&self, at: usize) -> Option<[u8; N]> {
+        self.data.get(at..at.checked_add(N)?)?.try_into().ok()
+    }
+
+    fn u16(&self, at: usize) -> Option<u16> {
+        let b = self.bytes::<2>(at)?;
+        Some(if self.little { u16::from_le_bytes(b) } else { u16::from_be_bytes(b) })
+    }
+
+    fn u32(&self, at: usize) -> Option<u32> {
+        let b = self.bytes::<4>(at)?;
+        Some(if self.little { u32::from_le_bytes(b) } else { u32::from_be_bytes(b) })
+    }
+
+    fn u64(&self, at: usize) -> Option<u64> {
+        let b = self.bytes::<8>(at)?;
+        Some(if self.little { u64::from_le_bytes(b) } else { u64::from_be_bytes(b) })
+    }
+
+    /// An address, offset or size: 8 bytes in ELF64, 4 in ELF32.
+    fn word(&self, at: usize) -> Option<u64> {
+        if self.wide { self.u64(at) } else { self.u32(at).map(u64::from) }
+    }
+
+    fn c_str(&self, at: usize) -> Option<String> {
+        let rest = self.data.get(at..)?;
+        let end = rest.iter().position(|&b| b == 0)?;
+        Some(String::from_utf8_lossy(&rest[..end]).to_string())
+    }
+}
+
+struct Segment {
+    kind: u32,
+    offset: u64,
+    vaddr: u64,
+    filesz: u64,
+}
+
+/// Read `path` and return its linking information, or `None` if it is not an
+/// ELF file. Truncated or malformed ELF files are warned about and skipped.
+pub fn read(path: &Path) -> io::Result<Option<ElfInfo>> {
+    let data = fs::read(path)?;
+    if data.len() < 16 || &data[..4] != b"\x7fELF" {
+        return Ok(None);
+    }
+    let reader = Reader { data: &data, wide: data[4] == 2, little: data[5] == 1 };
+    let info = parse(&reader);
+    if info.is_none() {
+        println!("⚠️  Skipping {}: truncated or malformed ELF file", path.display());
+    }
+    Ok(info)
+}
+
+fn parse(r: &Reader) -> Option<ElfInfo> {
+    // program header table location differs between ELF32 and ELF64
+    let (phoff, phentsize, phnum) = if r.wide {
+        (r.u64(0x20)?, r.u16(0x36)?, r.u16(0x38)?)
+    } else {
+        (u64::from(r.u32(0x1c)?), r.u16(0x2a)?, r.u16(0x2c)?)
+    };
+
+    let mut segments = vec![];
+    for i in 0..usize::from(phnum) {
+        let at = usize::try_from(phoff).ok()?.checked_add(i * usize::from(phentsize))?;
+        let segment = if r.wide {
+            Segment { kind: r.u32(at)?, offset: r.u64(at + 8)?, vaddr: r.u64(at + 16)?, filesz: r.u64(at + 32)? }
+        } else {
+            Segment {
+                kind: r.u32(at)?,
+                offset: u64::from(r.u32(at + 4)?),
+                vaddr: u64::from(r.u32(at + 8)?),
+                filesz: u64::from(r.u32(at + 16)?),
+            }
+        };
+        segments.push(segment);
+    }
+
+    let mut info = ElfInfo::default();
+    if let Some(interp) = segments.iter().find(|s| s.kind == PT_INTERP) {
+        info.interpreter = r.c_str(usize::try_from(interp.offset).ok()?);
+    }
+
+    let Some(dynamic) = segments.iter().find(|s| s.kind == PT_DYNAMIC) else {
+        return Some(info);
+    };
+
+    let entry_size = if r.wide { 16 } else { 8 };
+    let mut needed = vec![];
+    let mut soname = None;
+    let mut strtab = None;
+    for i in 0..usize::try_from(dynamic.filesz).ok()? / entry_size {
+        let at = usize::try_from(dynamic.offset).ok()?.checked_add(i * entry_size)?;
+        let tag = r.word(at)?;
+        let value = r.word(at + entry_size / 2)?;
+        match tag {
+            DT_NULL => break,
+            DT_NEEDED => needed.push(value),
+            DT_SONAME => soname = Some(value),
+            DT_STRTAB => strtab = Some(value),
+            _ => {}
+        }
+    }
+
+    // DT_STRTAB is a virtual address; find the loaded segment that holds it
+    let strtab = strtab?;
+    let load = segments
+        .iter()
+        .find(|s| s.kind == PT_LOAD && strtab >= s.vaddr && s.vaddr.checked_add(s.filesz).is_some_and(|end| strtab < end))?;
+    let base = (strtab - load.vaddr).checked_add(load.offset)?;
+    let string = |offset: u64| r.c_str(usize::try_from(base.checked_add(offset)?).ok()?);
+
+    info.needed = needed.into_iter().filter_map(string).collect();
+    info.soname = soname.and_then(string);
+    Some(info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VADDR: u64 = 0x400000;
+
+    /// A little-endian ELF64 file with PT_LOAD, PT_INTERP and PT_DYNAMIC
+    /// segments, one DT_NEEDED entry and a DT_SONAME.
+    fn elf64() -> Vec<u8> {
+        let mut data = vec![0u8; 352];
+        data[..6].copy_from_slice(b"\x7fELF\x02\x01");
+        data[0x20..0x28].copy_from_slice(&64u64.to_le_bytes());
+        data[0x36..0x38].copy_from_slice(&56u16.to_le_bytes());
+        data[0x38..0x3a].copy_from_slice(&3u16.to_le_bytes());
+
+        let segments = [(PT_LOAD, 0, VADDR, 352), (PT_INTERP, 232, 0, 25), (PT_DYNAMIC, 288, 0, 64)];
+        for (i, (kind, offset, vaddr, filesz)) in segments.into_iter().enumerate() {
+            let at = 64 + i * 56;
+            data[at..at + 4].copy_from_slice(&kind.to_le_bytes());
+            data[at + 8..at + 16].copy_from_slice(&(offset as u64).to_le_bytes());
+            data[at + 16..at + 24].copy_from_slice(&vaddr.to_le_bytes());
+            data[at + 32..at + 40].copy_from_slice(&(filesz as u64).to_le_bytes());
+        }
+
+        data[232..257].copy_from_slice(b"/lib/ld-musl-x86_64.so.1\0");
+        data[260..281].copy_from_slice(b"\0libc.so\0libfoo.so.1\0");
+        let dynamic = [(DT_NEEDED, 1), (DT_SONAME, 9), (DT_STRTAB, VADDR + 260), (DT_NULL, 0)];
+        for (i, (tag, value)) in dynamic.into_iter().enumerate() {
+            let at = 288 + i * 16;
+            data[at..at + 8].copy_from_slice(&tag.to_le_bytes());
+            data[at + 8..at + 16].copy_from_slice(&value.to_le_bytes());
+        }
+        data
+    }
+
+    fn reader(data: &[u8]) -> Reader<'_> {
+        Reader { data, wide: true, little: true }
+    }
+
+    #[test]
+    fn parses_interpreter_needed_and_soname() {
+        let data = elf64();
+        let info = parse(&reader(&data)).unwrap();
+        assert_eq!(info.interpreter.as_deref(), Some("/lib/ld-musl-x86_64.so.1"));
+        assert_eq!(info.needed, vec!["libc.so".to_string()]);
+        assert_eq!(info.soname.as_deref(), Some("libfoo.so.1"));
+        assert!(info.is_dynamic());
+    }
+
+    #[test]
+    fn truncated_files_do_not_parse() {
+        let data = elf64();
+        assert!(parse(&reader(&data[..200])).is_none());
+        assert!(parse(&reader(&data[..300])).is_none());
+    }
+
+    #[test]
+    fn overflowing_segments_do_not_panic() {
+        let mut data = elf64();
+        // PT_LOAD near the top of the address space whose end overflows
+        let vaddr = u64::MAX - 400;
+        data[64 + 16..64 + 24].copy_from_slice(&vaddr.to_le_bytes());
+        data[64 + 32..64 + 40].copy_from_slice(&u64::MAX.to_le_bytes());
+        data[288 + 40..288 + 48].copy_from_slice(&(vaddr + 260).to_le_bytes());
+        assert!(parse(&reader(&data)).is_none());
+
+        let mut data = elf64();
+        data[0x20..0x28].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(parse(&reader(&data)).is_none());
+    }
+
+    #[test]
+    fn read_skips_malformed_files() {
+        let temp = tempfile::tempdir().unwrap();
+        let (good, bad, text) = (temp.path().join("good"), temp.path().join("bad"), temp.path().join("text"));
+        fs::write(&good, elf64()).unwrap();
+        fs::write(&bad, &elf64()[..200]).unwrap();
+        fs::write(&text, "#!/bin/sh\n").unwrap();
+
+        assert!(read(&good).unwrap().is_some());
+        assert!(read(&bad).unwrap().is_none());
+        assert!(read(&text).unwrap().is_none());
+    }
+}
@@ -9,11 +9,18 @@ pub fn fetch(path: &str) -> Result<(), Box<dyn std::error::Error>> {
     let star_str = fs::read_to_string(&star_path)?;
     let star: Star = toml::from_str(&star_str)?;
 
+    // recipes declare their upstream files as [[sources]]
+    if star.sources.as_ref().is_some_and(|sources| !sources.is_empty()) {
+        let fetched = crate::recipe::fetch_sources(dir, &star)?;
+        println!("✅ {} source(s) ready", fetched.len());
+        return Ok(());
+    }
+
     let source = star.source.as_ref()
         .ok_or("No `source` field in star.toml")?;
 
     if source.starts_with("http://") {
-        let filename = source.split('/').last().unwrap_or("source.tar.gz");
+        let filename = source.split('/').next_back().unwrap_or("source.tar.gz");
         let target = dir.join(".cache").join("src").join(filename);
         fs::create_dir_all(target.parent().unwrap())?;
 
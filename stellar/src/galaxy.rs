@@ -1,12 +1,13 @@
 use std::fs;
 use std::path::Path;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use serde::{Deserialize, Serialize};
 use cosmos_core::star::Star;
-use cosmos_core::galaxy::GalaxyMeta;
-use toml;
-use cosmos_core::resolver::calculate_checksum;
+use cosmos_core::galaxy::{GalaxyMeta, NovaLibModule};
+use cosmos_core::resolver::{calculate_checksum, satisfies_constraint};
+use crate::prompt::Prompter;
 
-pub fn galaxy_init(name: &str) -> Result<(), Box<dyn std::error::Error>> {
+pub fn galaxy_init(name: &str, checksums: Option<bool>, multi_version: bool, prompter: &Prompter) -> Result<(), Box<dyn std::error::Error>> {
     let path = format!("{}-galaxy", name);
     let dir = Path::new(&path);
 
@@ -17,11 +18,12 @@ pub fn galaxy_init(name: &str) -> Result<(), Box<dyn std::error::Error>> {
     fs::create_dir_all(dir.join("stars"))?;
     fs::create_dir_all(dir.join("packages"))?;
 
-    // prompt for if using checksum, use dialoguer
-    let use_checksum: bool = dialoguer::Confirm::new()
-        .with_prompt("Enable global checksums for star sources?")
-        .default(true)
-        .interact()?;
+    let use_checksum = prompter.confirm(
+        "Enable global checksums for star sources?",
+        "--checksums or --no-checksums",
+        checksums,
+        true,
+    )?;
 
     //().format("%Y.%m.%d").to_string();
     let today = chrono::Utc::now().format("%Y.%m.%d").to_string();
@@ -35,6 +37,8 @@ pub fn galaxy_init(name: &str) -> Result<(), Box<dyn std::error::Error>> {
         } else {
             None
         },
+        nova_lib: None,
+        multi_version: multi_version.then_some(true),
     };
 
     let meta_str = toml::to_string_pretty(&galaxy)?;
@@ -44,15 +48,123 @@ pub fn galaxy_init(name: &str) -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-pub fn index_galaxy(path: &str) -> Result<(), Box<dyn std::error::Error>> {
+/// Where `index-galaxy` remembers the size, mtime and checksum of every file
+/// it hashed, so unchanged files are not hashed again.
+const INDEX_CACHE: &str = ".index-cache.toml";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct IndexCache {
+    #[serde(default)]
+    files: BTreeMap<String, CachedHash>, // path relative to the galaxy → hash
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct CachedHash {
+    size: u64,
+    mtime_ns: i64,
+    checksum: String,
+}
+
+impl IndexCache {
+    fn load(root: &Path) -> Self {
+        fs::read_to_string(root.join(INDEX_CACHE))
+            .ok()
+            .and_then(|content| toml::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, root: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        fs::write(root.join(INDEX_CACHE), toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// The checksum of `path`, rehashed only if its size or mtime changed.
+    /// Returns the checksum and whether it had to be computed.
+    fn checksum(&mut self, root: &Path, path: &Path) -> Result<(String, bool), Box<dyn std::error::Error>> {
+        let key = path.strip_prefix(root)?.to_string_lossy().to_string();
+        let metadata = fs::metadata(path)?;
+        let mtime_ns = metadata
+            .modified()?
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as i64)
+            .unwrap_or(0);
+
+        if let Some(cached) = self.files.get(&key) {
+            if cached.size == metadata.len() && cached.mtime_ns == mtime_ns {
+                return Ok((cached.checksum.clone(), false));
+            }
+        }
+        let checksum = calculate_checksum(path)?;
+        self.files.insert(key, CachedHash { size: metadata.len(), mtime_ns, checksum: checksum.clone() });
+        Ok((checksum, true))
+    }
+}
+
+/// Copy `build-star` output from `dist` into the galaxy. A star replaced by a
+/// new version is moved to `stars/<name>/<version>.toml` and its tarball kept
+/// when the galaxy is multi-version; otherwise the old tarball is removed.
+fn ingest(root: &Path, dist: &Path, multi_version: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let mut definitions = fs::read_dir(dist)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, std::io::Error>>()?;
+    definitions.retain(|path| path.extension().is_some_and(|ext| ext == "toml"));
+    definitions.sort();
+
+    for definition in definitions {
+        let star: Star = toml::from_str(&fs::read_to_string(&definition)?)?;
+        let target = root.join("stars").join(format!("{}.toml", star.name));
+
+        let previous = fs::read_to_string(&target).ok().and_then(|content| toml::from_str::<Star>(&content).ok());
+        if let Some(previous) = previous {
+            if previous.version != star.version {
+                if multi_version {
+                    let archive = root.join("stars").join(&star.name).join(format!("{}.toml", previous.version));
+                    fs::create_dir_all(archive.parent().unwrap())?;
+                    fs::copy(&target, &archive)?;
+                    println!("📦 Kept {} {} in {}", previous.name, previous.version, archive.display());
+                } else if let Some(source) = previous.source.filter(|s| !s.contains("://")) {
+                    let old = root.join(source.trim_start_matches("./"));
+                    if old.exists() && star.source.as_deref() != Some(source.as_str()) {
+                        fs::remove_file(&old)?;
+                    }
+                }
+            }
+        }
+
+        if let Some(source) = star.source.as_deref().filter(|s| !s.contains("://")) {
+            let file_name = Path::new(source).file_name().ok_or("invalid source path")?;
+            let tarball = dist.join(file_name);
+            if !tarball.exists() {
+                return Err(format!("❌ Error: {} is missing from {}", file_name.to_string_lossy(), dist.display()).into());
+            }
+            let dest = root.join(source.trim_start_matches("./"));
+            fs::create_dir_all(dest.parent().unwrap())?;
+            fs::copy(&tarball, &dest)?;
+            // keep the build's mtime so an unchanged tarball is not hashed again
+            fs::File::options().write(true).open(&dest)?.set_modified(fs::metadata(&tarball)?.modified()?)?;
+        }
+        fs::copy(&definition, &target)?;
+        println!("📥 Ingested {} v{}", star.name, star.version);
+    }
+    Ok(())
+}
+
+pub fn index_galaxy(path: &str, from: Option<&str>, checksums: Option<bool>, prompter: &Prompter) -> Result<(), Box<dyn std::error::Error>> {
     let root = Path::new(path);
     let stars_dir = root.join("stars");
     let meta_path = root.join("meta.toml");
+    let mut meta: GalaxyMeta = toml::from_str(&fs::read_to_string(&meta_path)?)?;
+
+    if let Some(dist) = from {
+        ingest(root, Path::new(dist), meta.multi_version == Some(true))?;
+    }
 
-    let star_files = fs::read_dir(&stars_dir)?
+    let mut star_files = fs::read_dir(&stars_dir)?
         .filter_map(Result::ok)
-        .filter(|f| f.path().extension().map_or(false, |ext| ext == "toml"))
+        .map(|f| f.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "toml"))
         .collect::<Vec<_>>();
+    star_files.sort();
 
     if star_files.is_empty() {
         println!("⚠️  No star TOMLs found in stars/ directory.");
@@ -60,41 +172,77 @@ pub fn index_galaxy(path: &str) -> Result<(), Box<dyn std::error::Error>> {
         println!("🔍 Found {} star definitions.", star_files.len());
     }
 
+    let previous = meta.stars.clone().unwrap_or_default();
     let mut versions = HashMap::new();
-    for entry in star_files {
-        let path = entry.path();
+    let mut sources = HashMap::new();
+    for path in star_files {
         let content = fs::read_to_string(&path)?;
-        let star: Star = toml::from_str(&content)?;
-        println!("  - Indexed {} v{}", star.name, star.version);
+        let star: Star = match toml::from_str(&content) {
+            Ok(star) => star,
+            Err(e) => {
+                // keep what was indexed before rather than dropping the star
+                let name = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+                println!("⚠️  Could not parse {}: {}", path.display(), e);
+                if let Some(version) = previous.get(&name) {
+                    versions.insert(name, version.clone());
+                }
+                continue;
+            }
+        };
         versions.insert(star.name.clone(), star.version.clone());
+        if let Some(source) = star.source.filter(|s| !s.contains("://")) {
+            sources.insert(star.name, source);
+        }
     }
 
-    let use_checksum: bool = dialoguer::Confirm::new()
-        .with_prompt("Do you want to run checksum validation?")
-        .default(true)
-        .interact()?;
+    let use_checksum = prompter.confirm(
+        "Do you want to run checksum validation?",
+        "--checksums or --no-checksums",
+        checksums,
+        true,
+    )?;
 
+    let mut cache = IndexCache::load(root);
     let mut checksums: HashMap<String, String> = HashMap::new();
+    let mut hashed = 0;
+    let mut reused = 0;
 
     if use_checksum {
-        let packages_dir = root.join("packages");
-        // use star meta to get checksums of packages
-        for (name, version) in &versions {
-            let package_path = packages_dir.join(format!("{}-{}.tar.gz", name, version));
+        let mut names: Vec<&String> = versions.keys().collect();
+        names.sort();
+        for name in names {
+            let version = &versions[name];
+            let package_path = match sources.get(name) {
+                Some(source) => root.join(source.trim_start_matches("./")),
+                None => root.join("packages").join(format!("{}-{}.tar.gz", name, version)),
+            };
             if !package_path.exists() {
                 println!("⚠️  Package {} v{} not found in packages/ directory. Is this a nebulae?", name, version);
                 continue;
             }
 
-            let checksum = calculate_checksum(&package_path)?;
-            println!("🔒 Validated checksum for {} (v{}): {}", name, version, checksum);
+            let (checksum, fresh) = cache.checksum(root, &package_path)?;
+            if fresh {
+                println!("🔒 Hashed {} (v{}): {}", name, version, checksum);
+                hashed += 1;
+            } else {
+                reused += 1;
+            }
             checksums.insert(name.to_string(), checksum);
         }
     }
 
+    let nova_lib = index_nova_lib(root, use_checksum, &mut cache)?;
+    cache.files.retain(|path, _| root.join(path).exists());
+    cache.save(root)?;
+
+    print_summary(&previous, &versions);
+    if use_checksum {
+        println!("🔒 {} file(s) hashed, {} unchanged", hashed, reused);
+    }
 
-    let mut meta: GalaxyMeta = toml::from_str(&fs::read_to_string(&meta_path)?)?;
     meta.stars = Some(versions);
+    meta.nova_lib = if nova_lib.is_empty() { None } else { Some(nova_lib) };
     if use_checksum {
         meta.checksums = Some(checksums);
     } else {
@@ -106,4 +254,274 @@ pub fn index_galaxy(path: &str) -> Result<(), Box<dyn std::error::Error>> {
 
     println!("✅ Updated galaxy index at: {}", path);
     Ok(())
-}
\ No newline at end of file
+}
+
+fn print_summary(previous: &HashMap<String, String>, current: &HashMap<String, String>) {
+    let mut added = vec![];
+    let mut updated = vec![];
+    let mut removed = vec![];
+    for (name, version) in current {
+        match previous.get(name) {
+            None => added.push(format!("{} {}", name, version)),
+            Some(old) if old != version => updated.push(format!("{} {} → {}", name, old, version)),
+            Some(_) => {}
+        }
+    }
+    for (name, version) in previous {
+        if !current.contains_key(name) {
+            removed.push(format!("{} {}", name, version));
+        }
+    }
+    for (label, list) in [("➕ Added", &mut added), ("🔄 Updated", &mut updated), ("➖ Removed", &mut removed)] {
+        list.sort();
+        if !list.is_empty() {
+            println!("{}: {}", label, list.join(", "));
+        }
+    }
+    println!("📊 {} added, {} updated, {} removed", added.len(), updated.len(), removed.len());
+}
+
+/// Index the shared Nova modules under `nova-lib/`. A module's version comes
+/// from a `-- version: x.y.z` line in its header comment.
+fn index_nova_lib(root: &Path, use_checksum: bool, cache: &mut IndexCache) -> Result<HashMap<String, NovaLibModule>, Box<dyn std::error::Error>> {
+    let lib_dir = root.join("nova-lib");
+    let mut modules = HashMap::new();
+    if !lib_dir.exists() {
+        return Ok(modules);
+    }
+
+    for entry in walkdir::WalkDir::new(&lib_dir).sort_by_file_name().into_iter().filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if !entry.file_type().is_file() || path.extension().is_none_or(|ext| ext != "lua") {
+            continue;
+        }
+
+        let relative = path.strip_prefix(&lib_dir)?.with_extension("");
+        let name = relative.to_string_lossy().replace('/', ".");
+        if !nova::is_valid_module_name(&name) {
+            println!("⚠️  Skipping {}: not a valid module name", path.display());
+            continue;
+        }
+
+        let content = fs::read_to_string(path)?;
+        let version = content
+            .lines()
+            .take_while(|line| line.starts_with("--"))
+            .find_map(|line| line.trim_start_matches('-').trim().strip_prefix("version:"))
+            .map(|v| v.trim().to_string());
+        let version = match version {
+            Some(version) => version,
+            None => {
+                println!("⚠️  Nova module {} has no `-- version:` header, using 0.0.0", name);
+                "0.0.0".to_string()
+            }
+        };
+
+        let checksum = if use_checksum { Some(cache.checksum(root, path)?.0) } else { None };
+        println!("  - Indexed Nova module {} v{}", name, version);
+        modules.insert(name, NovaLibModule { version, checksum });
+    }
+
+    Ok(modules)
+}
+
+/// Audit a galaxy directory before publishing it: the index, star files,
+/// tarballs, checksums and the dependency graph must all agree.
+pub fn galaxy_check(path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let root = Path::new(path);
+    let meta: GalaxyMeta = toml::from_str(&fs::read_to_string(root.join("meta.toml"))?)?;
+    let mut problems = vec![];
+
+    // star files as Galaxy::load reads them, but reporting instead of skipping
+    let mut stars: BTreeMap<String, Star> = BTreeMap::new();
+    let mut files = BTreeSet::new();
+    let mut paths = fs::read_dir(root.join("stars"))?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, std::io::Error>>()?;
+    paths.sort();
+    for path in paths {
+        if path.extension().is_none_or(|ext| ext != "toml") {
+            continue;
+        }
+        let file_name = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        files.insert(file_name.clone());
+        match toml::from_str::<Star>(&fs::read_to_string(&path)?) {
+            Ok(star) if star.name != file_name => {
+                problems.push(format!("stars/{}.toml declares star '{}'", file_name, star.name));
+            }
+            Ok(star) => {
+                stars.insert(file_name, star);
+            }
+            Err(e) => problems.push(format!("stars/{}.toml could not be parsed: {}", file_name, e)),
+        }
+    }
+
+    let indexed: BTreeMap<String, String> = meta.stars.clone().unwrap_or_default().into_iter().collect();
+    for (name, version) in &indexed {
+        match stars.get(name) {
+            Some(star) if &star.version != version => problems.push(format!(
+                "{} is indexed as {} in meta.toml, but stars/{}.toml has {}", name, version, name, star.version
+            )),
+            Some(_) => {}
+            None if !files.contains(name) => problems.push(format!("{} is in meta.toml but has no stars/{}.toml", name, name)),
+            None => {}
+        }
+    }
+    for name in stars.keys().filter(|name| !indexed.contains_key(*name)) {
+        problems.push(format!("stars/{}.toml is not listed in meta.toml", name));
+    }
+
+    problems.extend(check_packages(root, &stars, &meta));
+    problems.extend(check_dependency_graph(&stars));
+    problems.extend(check_nova_lib(root, &meta));
+
+    if !problems.is_empty() {
+        for problem in &problems {
+            eprintln!("❌ {}", problem);
+        }
+        return Err(format!("❌ Error: {} problem(s) found in galaxy {}", problems.len(), meta.name).into());
+    }
+
+    println!("✅ Galaxy {} is consistent ({} stars)", meta.name, stars.len());
+    Ok(())
+}
+
+/// Every local `source` must exist, every tarball must belong to a star, and
+/// recorded checksums must match.
+fn check_packages(root: &Path, stars: &BTreeMap<String, Star>, meta: &GalaxyMeta) -> Vec<String> {
+    let mut problems = vec![];
+    let checksums = meta.checksums.clone().unwrap_or_default();
+    let mut referenced = BTreeSet::new();
+
+    for (name, star) in stars {
+        let Some(source) = &star.source else {
+            continue;
+        };
+        if source.contains("://") {
+            continue; // remote tarballs are not part of the directory
+        }
+        let tarball = root.join(source.trim_start_matches("./"));
+        referenced.insert(tarball.clone());
+        if !tarball.exists() {
+            problems.push(format!("{} has no tarball at {}", name, source));
+            continue;
+        }
+        match (checksums.get(name), &meta.checksums) {
+            (Some(expected), _) => match calculate_checksum(&tarball) {
+                Ok(actual) if &actual == expected => {}
+                Ok(_) => problems.push(format!("Checksum mismatch for {} ({})", name, source)),
+                Err(e) => problems.push(format!("Could not read {}: {}", source, e)),
+            },
+            (None, Some(_)) => println!("⚠️  {} has no checksum in meta.toml", name),
+            (None, None) => {}
+        }
+    }
+
+    for name in checksums.keys().filter(|name| !stars.contains_key(*name)) {
+        problems.push(format!("meta.toml has a checksum for unknown star {}", name));
+    }
+
+    // superseded versions kept by a multi-version galaxy still own their tarballs
+    for archived in walkdir::WalkDir::new(root.join("stars")).min_depth(2).into_iter().filter_map(|e| e.ok()) {
+        let star = fs::read_to_string(archived.path()).ok().and_then(|content| toml::from_str::<Star>(&content).ok());
+        if let Some(source) = star.and_then(|star| star.source) {
+            referenced.insert(root.join(source.trim_start_matches("./")));
+        }
+    }
+
+    let packages = root.join("packages");
+    if let Ok(entries) = fs::read_dir(&packages) {
+        let mut extra: Vec<String> = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file() && !referenced.contains(path))
+            .map(|path| path.file_name().unwrap_or_default().to_string_lossy().to_string())
+            .collect();
+        extra.sort();
+        for file in extra {
+            problems.push(format!("packages/{} does not belong to any star", file));
+        }
+    }
+    problems
+}
+
+/// Dependencies must point at stars in this galaxy that meet the constraint,
+/// and must not form cycles.
+fn check_dependency_graph(stars: &BTreeMap<String, Star>) -> Vec<String> {
+    let mut problems = vec![];
+    let mut graph: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+
+    for (name, star) in stars {
+        let mut dependencies: Vec<_> = star.dependencies.iter().flatten().collect();
+        dependencies.sort();
+        for (dependency, constraint) in dependencies {
+            match stars.get(dependency) {
+                None => problems.push(format!("{} depends on {}, which is not in this galaxy", name, dependency)),
+                Some(target) if constraint != "*" && !constraint.is_empty()
+                    && !satisfies_constraint(&target.version, constraint).unwrap_or(false) =>
+                {
+                    problems.push(format!(
+                        "{} depends on {} {}, but this galaxy has {}", name, dependency, constraint, target.version
+                    ));
+                }
+                Some(_) => graph.entry(name).or_default().push(dependency),
+            }
+        }
+    }
+
+    // depth-first search; a node seen again on the current path closes a cycle
+    fn visit<'a>(
+        node: &'a str,
+        graph: &BTreeMap<&'a str, Vec<&'a str>>,
+        path: &mut Vec<&'a str>,
+        done: &mut BTreeSet<&'a str>,
+        cycles: &mut BTreeSet<Vec<&'a str>>,
+    ) {
+        if let Some(start) = path.iter().position(|n| *n == node) {
+            let mut cycle = path[start..].to_vec();
+            // rotate so the same cycle is reported once, whichever star it was reached from
+            let min = cycle.iter().enumerate().min_by_key(|(_, n)| **n).map(|(i, _)| i).unwrap_or(0);
+            cycle.rotate_left(min);
+            cycles.insert(cycle);
+            return;
+        }
+        if done.contains(node) {
+            return;
+        }
+        path.push(node);
+        for next in graph.get(node).into_iter().flatten() {
+            visit(next, graph, path, done, cycles);
+        }
+        path.pop();
+        done.insert(node);
+    }
+
+    let mut cycles = BTreeSet::new();
+    let mut done = BTreeSet::new();
+    for node in graph.keys() {
+        visit(node, &graph, &mut vec![], &mut done, &mut cycles);
+    }
+    for cycle in cycles {
+        problems.push(format!("Dependency cycle: {} → {}", cycle.join(" → "), cycle[0]));
+    }
+    problems
+}
+
+/// Modules listed in `meta.toml` must exist under `nova-lib/` with matching checksums.
+fn check_nova_lib(root: &Path, meta: &GalaxyMeta) -> Vec<String> {
+    let mut problems = vec![];
+    let modules: BTreeMap<_, _> = meta.nova_lib.iter().flatten().collect();
+    for (name, module) in modules {
+        let path = root.join("nova-lib").join(nova::module_file(name));
+        if !path.exists() {
+            problems.push(format!("Nova module {} is in meta.toml but nova-lib has no {}", name, path.display()));
+            continue;
+        }
+        if let Some(expected) = &module.checksum {
+            if calculate_checksum(&path).map(|actual| &actual != expected).unwrap_or(true) {
+                problems.push(format!("Checksum mismatch for Nova module {}", name));
+            }
+        }
+    }
+    problems
+}
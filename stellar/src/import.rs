@@ -0,0 +1,828 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::Path;
+use flate2::read::MultiGzDecoder;
+use semver::Version;
+use tempfile::tempdir;
+use cosmos_core::star::Star;
+
+use crate::build_star::copy_tree;
+use crate::new_star::parse_author;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Deb,
+    Apk,
+    Pacman,
+}
+
+impl Format {
+    fn detect(path: &Path, head: &[u8]) -> Option<Format> {
+        let name = path.file_name()?.to_string_lossy();
+        if head.starts_with(b"!<arch>\n") {
+            Some(Format::Deb)
+        } else if name.ends_with(".apk") {
+            Some(Format::Apk)
+        } else if name.contains(".pkg.tar") {
+            Some(Format::Pacman)
+        } else {
+            None
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Format::Deb => "Debian",
+            Format::Apk => "Alpine",
+            Format::Pacman => "Arch",
+        }
+    }
+}
+
+/// What a foreign package says about itself, before it is mapped onto a star.
+#[derive(Debug, Default)]
+struct Package {
+    name: String,
+    version: String,
+    description: Option<String>,
+    license: Option<String>,
+    maintainer: Option<String>,
+    depends: Vec<String>, // `name`, or `name<op>version` with `<op>` one of < <= = >= > ~
+    scripts: Vec<(String, String)>, // every maintainer script, by its original name
+    pre_install: Option<String>,
+    post_install: Option<String>,
+}
+
+/// Convert a `.deb`, `.apk` or `.pkg.tar.*` into a star directory at `output`
+/// (default: the package name).
+pub fn import(file: &str, output: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let path = Path::new(file);
+    let data = fs::read(path).map_err(|e| format!("❌ Error: Cannot read {}: {}", file, e))?;
+    let format = Format::detect(path, &data)
+        .ok_or_else(|| format!("❌ Error: {} is not a .deb, .apk or .pkg.tar.* package", file))?;
+
+    let temp = tempdir()?;
+    let payload = temp.path().join("payload");
+    let package = match format {
+        Format::Deb => read_deb(&data, temp.path(), &payload)?,
+        Format::Apk => read_apk(path, &data, &payload)?,
+        Format::Pacman => read_pacman(path, &payload)?,
+    };
+    if package.name.is_empty() || package.version.is_empty() {
+        return Err(format!("❌ Error: {} does not name its package and version", file).into());
+    }
+    if !is_valid_star_name(&package.name) {
+        return Err(format!("❌ Error: {} names its package '{}', which is not a valid star name", file, package.name).into());
+    }
+    println!("📦 Read {} package {} {}", format.label(), package.name, package.version);
+
+    let dir = Path::new(output.unwrap_or(&package.name)).to_path_buf();
+    if dir.exists() {
+        return Err("❌ Error: Directory already exists".into());
+    }
+
+    let version = to_semver(&package.version);
+    if version != package.version {
+        println!("🔢 Version {} mapped to {}", package.version, version);
+    }
+
+    let mut dependencies = HashMap::new();
+    let mut skipped = 0;
+    for spec in &package.depends {
+        match map_dependency(format, spec) {
+            Some((name, constraint)) => {
+                dependencies.entry(name).or_insert(constraint);
+            }
+            None => skipped += 1,
+        }
+    }
+    if skipped > 0 {
+        println!("📝 Skipped {} conflict, library or command dependencies; stellar build-star detects library dependencies itself", skipped);
+    }
+
+    let mut authors = HashMap::new();
+    if let Some(maintainer) = &package.maintainer {
+        let (name, email) = parse_author(maintainer);
+        authors.insert(name, email);
+    }
+
+    let pre = package.pre_install.as_deref().map(translate).unwrap_or_default();
+    let post = package.post_install.as_deref().map(translate).unwrap_or_default();
+    let has_files = payload.exists() && fs::read_dir(&payload)?.next().is_some();
+    let needs_script = !pre.is_empty() || !post.is_empty();
+
+    let star = Star {
+        name: package.name.clone(),
+        version,
+        authors,
+        star_type: Some("normal".to_string()),
+        description: package.description.clone(),
+        license: package.license.clone(),
+        dependencies: (!dependencies.is_empty()).then_some(dependencies),
+        install_script: needs_script.then(|| "install.lua".to_string()),
+        source: None,
+        checksums: None,
+        users: None,
+        groups: None,
+        directories: None,
+        sources: None,
+        provides: None,
+        static_linked: None,
+        split: None,
+        postprocess: None,
+    };
+
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join("star.toml"), toml::to_string_pretty(&star)?)?;
+    if has_files {
+        copy_tree(&payload, &dir.join("files"))?;
+    }
+
+    if !package.scripts.is_empty() {
+        fs::create_dir_all(dir.join("import"))?;
+        for (name, content) in &package.scripts {
+            fs::write(dir.join("import").join(name), content)?;
+        }
+    }
+    if needs_script {
+        let source = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        fs::write(dir.join("install.lua"), install_lua(&source, &pre, &post, &dir.join("files"))?)?;
+        let todo = pre.iter().chain(&post).filter(|line| line.starts_with("-- TODO")).count();
+        println!("📝 Translated install scripts into install.lua ({} line(s) left as TODO)", todo);
+    }
+    for (name, _) in &package.scripts {
+        if !is_install_script(format, name) {
+            println!("⚠️  {} has no Cosmos equivalent; kept as import/{}", name, name);
+        }
+    }
+
+    println!("✅ Imported {} into {}", package.name, dir.display());
+    println!("   Next: stellar validate {0} && stellar build-star {0}", dir.display());
+    Ok(())
+}
+
+fn is_install_script(format: Format, name: &str) -> bool {
+    match format {
+        Format::Deb => name == "preinst" || name == "postinst",
+        Format::Apk => name == "pre-install" || name == "post-install",
+        Format::Pacman => name == "install",
+    }
+}
+
+type ArMember<'a> = (String, &'a [u8]);
+
+/// Members of an `ar` archive, as used by `.deb`.
+fn ar_members(data: &[u8]) -> Result<Vec<ArMember<'_>>, Box<dyn std::error::Error>> {
+    let mut members = vec![];
+    let mut at = 8;
+    while at + 60 <= data.len() {
+        let header = &data[at..at + 60];
+        let name = String::from_utf8_lossy(&header[..16]).trim().trim_end_matches('/').to_string();
+        let size: usize = String::from_utf8_lossy(&header[48..58])
+            .trim()
+            .parse()
+            .map_err(|_| "❌ Error: Corrupt .deb archive header")?;
+        let start = at + 60;
+        let end = start.checked_add(size).filter(|end| *end <= data.len()).ok_or("❌ Error: Truncated .deb archive")?;
+        members.push((name, &data[start..end]));
+        at = end + size % 2;
+    }
+    Ok(members)
+}
+
+/// Unpack the compressed tarball `bytes` into `dest` through a temporary file.
+fn extract_bytes(bytes: &[u8], scratch: &Path, dest: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    fs::write(scratch, bytes)?;
+    nova::archive::extract(scratch, dest)?;
+    fs::remove_file(scratch)?;
+    Ok(())
+}
+
+/// Fields of a Debian `control` file, keyed in lower case; continuation lines are joined with newlines.
+fn parse_control(text: &str) -> HashMap<String, String> {
+    let mut fields: HashMap<String, String> = HashMap::new();
+    let mut last = None;
+    for line in text.lines() {
+        if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some(value) = last.as_ref().and_then(|key| fields.get_mut(key)) {
+                value.push('\n');
+                value.push_str(line.trim());
+            }
+        } else if let Some((key, value)) = line.split_once(':') {
+            let key = key.trim().to_lowercase();
+            fields.insert(key.clone(), value.trim().to_string());
+            last = Some(key);
+        }
+    }
+    fields
+}
+
+fn read_deb(data: &[u8], temp: &Path, payload: &Path) -> Result<Package, Box<dyn std::error::Error>> {
+    let control_dir = temp.join("control");
+    for (name, bytes) in ar_members(data)? {
+        if name.starts_with("control.tar") {
+            extract_bytes(bytes, &temp.join(&name), &control_dir)?;
+        } else if name.starts_with("data.tar") {
+            extract_bytes(bytes, &temp.join(&name), payload)?;
+        }
+    }
+
+    let control = fs::read_to_string(control_dir.join("control"))
+        .map_err(|_| "❌ Error: The .deb has no control file")?;
+    let fields = parse_control(&control);
+    let field = |key: &str| fields.get(key).cloned().filter(|value| !value.is_empty());
+
+    let mut package = Package {
+        name: field("package").unwrap_or_default(),
+        version: field("version").unwrap_or_default(),
+        description: field("description").map(|d| d.lines().next().unwrap_or_default().to_string()),
+        maintainer: field("maintainer"),
+        ..Default::default()
+    };
+
+    for key in ["pre-depends", "depends"] {
+        for entry in field(key).unwrap_or_default().split(',') {
+            // of `a | b` alternatives the first is taken; `:any` qualifiers and `[arch]` lists are dropped
+            let first = entry.split('|').next().unwrap_or_default();
+            let first = first.split('[').next().unwrap_or_default().trim();
+            let (name, constraint) = match first.split_once('(') {
+                Some((name, constraint)) => (name.trim(), constraint.trim_end_matches(')').replace(' ', "")),
+                None => (first, String::new()),
+            };
+            let name = name.split(':').next().unwrap_or_default();
+            if !name.is_empty() {
+                package.depends.push(format!("{}{}", name, constraint.replace("<<", "<").replace(">>", ">")));
+            }
+        }
+    }
+
+    // machine-readable copyright files name the license
+    let copyright = payload.join("usr/share/doc").join(&package.name).join("copyright");
+    if let Ok(text) = fs::read_to_string(copyright) {
+        package.license = text
+            .lines()
+            .find_map(|line| line.strip_prefix("License:"))
+            .map(|license| license.trim().to_string())
+            .filter(|license| !license.is_empty());
+    }
+
+    for name in ["preinst", "postinst", "prerm", "postrm", "config", "triggers"] {
+        if let Ok(content) = fs::read_to_string(control_dir.join(name)) {
+            package.scripts.push((name.to_string(), content));
+        }
+    }
+    package.pre_install = script(&package, "preinst");
+    package.post_install = script(&package, "postinst");
+    Ok(package)
+}
+
+fn script(package: &Package, name: &str) -> Option<String> {
+    package.scripts.iter().find(|(n, _)| n == name).map(|(_, content)| content.clone())
+}
+
+/// Take the dot files at the top of an unpacked `.apk` or `.pkg.tar` out of
+/// the payload, by name.
+fn take_dot_files(payload: &Path) -> Result<HashMap<String, String>, Box<dyn std::error::Error>> {
+    let mut files = HashMap::new();
+    if !payload.exists() {
+        return Ok(files);
+    }
+    for entry in fs::read_dir(payload)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') && entry.file_type()?.is_file() {
+            files.insert(name, String::from_utf8_lossy(&fs::read(entry.path())?).to_string());
+            fs::remove_file(entry.path())?;
+        }
+    }
+    Ok(files)
+}
+
+/// `key = value` lines of a `.PKGINFO`, in order; keys such as `depend` repeat.
+fn parse_pkginfo(text: &str) -> Vec<(String, String)> {
+    text.lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .filter_map(|line| line.split_once(" = "))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+fn package_from_pkginfo(info: &[(String, String)], maintainer_key: &str) -> Package {
+    let first = |key: &str| info.iter().find(|(k, _)| k == key).map(|(_, v)| v.clone()).filter(|v| !v.is_empty());
+    let licenses: Vec<&str> = info.iter().filter(|(k, _)| k == "license").map(|(_, v)| v.as_str()).collect();
+    Package {
+        name: first("pkgname").unwrap_or_default(),
+        version: first("pkgver").unwrap_or_default(),
+        description: first("pkgdesc"),
+        license: (!licenses.is_empty()).then(|| licenses.join(" AND ")),
+        maintainer: first(maintainer_key),
+        depends: info.iter().filter(|(k, _)| k == "depend").map(|(_, v)| v.clone()).collect(),
+        ..Default::default()
+    }
+}
+
+fn read_apk(path: &Path, data: &[u8], payload: &Path) -> Result<Package, Box<dyn std::error::Error>> {
+    if !data.starts_with(&[0x1f, 0x8b]) {
+        return Err("❌ Error: Only gzip-based (v2) .apk packages are supported".into());
+    }
+
+    // signature, control and data are concatenated gzip streams of one tar
+    let mut archive = tar::Archive::new(MultiGzDecoder::new(BufReader::new(File::open(path)?)));
+    archive.set_ignore_zeros(true);
+    fs::create_dir_all(payload)?;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.unpack_in(payload)? {
+            println!("⚠️  Skipped unsafe archive entry: {}", entry.path()?.display());
+        }
+    }
+
+    let mut meta = take_dot_files(payload)?;
+    let info = meta.remove(".PKGINFO").ok_or("❌ Error: The .apk has no .PKGINFO")?;
+    let mut package = package_from_pkginfo(&parse_pkginfo(&info), "maintainer");
+
+    let mut scripts: Vec<(String, String)> = meta
+        .into_iter()
+        .filter(|(name, _)| !name.starts_with(".SIGN"))
+        .map(|(name, content)| (name.trim_start_matches('.').to_string(), content))
+        .collect();
+    scripts.sort();
+    package.scripts = scripts;
+    package.pre_install = script(&package, "pre-install");
+    package.post_install = script(&package, "post-install");
+    Ok(package)
+}
+
+fn read_pacman(path: &Path, payload: &Path) -> Result<Package, Box<dyn std::error::Error>> {
+    nova::archive::extract(path, payload)?;
+
+    let mut meta = take_dot_files(payload)?;
+    let info = meta.remove(".PKGINFO").ok_or("❌ Error: The package has no .PKGINFO")?;
+    let mut package = package_from_pkginfo(&parse_pkginfo(&info), "packager");
+
+    if let Some(install) = meta.remove(".INSTALL") {
+        package.pre_install = shell_function(&install, "pre_install");
+        package.post_install = shell_function(&install, "post_install");
+        for name in ["pre_upgrade", "post_upgrade", "pre_remove", "post_remove"] {
+            if shell_function(&install, name).is_some() {
+                println!("⚠️  {}() has no Cosmos equivalent; kept in import/install", name);
+            }
+        }
+        package.scripts.push(("install".to_string(), install));
+    }
+    Ok(package)
+}
+
+/// Package names become star names and, by default, directory names, so they
+/// are limited to what Debian, Alpine and Arch allow: no separators, no `..`,
+/// nothing hidden.
+fn is_valid_star_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(['.', '-'])
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '+' | '@'))
+}
+
+/// The body of the shell function `name` in an Arch `.INSTALL` file.
+fn shell_function(script: &str, name: &str) -> Option<String> {
+    let mut lines = script.lines();
+    while let Some(line) = lines.next() {
+        let Some(rest) = line.trim().strip_prefix(name).and_then(|rest| rest.trim_start().strip_prefix("()")) else {
+            continue;
+        };
+        // `name() { a; b; }` on one line
+        if let Some(body) = rest.trim().strip_prefix('{').and_then(|body| body.trim().strip_suffix('}')) {
+            return Some(body.split(';').map(str::trim).collect::<Vec<_>>().join("\n"));
+        }
+        let mut body = vec![];
+        for line in lines.by_ref() {
+            match line.trim() {
+                "{" => continue,
+                "}" => break,
+                _ => body.push(line),
+            }
+        }
+        return Some(body.join("\n"));
+    }
+    None
+}
+
+/// Map a distribution version onto semver: the epoch and package release are
+/// dropped, missing parts are zero, and whatever else remains becomes build metadata.
+fn to_semver(version: &str) -> String {
+    let upstream = version.split_once(':').map_or(version, |(_, v)| v);
+    let upstream = upstream.rsplit_once('-').map_or(upstream, |(v, _)| v);
+    if Version::parse(upstream).is_ok() {
+        return upstream.to_string();
+    }
+
+    let end = upstream.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(upstream.len());
+    let mut numbers: Vec<u64> = upstream[..end].split('.').filter(|p| !p.is_empty()).map(|p| p.parse().unwrap_or(0)).collect();
+    let mut build: Vec<String> = numbers.iter().skip(3).map(u64::to_string).collect();
+    numbers.resize(3, 0);
+    build.extend(
+        upstream[end..]
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .filter(|part| !part.is_empty())
+            .map(str::to_string),
+    );
+
+    let core = format!("{}.{}.{}", numbers[0], numbers[1], numbers[2]);
+    if build.is_empty() { core } else { format!("{}+{}", core, build.join(".")) }
+}
+
+/// Turn a dependency of the package into a star dependency. Virtual and
+/// library dependencies (`so:`, `cmd:`, `libfoo.so`) give `None`.
+fn map_dependency(format: Format, spec: &str) -> Option<(String, String)> {
+    let spec = spec.trim();
+    let at = spec.find(['<', '>', '=', '~']).unwrap_or(spec.len());
+    let (name, rest) = spec.split_at(at);
+    if name.is_empty() || name.starts_with('!') {
+        return None;
+    }
+    match format {
+        Format::Apk if name.contains(':') => return None,
+        Format::Pacman if name.contains(".so") => return None,
+        _ => {}
+    }
+
+    let op_len = rest.find(|c: char| !matches!(c, '<' | '>' | '=' | '~')).unwrap_or(rest.len());
+    let (op, version) = rest.split_at(op_len);
+    // star constraints are minimum versions, so upper bounds cannot be kept
+    let constraint = match op {
+        ">=" | ">" | "=" | "==" | "~" | "~=" if !version.is_empty() => to_semver(version),
+        _ => "*".to_string(),
+    };
+    Some((name.to_string(), constraint))
+}
+
+/// Split a shell line into words, or `None` if it uses anything beyond plain
+/// words and quotes (variables, pipes, redirects, globs, subshells).
+fn words(line: &str) -> Option<Vec<String>> {
+    if line.contains(['$', '`', ';', '|', '&', '<', '>', '(', ')', '{', '}', '*', '?', '\\']) {
+        return None;
+    }
+    let mut words = vec![];
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    for c in line.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => word.push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                in_word = true;
+            }
+            None if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            None => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if quote.is_some() {
+        return None;
+    }
+    if in_word {
+        words.push(word);
+    }
+    Some(words)
+}
+
+/// Split `args` into flag values and positionals. `valued` flags take the next
+/// word (or `--flag=value`); `plain` ones take none. Short flags may be
+/// bundled (`-aG group`). Any other flag gives `None`.
+fn options<'a>(args: &'a [String], valued: &[&str], plain: &[&str]) -> Option<(HashMap<String, &'a str>, Vec<&'a str>)> {
+    let mut values = HashMap::new();
+    let mut positionals = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if let Some((flag, value)) = arg.split_once('=').filter(|_| arg.starts_with("--")) {
+            if !valued.contains(&flag) {
+                return None;
+            }
+            values.insert(flag.to_string(), value);
+        } else if arg.starts_with("--") {
+            if valued.contains(&arg.as_str()) {
+                values.insert(arg.clone(), args.next()?.as_str());
+            } else if !plain.contains(&arg.as_str()) {
+                return None;
+            }
+        } else if arg.starts_with('-') && arg.len() > 1 {
+            let flags: Vec<String> = arg[1..].chars().map(|c| format!("-{}", c)).collect();
+            for (i, flag) in flags.iter().enumerate() {
+                if valued.contains(&flag.as_str()) && i == flags.len() - 1 {
+                    values.insert(flag.clone(), args.next()?.as_str());
+                } else if !plain.contains(&flag.as_str()) {
+                    return None;
+                }
+            }
+        } else {
+            positionals.push(arg.as_str());
+        }
+    }
+    Some((values, positionals))
+}
+
+pub fn lua(s: &str) -> String {
+    format!("{:?}", s)
+}
+
+/// The first value given for any of `flags`.
+fn value<'a>(values: &HashMap<String, &'a str>, flags: &[&str]) -> Option<&'a str> {
+    flags.iter().find_map(|flag| values.get(*flag).copied())
+}
+
+fn group_command(command: &str, args: &[String]) -> Option<Vec<String>> {
+    let (values, names) = options(args, &["--gid", "-g"], &["--system", "-S", "-r", "--quiet", "-q", "-f", "--force"])?;
+    let gid = value(&values, &["--gid", "-g"]).map(str::parse::<u32>).transpose().ok()?;
+    match (names.as_slice(), gid) {
+        ([name], Some(gid)) => Some(vec![format!("add_group({}, {})", lua(name), gid)]),
+        ([name], None) => Some(vec![format!("add_group({})", lua(name))]),
+        ([user, group], None) if command == "addgroup" => Some(vec![format!("add_user_to_group({}, {})", lua(user), lua(group))]),
+        _ => None,
+    }
+}
+
+/// The flags a user-creating command takes for each `add_user` option.
+struct UserFlags {
+    uid: &'static [&'static str],
+    group: &'static [&'static str],
+    home: &'static [&'static str],
+    shell: &'static [&'static str],
+    gecos: &'static [&'static str],
+    groups: &'static [&'static str], // supplementary groups
+}
+
+const USERADD: UserFlags = UserFlags {
+    uid: &["--uid", "-u"],
+    group: &["--gid", "-g"],
+    home: &["--home-dir", "-d"],
+    shell: &["--shell", "-s"],
+    gecos: &["--comment", "-c"],
+    groups: &["--groups", "-G"],
+};
+
+const ADDUSER: UserFlags = UserFlags {
+    uid: &["--uid", "-u"],
+    group: &["--ingroup", "-G"],
+    home: &["--home", "-h"],
+    shell: &["--shell", "-s"],
+    gecos: &["--gecos", "-g"],
+    groups: &[],
+};
+
+fn user_command(command: &str, args: &[String]) -> Option<Vec<String>> {
+    // the same letters mean different things to useradd and (busybox) adduser
+    let flags = if command == "useradd" { &USERADD } else { &ADDUSER };
+    let (uid, group, home, shell, gecos, groups) = (flags.uid, flags.group, flags.home, flags.shell, flags.gecos, flags.groups);
+    let valued: Vec<&str> = [uid, group, home, shell, gecos, groups].concat();
+    let plain = [
+        "--system", "-S", "-r", "--disabled-password", "-D", "--disabled-login", "--no-create-home", "-H", "-M",
+        "--create-home", "-m", "--group", "-U", "--user-group", "-N", "--no-user-group", "--quiet", "-q",
+    ];
+    let (values, names) = options(args, &valued, &plain)?;
+
+    if let [user, group] = names.as_slice() {
+        return (command == "adduser" && values.is_empty())
+            .then(|| vec![format!("add_user_to_group({}, {})", lua(user), lua(group))]);
+    }
+    let [name] = names.as_slice() else {
+        return None;
+    };
+
+    let mut opts = vec![];
+    if let Some(uid) = value(&values, uid) {
+        opts.push(format!("uid = {}", uid.parse::<u32>().ok()?));
+    }
+    for (key, flags) in [("group", group), ("home", home), ("shell", shell), ("gecos", gecos)] {
+        if let Some(v) = value(&values, flags) {
+            opts.push(format!("{} = {}", key, lua(v)));
+        }
+    }
+    let mut calls = vec![if opts.is_empty() {
+        format!("add_user({})", lua(name))
+    } else {
+        format!("add_user({}, {{ {} }})", lua(name), opts.join(", "))
+    }];
+    for group in value(&values, groups).unwrap_or_default().split(',').filter(|g| !g.is_empty()) {
+        calls.push(format!("add_user_to_group({}, {})", lua(name), lua(group)));
+    }
+    Some(calls)
+}
+
+/// Translate one shell command into Nova calls, if it is one stellar knows.
+fn translate_command(words: &[String]) -> Option<Vec<String>> {
+    let (command, args) = words.split_first()?;
+    let absolute = |paths: &[&str]| !paths.is_empty() && paths.iter().all(|p| p.starts_with('/'));
+
+    match command.as_str() {
+        "addgroup" | "groupadd" => group_command(command, args),
+        "adduser" | "useradd" => user_command(command, args),
+        "usermod" => {
+            let (values, names) = options(args, &["-G", "--groups"], &["-a", "--append"])?;
+            let [user] = names.as_slice() else { return None };
+            let groups = value(&values, &["-G", "--groups"])?;
+            Some(groups.split(',').map(|group| format!("add_user_to_group({}, {})", lua(user), lua(group))).collect())
+        }
+        "mkdir" => {
+            let (values, paths) = options(args, &["-m", "--mode"], &["-p", "--parents"])?;
+            if !absolute(&paths) {
+                return None;
+            }
+            let mode = value(&values, &["-m", "--mode"]);
+            if mode.is_some_and(|m| !m.chars().all(|c| c.is_digit(8))) {
+                return None;
+            }
+            let mut calls = vec![];
+            for path in paths {
+                calls.push(format!("mkdir({})", lua(path)));
+                if let Some(mode) = mode {
+                    calls.push(format!("chmod({}, tonumber(\"{}\", 8))", lua(path), mode));
+                }
+            }
+            Some(calls)
+        }
+        "chmod" => {
+            let (mode, paths) = args.split_first()?;
+            let paths: Vec<&str> = paths.iter().map(String::as_str).collect();
+            if !mode.chars().all(|c| c.is_digit(8)) || !(3..=4).contains(&mode.len()) || !absolute(&paths) {
+                return None;
+            }
+            Some(paths.iter().map(|path| format!("chmod({}, tonumber(\"{}\", 8))", lua(path), mode)).collect())
+        }
+        "chown" => {
+            let (owner, paths) = args.split_first()?;
+            let paths: Vec<&str> = paths.iter().map(String::as_str).collect();
+            if owner.starts_with('-') || !absolute(&paths) {
+                return None;
+            }
+            let (user, group) = owner.split_once(':').unwrap_or((owner, ""));
+            let arg = |s: &str| if s.is_empty() { "nil".to_string() } else { lua(s) };
+            Some(paths.iter().map(|path| format!("chown({}, {}, {})", lua(path), arg(user), arg(group))).collect())
+        }
+        "ln" => {
+            let symbolic = args.iter().any(|a| a == "--symbolic" || (a.starts_with('-') && !a.starts_with("--") && a.contains('s')));
+            let (_, paths) = options(args, &[], &["-s", "--symbolic", "-f", "--force", "-n", "--no-dereference"])?;
+            match paths.as_slice() {
+                [target, link] if symbolic && link.starts_with('/') && !link.ends_with('/') => {
+                    Some(vec![format!("symlink({}, {})", lua(target), lua(link))])
+                }
+                _ => None,
+            }
+        }
+        "update-rc.d" => match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+            [name, "defaults" | "enable", ..] => Some(vec![format!("service_enable({})", lua(name))]),
+            _ => None,
+        },
+        "rc-update" => match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+            ["add", name] | ["add", name, _] => Some(vec![format!("service_enable({})", lua(name))]),
+            _ => None,
+        },
+        "systemctl" => match args.split_first() {
+            Some((action, names)) if action == "enable" && !names.is_empty() && names.iter().all(|n| !n.starts_with('-')) => Some(
+                names.iter().map(|name| format!("service_enable({})", lua(name.trim_end_matches(".service")))).collect(),
+            ),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Nova lines for a maintainer script: commands stellar recognises become
+/// builtin calls, anything else is kept as a TODO comment. Scripts that only
+/// set shell options or exit give nothing.
+fn translate(script: &str) -> Vec<String> {
+    let mut lines = vec![];
+    for line in script.lines().map(str::trim) {
+        let boilerplate = line.is_empty() || line.starts_with('#') || line.starts_with("set -") || line == "exit 0" || line == ":";
+        if boilerplate {
+            continue;
+        }
+        // silenced or forgiven failures read the same in Nova, where builtins stop the install on error
+        let mut command = line;
+        while let Some(rest) = [" 2>/dev/null", " >/dev/null 2>&1", " >/dev/null", " || true", " || :"]
+            .iter()
+            .find_map(|suffix| command.strip_suffix(suffix))
+        {
+            command = rest.trim_end();
+        }
+        match words(command).and_then(|words| translate_command(&words)) {
+            Some(calls) => lines.extend(calls),
+            None => lines.push(format!("-- TODO: {}", line)),
+        }
+    }
+    lines
+}
+
+/// An `install()` that runs the translated pre-install lines, installs every
+/// imported file, then runs the post-install lines.
+fn install_lua(source: &str, pre: &[String], post: &[String], files_dir: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    let mut out = format!(
+        "-- Imported by `stellar import` from {}. Maintainer script lines stellar\n\
+         -- could not translate are kept as TODO comments; the originals are in import/.\n\n\
+         function install()\n",
+        source
+    );
+    let mut section = |title: &str, lines: &[String]| {
+        if !lines.is_empty() {
+            out.push_str(&format!("  -- {}\n", title));
+            for line in lines {
+                out.push_str(&format!("  {}\n", line));
+            }
+            out.push('\n');
+        }
+    };
+    section("before the files are installed", pre);
+
+    let mut files = vec![];
+    if files_dir.exists() {
+        for entry in walkdir::WalkDir::new(files_dir).min_depth(1).sort_by_file_name() {
+            let entry = entry?;
+            let relative = entry.path().strip_prefix(files_dir)?.to_string_lossy().to_string();
+            if entry.path_is_symlink() {
+                let target = fs::read_link(entry.path())?.to_string_lossy().to_string();
+                files.push(format!("symlink({}, {})", lua(&target), lua(&format!("/{}", relative))));
+            } else if entry.file_type().is_dir() {
+                if fs::read_dir(entry.path())?.next().is_none() {
+                    files.push(format!("mkdir({})", lua(&format!("/{}", relative))));
+                }
+            } else {
+                files.push(format!("copy({}, {})", lua(&relative), lua(&format!("/{}", relative))));
+            }
+        }
+    }
+    section("files", &files);
+    section("after the files are installed", post);
+
+    let trimmed = out.trim_end().to_string();
+    Ok(format!("{}\nend\n", trimmed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn star_names() {
+        for name in ["zlib", "libstdc++6", "python3.12", "lib32-glibc", "qt6-base_dev", "foo@bar"] {
+            assert!(is_valid_star_name(name), "{} rejected", name);
+        }
+        for name in ["", "..", ".hidden", "-rf", "../etc", "/usr/bin/foo", "a/b", "a b", "a\\b"] {
+            assert!(!is_valid_star_name(name), "{:?} accepted", name);
+        }
+    }
+
+    #[test]
+    fn versions_map_onto_semver() {
+        assert_eq!(to_semver("1.2.3"), "1.2.3");
+        assert_eq!(to_semver("1:1.2.3-4"), "1.2.3");
+        assert_eq!(to_semver("1.2.3-r2"), "1.2.3");
+        assert_eq!(to_semver("2.38"), "2.38.0");
+        assert_eq!(to_semver("7"), "7.0.0");
+        assert_eq!(to_semver("1.2.3.4-1"), "1.2.3+4");
+        assert_eq!(to_semver("1.1.1w-1"), "1.1.1+w");
+        assert_eq!(to_semver("9.4p1-2"), "9.4.0+p1");
+    }
+
+    #[test]
+    fn dependencies_keep_lower_bounds_only() {
+        assert_eq!(map_dependency(Format::Deb, "libc6>=2.36"), Some(("libc6".to_string(), "2.36.0".to_string())));
+        assert_eq!(map_dependency(Format::Deb, "dpkg<1.22"), Some(("dpkg".to_string(), "*".to_string())));
+        assert_eq!(map_dependency(Format::Deb, "zlib1g>=1:1.2.13"), Some(("zlib1g".to_string(), "1.2.13".to_string())));
+        assert_eq!(map_dependency(Format::Pacman, "glibc>=2.38"), Some(("glibc".to_string(), "2.38.0".to_string())));
+        assert_eq!(map_dependency(Format::Pacman, "bash"), Some(("bash".to_string(), "*".to_string())));
+        assert_eq!(map_dependency(Format::Pacman, "openssl<4"), Some(("openssl".to_string(), "*".to_string())));
+        assert_eq!(map_dependency(Format::Apk, "musl=1.2.4-r2"), Some(("musl".to_string(), "1.2.4".to_string())));
+        assert_eq!(map_dependency(Format::Apk, "so:libc.musl-x86_64.so.1"), None);
+        assert_eq!(map_dependency(Format::Apk, "!foo"), None);
+        assert_eq!(map_dependency(Format::Pacman, "libcrypto.so=3-64"), None);
+    }
+
+    #[test]
+    fn words_split_plain_lines_only() {
+        assert_eq!(words("adduser --system foo"), Some(vec!["adduser".into(), "--system".into(), "foo".into()]));
+        assert_eq!(words("echo 'a b' \"c d\" e''"), Some(vec!["echo".into(), "a b".into(), "c d".into(), "e".into()]));
+        assert_eq!(words("  "), Some(vec![]));
+        assert_eq!(words("rm -rf $DIR"), None);
+        assert_eq!(words("a | b"), None);
+        assert_eq!(words("echo 'open"), None);
+    }
+
+    #[test]
+    fn shell_functions_are_found_in_both_styles() {
+        let script = "pre_install() {\n  useradd foo\n  mkdir /var/foo\n}\n\npost_upgrade() { a; b }\n";
+        assert_eq!(shell_function(script, "pre_install").as_deref(), Some("  useradd foo\n  mkdir /var/foo"));
+        assert_eq!(shell_function(script, "post_upgrade").as_deref(), Some("a\nb"));
+        assert_eq!(shell_function(script, "post_install"), None);
+
+        let brace_below = "post_install()\n{\n  ldconfig\n}\n";
+        assert_eq!(shell_function(brace_below, "post_install").as_deref(), Some("  ldconfig"));
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use cosmos_core::galaxy::Galaxy;
+use cosmos_core::star::Star;
+
+use crate::elf;
+use crate::postprocess;
+
+/// Whether a file name looks like a shared library (`libfoo.so`, `libfoo.so.1.2`).
+fn is_shared_object_name(name: &str) -> bool {
+    name.ends_with(".so") || name.contains(".so.")
+}
+
+/// What the ELF files of one staged `files/` tree ship and need.
+#[derive(Debug, Default)]
+pub struct Scan {
+    provides: BTreeSet<String>,
+    needed: BTreeMap<String, Vec<String>>, // library → files needing it
+    dynamic: Vec<String>,
+}
+
+impl Scan {
+    /// The libraries shipped, in the form stored in `star.toml`.
+    pub fn provides(&self) -> Option<Vec<String>> {
+        if self.provides.is_empty() {
+            None
+        } else {
+            Some(self.provides.iter().cloned().collect())
+        }
+    }
+}
+
+/// Read the ELF files staged in `files_dir`.
+pub fn scan(files_dir: &Path) -> Result<Scan, Box<dyn std::error::Error>> {
+    let mut scan = Scan::default();
+    if !files_dir.exists() {
+        return Ok(scan);
+    }
+
+    for entry in walkdir::WalkDir::new(files_dir).sort_by_file_name() {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let relative = entry.path().strip_prefix(files_dir)?;
+        let installed = format!("/{}", relative.display());
+        if relative.starts_with(postprocess::DEBUG_DIR) {
+            continue;
+        }
+
+        if entry.path_is_symlink() {
+            if is_shared_object_name(&name) {
+                scan.provides.insert(name);
+            }
+            continue;
+        }
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        let Some(info) = elf::read(entry.path())? else {
+            continue;
+        };
+        if let Some(soname) = &info.soname {
+            scan.provides.insert(soname.clone());
+        }
+        if is_shared_object_name(&name) {
+            scan.provides.insert(name);
+        }
+        if let Some(interpreter) = &info.interpreter {
+            let base = interpreter.rsplit('/').next().unwrap_or(interpreter);
+            scan.needed.entry(base.to_string()).or_default().push(installed.clone());
+        }
+        for library in &info.needed {
+            scan.needed.entry(library.clone()).or_default().push(installed.clone());
+        }
+        if info.is_dynamic() {
+            scan.dynamic.push(installed);
+        }
+    }
+    Ok(scan)
+}
+
+/// Record what `star` ships in `provides`, and add a dependency on whichever
+/// star provides each library it needs: first the other outputs of the same
+/// build (`siblings`), then `galaxies`. Libraries nothing provides are reported.
+pub fn check(star: &mut Star, scan: &Scan, siblings: &[Star], galaxies: &[Galaxy]) {
+    if star.static_linked == Some(true) {
+        for path in &scan.dynamic {
+            println!("⚠️  {} is dynamically linked, but the star is marked static", path);
+        }
+    }
+
+    let dependencies = star.dependencies.get_or_insert_with(Default::default);
+    for (library, users) in &scan.needed {
+        if scan.provides.contains(library) {
+            continue;
+        }
+
+        let provides = |other: &&Star| other.name != star.name && other.provides.iter().flatten().any(|p| p == library);
+        let mut providers: Vec<&Star> = siblings.iter().filter(provides).collect();
+        if providers.is_empty() {
+            providers = galaxies.iter().flat_map(|galaxy| galaxy.stars.values()).filter(provides).collect();
+            providers.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+
+        if providers.iter().any(|p| dependencies.contains_key(&p.name)) {
+            continue;
+        }
+        match providers.first() {
+            Some(provider) => {
+                println!("🔗 Adding dependency {} (provides {} for {})", provider.name, library, users.join(", "));
+                dependencies.insert(provider.name.clone(), "*".to_string());
+            }
+            None => println!("⚠️  No star provides {} (needed by {})", library, users.join(", ")),
+        }
+    }
+    if dependencies.is_empty() {
+        star.dependencies = None;
+    }
+
+    star.provides = scan.provides();
+}
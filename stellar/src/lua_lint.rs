@@ -0,0 +1,228 @@
+use std::collections::{BTreeMap, HashSet};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Kind {
+    Name(String),
+    Str(String),
+    Punct(String),
+    Number,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: Kind,
+    line: usize,
+}
+
+/// What a static read of a Nova script found.
+#[derive(Debug, Default)]
+pub struct ScriptReport {
+    /// Global functions called but never defined, with the first line each is called on.
+    pub undefined: BTreeMap<String, usize>,
+    /// Literal sources passed to `copy` and `template`, with their line.
+    pub file_sources: Vec<(String, usize)>,
+}
+
+const KEYWORDS: [&str; 22] = [
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "goto", "if", "in",
+    "local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+];
+
+/// Count the `=` signs of a long bracket opening at `at` (`[[`, `[==[`), if there is one.
+fn long_bracket(chars: &[char], at: usize) -> Option<usize> {
+    if chars.get(at) != Some(&'[') {
+        return None;
+    }
+    let level = chars[at + 1..].iter().take_while(|&&c| c == '=').count();
+    (chars.get(at + 1 + level) == Some(&'[')).then_some(level)
+}
+
+/// Skip past the long bracket closing `]==]` of `level`, returning the new position
+/// and the text in between.
+fn skip_long(chars: &[char], mut i: usize, level: usize, line: &mut usize) -> (usize, String) {
+    let mut text = String::new();
+    while i < chars.len() {
+        if chars[i] == ']'
+            && chars[i + 1..].iter().take(level).all(|&c| c == '=')
+            && chars.get(i + 1 + level) == Some(&']')
+        {
+            return (i + level + 2, text);
+        }
+        if chars[i] == '\n' {
+            *line += 1;
+        }
+        text.push(chars[i]);
+        i += 1;
+    }
+    (i, text)
+}
+
+fn tokenize(code: &str) -> Vec<Token> {
+    let chars: Vec<char> = code.chars().collect();
+    let mut tokens = vec![];
+    let mut line = 1;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start_line = line;
+        match c {
+            '\n' => {
+                line += 1;
+                i += 1;
+            }
+            c if c.is_whitespace() => i += 1,
+            '-' if chars.get(i + 1) == Some(&'-') => {
+                if let Some(level) = long_bracket(&chars, i + 2) {
+                    i = skip_long(&chars, i + 4 + level, level, &mut line).0;
+                } else {
+                    while i < chars.len() && chars[i] != '\n' {
+                        i += 1;
+                    }
+                }
+            }
+            '[' if long_bracket(&chars, i).is_some() => {
+                let level = long_bracket(&chars, i).unwrap();
+                let (next, text) = skip_long(&chars, i + 2 + level, level, &mut line);
+                tokens.push(Token { kind: Kind::Str(text), line: start_line });
+                i = next;
+            }
+            '"' | '\'' => {
+                let mut text = String::new();
+                i += 1;
+                while i < chars.len() && chars[i] != c && chars[i] != '\n' {
+                    if chars[i] == '\\' && i + 1 < chars.len() {
+                        i += 1;
+                    }
+                    text.push(chars[i]);
+                    i += 1;
+                }
+                i += 1;
+                tokens.push(Token { kind: Kind::Str(text), line: start_line });
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut name = String::new();
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    name.push(chars[i]);
+                    i += 1;
+                }
+                tokens.push(Token { kind: Kind::Name(name), line: start_line });
+            }
+            c if c.is_ascii_digit() => {
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '.') {
+                    // exponents may carry a sign: 1e-5, 0x1p+4
+                    if matches!(chars[i], 'e' | 'E' | 'p' | 'P') && matches!(chars.get(i + 1), Some('+' | '-')) {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                tokens.push(Token { kind: Kind::Number, line: start_line });
+            }
+            _ => {
+                let rest: String = chars[i..chars.len().min(i + 3)].iter().collect();
+                let punct = ["...", "..", "==", "~=", "<=", ">=", "::", "//", "<<", ">>"]
+                    .iter()
+                    .find(|p| rest.starts_with(*p))
+                    .map(|p| p.to_string())
+                    .unwrap_or_else(|| c.to_string());
+                i += punct.chars().count();
+                tokens.push(Token { kind: Kind::Punct(punct), line: start_line });
+            }
+        }
+    }
+    tokens
+}
+
+fn name_of(token: Option<&Token>) -> Option<&str> {
+    match token.map(|t| &t.kind) {
+        Some(Kind::Name(name)) if !KEYWORDS.contains(&name.as_str()) => Some(name),
+        _ => None,
+    }
+}
+
+fn is_punct(token: Option<&Token>, punct: &str) -> bool {
+    matches!(token.map(|t| &t.kind), Some(Kind::Punct(p)) if p == punct)
+}
+
+fn is_keyword(token: Option<&Token>, keyword: &str) -> bool {
+    matches!(token.map(|t| &t.kind), Some(Kind::Name(n)) if n == keyword)
+}
+
+/// Collect the names in a `name, name, ...` list starting at `i`.
+fn name_list(tokens: &[Token], mut i: usize, defined: &mut HashSet<String>) {
+    while let Some(name) = name_of(tokens.get(i)) {
+        defined.insert(name.to_string());
+        i += 1;
+        // skip Lua 5.4 attributes such as <const>
+        if is_punct(tokens.get(i), "<") {
+            i += 3;
+        }
+        if !is_punct(tokens.get(i), ",") {
+            break;
+        }
+        i += 1;
+    }
+}
+
+/// Every name the script defines anywhere: functions, locals, parameters,
+/// loop variables and assignment targets. Scopes are ignored, which errs on
+/// the side of not reporting.
+fn defined_names(tokens: &[Token]) -> HashSet<String> {
+    let mut defined = HashSet::new();
+    for (i, token) in tokens.iter().enumerate() {
+        let next = tokens.get(i + 1);
+        if is_keyword(Some(token), "function") {
+            let mut j = i + 1;
+            if let Some(name) = name_of(next) {
+                if !is_punct(tokens.get(i + 2), ".") && !is_punct(tokens.get(i + 2), ":") {
+                    defined.insert(name.to_string());
+                }
+            }
+            while j < tokens.len() && !is_punct(tokens.get(j), "(") {
+                j += 1;
+            }
+            name_list(tokens, j + 1, &mut defined);
+        } else if is_keyword(Some(token), "local") || is_keyword(Some(token), "for") {
+            name_list(tokens, i + 1, &mut defined);
+        } else if let Some(name) = name_of(Some(token)) {
+            let after_field = i > 0 && (is_punct(tokens.get(i - 1), ".") || is_punct(tokens.get(i - 1), ":"));
+            if is_punct(next, "=") && !after_field {
+                defined.insert(name.to_string());
+            }
+        }
+    }
+    defined
+}
+
+/// Read a Nova script without running it. `known` holds the globals the
+/// runtime provides, builtins and the Lua standard library included.
+pub fn inspect(code: &str, known: &HashSet<String>) -> ScriptReport {
+    let tokens = tokenize(code);
+    let defined = defined_names(&tokens);
+    let mut report = ScriptReport::default();
+
+    for (i, token) in tokens.iter().enumerate() {
+        let Some(name) = name_of(Some(token)) else {
+            continue;
+        };
+        let prev = if i > 0 { tokens.get(i - 1) } else { None };
+        if is_punct(prev, ".") || is_punct(prev, ":") || is_keyword(prev, "function") {
+            continue;
+        }
+        let next = tokens.get(i + 1);
+        let is_call = is_punct(next, "(") || is_punct(next, "{") || matches!(next.map(|t| &t.kind), Some(Kind::Str(_)));
+        if !is_call {
+            continue;
+        }
+
+        if !defined.contains(name) && !known.contains(name) {
+            report.undefined.entry(name.to_string()).or_insert(token.line);
+        }
+        if (name == "copy" || name == "template") && is_punct(next, "(") {
+            if let Some(Kind::Str(source)) = tokens.get(i + 2).map(|t| &t.kind) {
+                report.file_sources.push((source.clone(), token.line));
+            }
+        }
+    }
+    report
+}
@@ -1,10 +1,25 @@
 use clap::{Parser, Subcommand};
+use prompt::{flag_pair, Prompter};
 
 mod new_star;
 mod build_star;
 mod fetch;
 mod validate;
 mod galaxy;
+mod nova_shell;
+mod test_star;
+mod prompt;
+mod elf;
+mod linkage;
+mod lua_lint;
+mod split;
+mod postprocess;
+mod import;
+mod build_galaxy;
+mod recipe;
+mod rebuild_check;
+mod sbom;
+mod templates;
 
 #[derive(Parser)]
 #[command(
@@ -27,18 +42,56 @@ mod galaxy;
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// Accept the default for every question not answered by a flag
+    #[arg(long, short = 'y', global = true)]
+    yes: bool,
+
+    /// Never prompt; fail if a question is not answered by a flag
+    #[arg(long, global = true, conflicts_with = "yes")]
+    non_interactive: bool,
 }
 
 #[derive(Subcommand)]
 enum Commands {
-    /// Create a new star (interactive)
+    /// Create a new star
     NewStar {
         name: String,
+
+        #[command(flatten)]
+        args: new_star::NewStarArgs,
     },
 
     /// Build a star package
     BuildStar {
         path: String,
+
+        /// Record checksums of files/ in the built star.toml
+        #[arg(long, conflicts_with = "no_checksum")]
+        checksum: bool,
+
+        /// Do not record checksums
+        #[arg(long)]
+        no_checksum: bool,
+
+        /// Galaxy directory whose stars provide detected library dependencies
+        /// (defaults to the galaxies in /etc/cosmos/config.toml)
+        #[arg(long)]
+        galaxy: Option<String>,
+
+        /// Keep the files' own mtimes when SOURCE_DATE_EPOCH is not set
+        /// (the tarball is then not reproducible)
+        #[arg(long)]
+        keep_mtimes: bool,
+    },
+
+    /// Build a star twice and check that the tarballs are identical
+    RebuildCheck {
+        path: String,
+
+        /// Build as `build-star --keep-mtimes` does
+        #[arg(long)]
+        keep_mtimes: bool,
     },
 
     /// Fetch a star from a galaxy
@@ -49,28 +102,149 @@ enum Commands {
     /// Validate a star package
     Validate {
         path: String,
+
+        /// Galaxy directory to look dependencies up in
+        /// (defaults to the galaxies in /etc/cosmos/config.toml)
+        #[arg(long)]
+        galaxy: Option<String>,
     },
 
     /// Initialize a new galaxy
     GalaxyInit {
         name: String,
+
+        /// Enable tarball checksums for the galaxy
+        #[arg(long, conflicts_with = "no_checksums")]
+        checksums: bool,
+
+        /// Disable tarball checksums
+        #[arg(long)]
+        no_checksums: bool,
+
+        /// Keep superseded star versions when indexing
+        #[arg(long)]
+        multi_version: bool,
     },
 
     /// Update the galaxy index with star definitions
     IndexGalaxy {
         path: String,
+
+        /// Ingest star definitions and tarballs from a build-star output directory
+        #[arg(long)]
+        from: Option<String>,
+
+        /// Compute tarball and Nova module checksums
+        #[arg(long, conflicts_with = "no_checksums")]
+        checksums: bool,
+
+        /// Skip checksums and drop existing ones from meta.toml
+        #[arg(long)]
+        no_checksums: bool,
+    },
+
+    /// Build a directory of star directories in dependency order and index the results
+    BuildGalaxy {
+        path: String,
+
+        /// Galaxy directory to index the built stars into
+        #[arg(long)]
+        galaxy: String,
+
+        /// Number of stars to build at once (defaults to the number of CPUs)
+        #[arg(long, short = 'j')]
+        jobs: Option<usize>,
+
+        /// Rebuild every star, even if its inputs are unchanged
+        #[arg(long)]
+        force: bool,
+
+        /// Record file checksums in the stars and tarball checksums in the index
+        #[arg(long, conflicts_with = "no_checksum")]
+        checksum: bool,
+
+        /// Do not record checksums
+        #[arg(long)]
+        no_checksum: bool,
+
+        /// Keep the files' own mtimes when SOURCE_DATE_EPOCH is not set
+        /// (the tarballs are then not reproducible)
+        #[arg(long)]
+        keep_mtimes: bool,
+    },
+
+    /// Check a galaxy's index, tarballs, checksums and dependencies before publishing
+    GalaxyCheck {
+        path: String,
+    },
+
+    /// Convert a .deb, .apk or .pkg.tar.* package into a star directory
+    Import {
+        file: String,
+
+        /// Directory to create (defaults to the package name)
+        #[arg(long)]
+        output: Option<String>,
+    },
+
+    /// Write a software bill of materials for a star directory or built star.toml
+    Sbom {
+        path: String,
+
+        /// Output format: spdx or cyclonedx
+        #[arg(long, default_value = "spdx")]
+        format: String,
+
+        /// File to write (defaults to stdout)
+        #[arg(long)]
+        output: Option<String>,
+    },
+
+    /// Open an interactive Nova session against a throwaway install root
+    NovaShell {
+        path: String,
+
+        /// Init system service_enable and service_disable write for: openrc, runit or s6
+        #[arg(long, default_value = "openrc")]
+        init_system: String,
+    },
+
+    /// Build, install, test and uninstall a star in a temporary root
+    Test {
+        path: String,
     },
 }
 
 fn main() {
     let cli = Cli::parse();
+    let prompter = Prompter::new(cli.yes, cli.non_interactive);
 
-    match cli.command {
-        Commands::NewStar { name } => new_star::new_star(&name).unwrap(),
-        Commands::BuildStar { path } => build_star::build_star(&path).unwrap(),
-        Commands::Fetch { path } => fetch::fetch(&path).unwrap(),
-        Commands::Validate { path } => validate::validate(&path).unwrap(),
-        Commands::GalaxyInit { name } => galaxy::galaxy_init(&name).unwrap(),
-        Commands::IndexGalaxy { path } => galaxy::index_galaxy(&path).unwrap(),
+    let result = match cli.command {
+        Commands::NewStar { name, args } => new_star::new_star(&name, args, &prompter),
+        Commands::BuildStar { path, checksum, no_checksum, galaxy, keep_mtimes } => {
+            build_star::build_star(&path, flag_pair(checksum, no_checksum), galaxy.as_deref(), keep_mtimes, &prompter)
+        }
+        Commands::RebuildCheck { path, keep_mtimes } => rebuild_check::rebuild_check(&path, keep_mtimes),
+        Commands::Fetch { path } => fetch::fetch(&path),
+        Commands::Validate { path, galaxy } => validate::validate(&path, galaxy.as_deref()),
+        Commands::GalaxyInit { name, checksums, no_checksums, multi_version } => {
+            galaxy::galaxy_init(&name, flag_pair(checksums, no_checksums), multi_version, &prompter)
+        }
+        Commands::IndexGalaxy { path, from, checksums, no_checksums } => {
+            galaxy::index_galaxy(&path, from.as_deref(), flag_pair(checksums, no_checksums), &prompter)
+        }
+        Commands::BuildGalaxy { path, galaxy, jobs, force, checksum, no_checksum, keep_mtimes } => {
+            build_galaxy::build_galaxy(&path, &galaxy, jobs, force, flag_pair(checksum, no_checksum), keep_mtimes, &prompter)
+        }
+        Commands::GalaxyCheck { path } => galaxy::galaxy_check(&path),
+        Commands::Import { file, output } => import::import(&file, output.as_deref()),
+        Commands::Sbom { path, format, output } => sbom::sbom(&path, &format, output.as_deref()),
+        Commands::NovaShell { path, init_system } => nova_shell::nova_shell(&path, &init_system),
+        Commands::Test { path } => std::process::exit(test_star::test_star(&path)),
+    };
+
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
     }
-}
\ No newline at end of file
+}
@@ -1,103 +1,118 @@
 use std::collections::HashMap;
 use std::fs;
-use std::io::Write;
 use std::path::Path;
-use dialoguer::{Input, Select};
-use std::fs::File;
 use cosmos_core::star::Star;
+use crate::prompt::{Mode, Prompter};
+use crate::templates;
+
+/// Answers for `new-star` that would otherwise be prompted for.
+#[derive(clap::Args, Debug, Default)]
+pub struct NewStarArgs {
+    /// Star version
+    #[arg(long = "star-version", value_name = "VERSION")]
+    pub version: Option<String>,
+
+    /// One-line description
+    #[arg(long)]
+    pub description: Option<String>,
+
+    /// Author as "Name <email>"; repeat for more authors
+    #[arg(long = "author", value_name = "AUTHOR")]
+    pub authors: Vec<String>,
+
+    /// License identifier
+    #[arg(long)]
+    pub license: Option<String>,
+
+    /// Dependency as "name=version"; repeat for more
+    #[arg(long = "dependency", value_name = "NAME=VERSION")]
+    pub dependencies: Vec<String>,
+
+    /// Template: normal, static, library, config, service, recipe or nebula
+    #[arg(long, alias = "type", value_name = "TEMPLATE", conflicts_with = "from")]
+    pub template: Option<String>,
+
+    /// Fill files/ from an existing directory tree and install all of it
+    #[arg(long, value_name = "DIR")]
+    pub from: Option<String>,
+}
+
+pub fn parse_author(author: &str) -> (String, String) {
+    match author.split_once('<') {
+        Some((name, email)) => (name.trim().to_string(), email.trim_end_matches('>').trim().to_string()),
+        None => (author.trim().to_string(), "none@example.com".to_string()),
+    }
+}
 
-pub fn new_star(name: &str) -> Result<(), Box<dyn std::error::Error>> {
+pub fn new_star(name: &str, args: NewStarArgs, prompter: &Prompter) -> Result<(), Box<dyn std::error::Error>> {
     let dir = Path::new(name);
     if dir.exists() {
         return Err("❌ Error: Directory already exists".into());
     }
+    if let Some(from) = &args.from {
+        if !Path::new(from).is_dir() {
+            return Err(format!("❌ Error: --from {} is not a directory", from).into());
+        }
+    }
 
-    fs::create_dir_all(dir.join("files"))?;
-
-    let version: String = Input::new()
-        .with_prompt("🔢 Version")
-        .default("1.0.0".into())
-        .interact_text()?;
-
-    let description: String = Input::new()
-        .with_prompt("📝 Description")
-        .default("".into())
-        .interact_text()?;
-
-    let author_name: String = Input::new()
-        .with_prompt("👤 Author name")
-        .default("Anonymous".into())
-        .interact_text()?;
+    let mut dependencies = HashMap::new();
+    for dependency in &args.dependencies {
+        let (dep_name, dep_version) = dependency.split_once('=')
+            .ok_or_else(|| format!("❌ Error: Invalid --dependency '{}'. Expected name=version", dependency))?;
+        dependencies.insert(dep_name.trim().to_string(), dep_version.trim().to_string());
+    }
 
-    let author_email: String = Input::new()
-        .with_prompt("📧 Author email")
-        .default("none@example.com".into())
-        .interact_text()?;
+    let version = prompter.input("🔢 Version", "--star-version", args.version, "1.0.0")?;
+    let description = prompter.input("📝 Description", "--description", args.description, "")?;
 
     let mut authors = HashMap::new();
-    authors.insert(author_name, author_email);
-
-    loop {
-        let more_authors: bool = dialoguer::Confirm::new()
-            .with_prompt("Add another author?")
-            .default(false)
-            .interact()?;
-        if !more_authors {
-            break;
-        }
-        let author_name: String = Input::new()
-            .with_prompt("👤 Author name")
-            .default("Anonymous".into())
-            .interact_text()?;
-        let author_email: String = Input::new()
-            .with_prompt("📧 Author email")
-            .default("none@example.com".into())
-            .interact_text()?;
+    if args.authors.is_empty() {
+        let author_name = prompter.input("👤 Author name", "--author", None, "Anonymous")?;
+        let author_email = prompter.input("📧 Author email", "--author", None, "none@example.com")?;
         authors.insert(author_name, author_email);
-    }
-
 
-    let license: String = Input::new()
-        .with_prompt("📜 License")
-        .default("MIT".into())
-        .interact_text()?;
+        while prompter.mode() == Mode::Interactive
+            && prompter.confirm("Add another author?", "--author", None, false)?
+        {
+            let author_name = prompter.input("👤 Author name", "--author", None, "Anonymous")?;
+            let author_email = prompter.input("📧 Author email", "--author", None, "none@example.com")?;
+            authors.insert(author_name, author_email);
+        }
+    } else {
+        authors.extend(args.authors.iter().map(|author| parse_author(author)));
+    }
 
-    // choose if they want to add dependencies
-    let add_dependencies: bool = dialoguer::Confirm::new()
-        .with_prompt("Add dependencies?")
-        .default(false)
-        .interact()?;
-    let mut dependencies = HashMap::new();
-    if add_dependencies {
-        loop {
-            let dep_name: String = Input::new()
-                .with_prompt("🔗 Dependency name")
-                .default("".into())
-                .interact_text()?;
-            let dep_version: String = Input::new()
-                .with_prompt("🔗 Dependency version")
-                .default("1.0.0".into())
-                .interact_text()?;
-            dependencies.insert(dep_name, dep_version);
-            let more_dependencies: bool = dialoguer::Confirm::new()
-                .with_prompt("Add another dependency?")
-                .default(false)
-                .interact()?;
-            if !more_dependencies {
-                break;
+    let license = prompter.input("📜 License", "--license", args.license, "MIT")?;
+
+    // dependencies are optional, so only ask when none were given and someone is there to answer
+    let mut add_dependencies = !dependencies.is_empty();
+    if dependencies.is_empty() && prompter.mode() == Mode::Interactive {
+        add_dependencies = prompter.confirm("Add dependencies?", "--dependency", None, false)?;
+        if add_dependencies {
+            loop {
+                let dep_name = prompter.input("🔗 Dependency name", "--dependency", None, "")?;
+                let dep_version = prompter.input("🔗 Dependency version", "--dependency", None, "1.0.0")?;
+                dependencies.insert(dep_name, dep_version);
+                if !prompter.confirm("Add another dependency?", "--dependency", None, false)? {
+                    break;
+                }
             }
         }
     }
 
-    let type_options = vec!["normal", "nebula"];
-    let selected = Select::new()
-        .with_prompt("🧬 Type")
-        .default(0)
-        .items(&type_options)
-        .interact()?;
-    let star_type = type_options[selected].to_string();
+    // a tree to package is always a normal star
+    let template = match &args.from {
+        Some(_) => "normal",
+        None => {
+            let selected = prompter.select("🧬 Template", "--template", args.template.as_deref(), &templates::TEMPLATES, 0)?;
+            templates::TEMPLATES[selected]
+        }
+    };
+    let star_type = if template == "nebula" { "nebula" } else { "normal" }.to_string();
 
-    let star = Star {
+    fs::create_dir_all(dir)?;
+
+    let mut star = Star {
         name: name.to_string(),
         version,
         description: Some(description),
@@ -108,21 +123,35 @@ pub fn new_star(name: &str) -> Result<(), Box<dyn std::error::Error>> {
         license: Some(license),
         authors,
         checksums: None,
+        users: None,
+        groups: None,
+        directories: None,
+        sources: None,
+        provides: None,
+        static_linked: None,
+        split: None,
+        postprocess: None,
+    };
+
+    let hint = match &args.from {
+        Some(from) => {
+            let copied = templates::from_dir(Path::new(from), dir)?;
+            format!("Copied {} file(s) from {}; review install.lua", copied, from)
+        }
+        None => templates::apply(template, &mut star, dir)?,
     };
 
     let toml_str = toml::to_string_pretty(&star)?;
     fs::write(dir.join("star.toml"), toml_str)?;
 
-    if selected == 0 {
-        let mut f = File::create(dir.join("install.lua"))?;
-        writeln!(f, "function install()\n  -- copy(\"bin/tool\", \"/usr/bin/tool\")\nend")?;
-    } else {
+    if template == "nebula" {
         println!("⚠️ You selected a nebula, please remember that nebulas are metapackages and cannot contain files or install scripts.");
         if !add_dependencies {
             println!("⚠️ Nebula stars should have dependencies. Please add them to the star.toml file.");
         }
     }
 
-    println!("✅ Created new star at ./{}/", name);
+    println!("✅ Created new {} star at ./{}/", template, name);
+    println!("👉 {}", hint);
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,218 @@
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use tempfile::{tempdir, TempDir};
+use cosmos_core::star::Star;
+use nova::mlua::{Error as LuaError, Value};
+use nova::services::InitSystem;
+use nova::{Capability, NovaError, NovaRuntime, NovaSession};
+
+const HELP: &str = "\
+Commands:
+  :tree     show what has been created in the sandbox install root
+  :record   show the files, users, groups and services recorded so far
+  :reset    throw the sandbox away and start over with a fresh one
+  :reload   re-run install.lua in the current sandbox
+  :help     show this help
+  :quit     leave the shell
+Anything else is run as Lua, e.g. `install()` or `copy(\"usr/bin/app\", \"/usr/bin/app\")`.";
+
+/// A Nova session against a throwaway install root, with the star's own
+/// `files/` and `nova-lib/` available just like in a real install.
+struct Sandbox {
+    root: TempDir,
+    session: NovaSession,
+}
+
+impl Sandbox {
+    fn new(star_dir: &Path, star: &Star, init: InitSystem) -> Result<Self, Box<dyn std::error::Error>> {
+        let root = tempdir()?;
+        let mut runtime = NovaRuntime::new(star_dir, root.path())
+            .with_capability(Capability::Users)
+            .with_capability(Capability::Services)
+            .init_system(Some(init.name()))
+            .metadata("name", &star.name)
+            .metadata("version", &star.version)
+            .metadata("arch", std::env::consts::ARCH);
+        if let Some(description) = &star.description {
+            runtime = runtime.metadata("description", description);
+        }
+
+        let session = runtime.session()?;
+        let sandbox = Sandbox { root, session };
+        sandbox.load_script(star_dir);
+        Ok(sandbox)
+    }
+
+    fn load_script(&self, star_dir: &Path) {
+        let script = star_dir.join("install.lua");
+        if !script.exists() {
+            return;
+        }
+        match self.session.exec_file(script.to_str().unwrap()) {
+            Ok(()) => println!("🔧 Loaded {}", script.display()),
+            Err(e) => println!("❌ {}", e),
+        }
+    }
+}
+
+/// `init_system` picks the layout `service_enable` and `service_disable`
+/// write, since an empty sandbox gives nothing to detect it from.
+pub fn nova_shell(path: &str, init_system: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let init = InitSystem::from_name(init_system).ok_or_else(|| {
+        format!("❌ Error: Unknown init system '{}'. Expected openrc, runit or s6", init_system)
+    })?;
+
+    let dir = Path::new(path);
+    let star_path = dir.join("star.toml");
+    if !star_path.exists() {
+        return Err(format!("❌ Error: star.toml not found in {}", dir.display()).into());
+    }
+    let star: Star = toml::from_str(&fs::read_to_string(&star_path)?)?;
+
+    let mut sandbox = Sandbox::new(dir, &star, init)?;
+    println!("⭐ Nova shell for {} {} ({})", star.name, star.version, init.name());
+    println!("📁 Sandbox install root: {}", sandbox.root.path().display());
+    println!("Type :help for commands.");
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut buffer = String::new();
+
+    loop {
+        print!("{}", if buffer.is_empty() { "nova> " } else { "  ... " });
+        io::stdout().flush()?;
+
+        let Some(line) = lines.next() else {
+            println!();
+            break;
+        };
+        let line = line?;
+
+        if buffer.is_empty() {
+            match line.trim() {
+                "" => continue,
+                ":quit" | ":q" | ":exit" => break,
+                ":help" => {
+                    println!("{}", HELP);
+                    continue;
+                }
+                ":tree" => {
+                    print_tree(sandbox.root.path())?;
+                    continue;
+                }
+                ":record" => {
+                    print_record(&sandbox.session);
+                    continue;
+                }
+                ":reset" => {
+                    sandbox = Sandbox::new(dir, &star, init)?;
+                    println!("🧹 Fresh sandbox at {}", sandbox.root.path().display());
+                    continue;
+                }
+                ":reload" => {
+                    sandbox.load_script(dir);
+                    continue;
+                }
+                other if other.starts_with(':') => {
+                    println!("⚠️  Unknown command {}. Type :help for commands.", other);
+                    continue;
+                }
+                _ => {}
+            }
+        } else {
+            buffer.push('\n');
+        }
+        buffer.push_str(&line);
+
+        match sandbox.session.eval(&buffer, "stdin") {
+            Ok(values) => {
+                if !values.is_empty() {
+                    let shown: Vec<String> = values.iter().map(show_value).collect();
+                    println!("{}", shown.join("\t"));
+                }
+                buffer.clear();
+            }
+            // keep reading until the statement is complete
+            Err(NovaError::Lua(LuaError::SyntaxError { incomplete_input: true, .. })) => {}
+            Err(e) => {
+                println!("❌ {}", e);
+                buffer.clear();
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn show_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.to_string_lossy().to_string(),
+        Value::Table(_) | Value::Function(_) | Value::UserData(_) => format!("<{}>", value.type_name()),
+        other => other.to_string().unwrap_or_else(|_| other.type_name().to_string()),
+    }
+}
+
+fn print_tree(root: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mut empty = true;
+    for entry in walkdir::WalkDir::new(root).min_depth(1).sort_by_file_name() {
+        let entry = entry?;
+        let relative = entry.path().strip_prefix(root)?;
+        let indent = "  ".repeat(entry.depth() - 1);
+        let name = relative.file_name().unwrap_or_default().to_string_lossy();
+        let metadata = entry.path().symlink_metadata()?;
+        let mode = metadata.permissions().mode() & 0o7777;
+
+        if metadata.file_type().is_symlink() {
+            println!("{}{} → {}", indent, name, fs::read_link(entry.path())?.display());
+        } else if metadata.is_dir() {
+            println!("{}{}/ ({:o})", indent, name, mode);
+        } else {
+            println!("{}{} ({:o}, {} bytes)", indent, name, mode, metadata.len());
+        }
+        empty = false;
+    }
+    if empty {
+        println!("(sandbox is empty)");
+    }
+    Ok(())
+}
+
+fn print_record(session: &NovaSession) {
+    let record = session.record();
+    for (label, items) in [
+        ("📁 Files", &record.files),
+        ("👤 Users", &record.users),
+        ("👥 Groups", &record.groups),
+        ("🔧 Service links", &record.service_links),
+    ] {
+        println!("{}: {}", label, if items.is_empty() { "-".to_string() } else { items.join(", ") });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn services_use_the_chosen_init_system() {
+        let star_dir = tempdir().unwrap();
+        let star: Star = toml::from_str("name = \"hello\"\nversion = \"1.0.0\"\n[authors]").unwrap();
+
+        for (init, definition, link) in [
+            (InitSystem::OpenRc, "etc/init.d/hello", "etc/runlevels/default/hello"),
+            (InitSystem::Runit, "etc/sv/hello", "etc/runit/runsvdir/default/hello"),
+        ] {
+            let sandbox = Sandbox::new(star_dir.path(), &star, init).unwrap();
+            let definition = sandbox.root.path().join(definition);
+            fs::create_dir_all(definition.parent().unwrap()).unwrap();
+            fs::write(&definition, "").unwrap();
+
+            sandbox.session.eval("service_enable(\"hello\")", "test").unwrap();
+            assert!(sandbox.root.path().join(link).symlink_metadata().is_ok());
+            sandbox.session.eval("service_disable(\"hello\")", "test").unwrap();
+            assert!(sandbox.root.path().join(link).symlink_metadata().is_err());
+        }
+    }
+}
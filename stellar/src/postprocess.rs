@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::os::unix::fs::{symlink, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use flate2::{Compression, GzBuilder};
+use cosmos_core::star::{Star, StarSplit};
+
+use crate::elf::{self, ElfInfo};
+
+/// Where detached debug info is installed, relative to the install root.
+pub const DEBUG_DIR: &str = "usr/lib/debug";
+
+const COMPRESSED: [&str; 4] = [".gz", ".bz2", ".xz", ".zst"];
+
+/// Every staged path below `files_dir` with its install path, collected up
+/// front so steps can add and remove files as they go.
+fn staged(files_dir: &Path) -> Result<Vec<(PathBuf, String)>, Box<dyn std::error::Error>> {
+    let mut paths = vec![];
+    for entry in walkdir::WalkDir::new(files_dir).min_depth(1).sort_by_file_name() {
+        let entry = entry?;
+        let relative = entry.path().strip_prefix(files_dir)?.to_string_lossy().to_string();
+        paths.push((entry.path().to_path_buf(), relative));
+    }
+    Ok(paths)
+}
+
+/// `elf::read`, warning about and skipping files that cannot be read.
+fn read_elf(path: &Path, relative: &str) -> Option<ElfInfo> {
+    elf::read(path).unwrap_or_else(|e| {
+        println!("⚠️  Could not read /{}: {}", relative, e);
+        None
+    })
+}
+
+/// Run the `[postprocess]` steps of `star` over the staged `files_dir`, then
+/// warn about risky permissions. Stars without the table only get the
+/// warnings, since their install scripts may rely on the staged names.
+/// Returns the `<name>-debug` split to carve when debug info was kept.
+pub fn run(star: &Star, files_dir: &Path) -> Result<Option<StarSplit>, Box<dyn std::error::Error>> {
+    if !files_dir.exists() {
+        return Ok(None);
+    }
+    let Some(options) = &star.postprocess else {
+        lint_permissions(files_dir)?;
+        return Ok(None);
+    };
+
+    if options.remove_la() {
+        remove_la(files_dir)?;
+    }
+    if options.compress_man() {
+        compress_man(files_dir)?;
+    }
+    let kept_debug = options.strip() && strip(files_dir, options.debug())?;
+    if options.fix_permissions() {
+        fix_permissions(files_dir)?;
+    }
+    lint_permissions(files_dir)?;
+
+    Ok(kept_debug.then(|| StarSplit {
+        name: format!("{}-debug", star.name),
+        description: Some(format!("Debug symbols for {}", star.name)),
+        dependencies: Some(HashMap::from([(star.name.clone(), "*".to_string())])),
+        files: vec![format!("/{}", DEBUG_DIR)],
+    }))
+}
+
+/// Drop libtool `.la` archives from library directories; nothing on a Cosmos
+/// system links through them.
+fn remove_la(files_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mut removed = 0;
+    for (path, relative) in staged(files_dir)? {
+        let in_lib = Path::new(&relative)
+            .parent()
+            .is_some_and(|dir| dir.components().any(|c| c.as_os_str().to_string_lossy().starts_with("lib")));
+        if in_lib && relative.ends_with(".la") && path.symlink_metadata()?.is_file() {
+            fs::remove_file(&path)?;
+            removed += 1;
+        }
+    }
+    if removed > 0 {
+        println!("🧹 Removed {} libtool archive(s)", removed);
+    }
+    Ok(())
+}
+
+/// Gzip every man page and point symlinks between them at the compressed names.
+fn compress_man(files_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mut compressed = 0;
+    for (path, relative) in staged(files_dir)? {
+        let is_man = relative.starts_with("usr/share/man/") || relative.starts_with("usr/man/");
+        if !is_man || COMPRESSED.iter().any(|ext| relative.ends_with(ext)) {
+            continue;
+        }
+
+        let metadata = path.symlink_metadata()?;
+        let target = PathBuf::from(format!("{}.gz", path.display()));
+        if metadata.file_type().is_symlink() {
+            let link = fs::read_link(&path)?.to_string_lossy().to_string();
+            let link = if COMPRESSED.iter().any(|ext| link.ends_with(ext)) { link } else { format!("{}.gz", link) };
+            fs::remove_file(&path)?;
+            symlink(link, &target)?;
+        } else if metadata.is_file() {
+            let mut gz = GzBuilder::new()
+                .mtime(0)
+                .operating_system(255)
+                .write(File::create(&target)?, Compression::best());
+            gz.write_all(&fs::read(&path)?)?;
+            gz.finish()?;
+            fs::set_permissions(&target, metadata.permissions())?;
+            fs::remove_file(&path)?;
+            compressed += 1;
+        }
+    }
+    if compressed > 0 {
+        println!("🗜️  Compressed {} man page(s)", compressed);
+    }
+    Ok(())
+}
+
+/// A binutils program, overridable for cross builds (`STRIP`, `OBJCOPY`).
+fn tool(var: &str, default: &str) -> String {
+    std::env::var(var).unwrap_or_else(|_| default.to_string())
+}
+
+/// Run a binutils program on `/relative`. `Ok(false)` means it ran and failed,
+/// which is reported here.
+fn run_tool(program: &str, args: &[String], relative: &str) -> io::Result<bool> {
+    let output = Command::new(program).args(args).output()?;
+    if !output.status.success() {
+        println!("⚠️  {} failed on /{}: {}", program, relative, String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(output.status.success())
+}
+
+/// Strip every ELF file, first saving its debug info under `DEBUG_DIR` when
+/// `keep_debug` is set. Returns whether any debug info was saved.
+fn strip(files_dir: &Path, keep_debug: bool) -> Result<bool, Box<dyn std::error::Error>> {
+    let strip = tool("STRIP", "strip");
+    let objcopy = tool("OBJCOPY", "objcopy");
+    let needed = if keep_debug { vec![&strip, &objcopy] } else { vec![&strip] };
+    if let Some(missing) = needed.into_iter().find(|program| Command::new(program).arg("--version").output().is_err()) {
+        println!("⚠️  {} not found, ELF files are left unstripped", missing);
+        return Ok(false);
+    }
+    let (mut stripped, mut before, mut after, mut kept) = (0, 0, 0, false);
+
+    for (path, relative) in staged(files_dir)? {
+        let metadata = path.symlink_metadata()?;
+        if !metadata.is_file() || relative.starts_with(DEBUG_DIR) || read_elf(&path, &relative).is_none() {
+            continue;
+        }
+        let file = path.to_string_lossy().to_string();
+
+        let result = (|| -> io::Result<bool> {
+            if keep_debug {
+                let debug = files_dir.join(DEBUG_DIR).join(format!("{}.debug", relative));
+                fs::create_dir_all(debug.parent().unwrap())?;
+                let debug = debug.to_string_lossy().to_string();
+                if !run_tool(&objcopy, &["--only-keep-debug".into(), file.clone(), debug.clone()], &relative)? {
+                    return Ok(false);
+                }
+                fs::set_permissions(&debug, fs::Permissions::from_mode(0o644))?;
+                kept = true;
+                return Ok(run_tool(&strip, &["--strip-unneeded".into(), file.clone()], &relative)?
+                    && run_tool(&objcopy, &[format!("--add-gnu-debuglink={}", debug), file.clone()], &relative)?);
+            }
+            run_tool(&strip, &["--strip-unneeded".into(), file.clone()], &relative)
+        })();
+
+        match result {
+            Ok(true) => {
+                stripped += 1;
+                before += metadata.len();
+                after += fs::metadata(&path)?.len();
+            }
+            Ok(false) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    if stripped > 0 {
+        println!("📉 Stripped {} ELF file(s): {} → {} bytes", stripped, before, after);
+    }
+    Ok(kept)
+}
+
+/// Clear group and world write bits (sticky directories excepted) and make
+/// programs executable wherever they can be read.
+fn fix_permissions(files_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mut fixed = 0;
+    for (path, relative) in staged(files_dir)? {
+        let metadata = path.symlink_metadata()?;
+        let mode = metadata.permissions().mode() & 0o7777;
+        let mut wanted = mode;
+
+        if metadata.is_dir() {
+            if mode & 0o1000 == 0 {
+                wanted &= !0o022;
+            }
+        } else if metadata.is_file() {
+            wanted &= !0o022;
+            let in_bin = Path::new(&relative)
+                .parent()
+                .and_then(Path::file_name)
+                .is_some_and(|dir| dir == "bin" || dir == "sbin" || dir == "libexec");
+            let is_program = !relative.starts_with(DEBUG_DIR)
+                && (in_bin || read_elf(&path, &relative).is_some_and(|info| info.interpreter.is_some()));
+            if is_program {
+                wanted |= (wanted & 0o444) >> 2;
+            }
+        } else {
+            continue;
+        }
+
+        if wanted != mode {
+            fs::set_permissions(&path, fs::Permissions::from_mode(wanted))?;
+            fixed += 1;
+        }
+    }
+    if fixed > 0 {
+        println!("🔒 Fixed permissions on {} path(s)", fixed);
+    }
+    Ok(())
+}
+
+/// Warn about world-writable paths and setuid or setgid files, which are
+/// sometimes intended but always worth a second look.
+fn lint_permissions(files_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    for (path, relative) in staged(files_dir)? {
+        let metadata = path.symlink_metadata()?;
+        let mode = metadata.permissions().mode();
+        if metadata.file_type().is_symlink() {
+            continue;
+        }
+        if mode & 0o002 != 0 && !(metadata.is_dir() && mode & 0o1000 != 0) {
+            println!("⚠️  /{} is world-writable ({:o})", relative, mode & 0o7777);
+        }
+        if metadata.is_file() && mode & 0o4000 != 0 {
+            println!("⚠️  /{} is setuid ({:o})", relative, mode & 0o7777);
+        }
+        if metadata.is_file() && mode & 0o2000 != 0 {
+            println!("⚠️  /{} is setgid ({:o})", relative, mode & 0o7777);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn star(postprocess: &str) -> Star {
+        toml::from_str(&format!("name = \"foo\"\nversion = \"1.0.0\"\n[authors]\n{}", postprocess)).unwrap()
+    }
+
+    fn stage(files_dir: &Path) {
+        fs::create_dir_all(files_dir.join("usr/lib")).unwrap();
+        fs::create_dir_all(files_dir.join("usr/share/man/man1")).unwrap();
+        fs::write(files_dir.join("usr/lib/libfoo.la"), "# libtool").unwrap();
+        fs::write(files_dir.join("usr/share/man/man1/foo.1"), ".TH FOO 1").unwrap();
+        fs::set_permissions(files_dir.join("usr/share/man/man1/foo.1"), fs::Permissions::from_mode(0o664)).unwrap();
+    }
+
+    #[test]
+    fn stars_without_the_table_are_left_alone() {
+        let temp = tempfile::tempdir().unwrap();
+        stage(temp.path());
+        assert!(run(&star(""), temp.path()).unwrap().is_none());
+        assert!(temp.path().join("usr/lib/libfoo.la").exists());
+        let man = fs::metadata(temp.path().join("usr/share/man/man1/foo.1")).unwrap();
+        assert_eq!(man.permissions().mode() & 0o777, 0o664);
+    }
+
+    #[test]
+    fn the_table_turns_steps_on() {
+        let temp = tempfile::tempdir().unwrap();
+        stage(temp.path());
+        assert!(run(&star("[postprocess]\nstrip = false"), temp.path()).unwrap().is_none());
+        assert!(!temp.path().join("usr/lib/libfoo.la").exists());
+        assert!(!temp.path().join("usr/share/man/man1/foo.1").exists());
+        let man = fs::metadata(temp.path().join("usr/share/man/man1/foo.1.gz")).unwrap();
+        assert_eq!(man.permissions().mode() & 0o777, 0o644);
+    }
+
+    #[test]
+    fn unreadable_files_are_not_elf() {
+        assert!(read_elf(Path::new("/nonexistent/file"), "nonexistent/file").is_none());
+    }
+}
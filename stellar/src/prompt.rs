@@ -0,0 +1,139 @@
+use dialoguer::{Confirm, Input, Select};
+
+/// How stellar gets answers it would otherwise ask for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Ask on the terminal for anything not given on the command line.
+    Interactive,
+    /// `--yes`: take the default for anything not given on the command line.
+    Defaults,
+    /// `--non-interactive`: never ask; anything not given is an error.
+    Strict,
+}
+
+/// Answers prompts from command-line values first, then according to the mode.
+#[derive(Debug, Clone, Copy)]
+pub struct Prompter {
+    mode: Mode,
+}
+
+type PromptResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+impl Prompter {
+    pub fn new(yes: bool, non_interactive: bool) -> Self {
+        let mode = if non_interactive {
+            Mode::Strict
+        } else if yes {
+            Mode::Defaults
+        } else {
+            Mode::Interactive
+        };
+        Prompter { mode }
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    fn missing(&self, prompt: &str, flag: &str) -> Box<dyn std::error::Error> {
+        format!("❌ Error: No answer for \"{}\" in --non-interactive mode. Pass {}.", prompt, flag).into()
+    }
+
+    pub fn confirm(&self, prompt: &str, flag: &str, given: Option<bool>, default: bool) -> PromptResult<bool> {
+        match (given, self.mode) {
+            (Some(answer), _) => Ok(answer),
+            (None, Mode::Defaults) => Ok(default),
+            (None, Mode::Strict) => Err(self.missing(prompt, flag)),
+            (None, Mode::Interactive) => Ok(Confirm::new().with_prompt(prompt).default(default).interact()?),
+        }
+    }
+
+    pub fn input(&self, prompt: &str, flag: &str, given: Option<String>, default: &str) -> PromptResult<String> {
+        match (given, self.mode) {
+            (Some(answer), _) => Ok(answer),
+            (None, Mode::Defaults) => Ok(default.to_string()),
+            (None, Mode::Strict) => Err(self.missing(prompt, flag)),
+            (None, Mode::Interactive) => Ok(Input::new()
+                .with_prompt(prompt)
+                .default(default.to_string())
+                .interact_text()?),
+        }
+    }
+
+    /// Pick one of `options`, returning its index.
+    pub fn select(&self, prompt: &str, flag: &str, given: Option<&str>, options: &[&str], default: usize) -> PromptResult<usize> {
+        match (given, self.mode) {
+            (Some(answer), _) => options.iter().position(|o| *o == answer).ok_or_else(|| {
+                format!("❌ Error: Invalid value '{}' for {}. Expected one of: {}", answer, flag, options.join(", ")).into()
+            }),
+            (None, Mode::Defaults) => Ok(default),
+            (None, Mode::Strict) => Err(self.missing(prompt, flag)),
+            (None, Mode::Interactive) => Ok(Select::new()
+                .with_prompt(prompt)
+                .default(default)
+                .items(options)
+                .interact()?),
+        }
+    }
+}
+
+/// Turn a `--flag` / `--no-flag` pair into an optional answer.
+pub fn flag_pair(yes: bool, no: bool) -> Option<bool> {
+    match (yes, no) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mode_follows_flags() {
+        assert_eq!(Prompter::new(false, false).mode(), Mode::Interactive);
+        assert_eq!(Prompter::new(true, false).mode(), Mode::Defaults);
+        assert_eq!(Prompter::new(true, true).mode(), Mode::Strict);
+        assert_eq!(Prompter::new(false, true).mode(), Mode::Strict);
+    }
+
+    #[test]
+    fn given_answers_win_in_every_mode() {
+        for prompter in [Prompter::new(false, false), Prompter::new(true, false), Prompter::new(false, true)] {
+            assert!(!prompter.confirm("Proceed?", "--yes", Some(false), true).unwrap());
+            assert_eq!(prompter.input("Version", "--star-version", Some("2.0.0".into()), "1.0.0").unwrap(), "2.0.0");
+            assert_eq!(prompter.select("Type", "--type", Some("nebula"), &["normal", "nebula"], 0).unwrap(), 1);
+        }
+    }
+
+    #[test]
+    fn defaults_mode_takes_defaults() {
+        let prompter = Prompter::new(true, false);
+        assert!(prompter.confirm("Proceed?", "--yes", None, true).unwrap());
+        assert_eq!(prompter.input("Version", "--star-version", None, "1.0.0").unwrap(), "1.0.0");
+        assert_eq!(prompter.select("Type", "--type", None, &["normal", "nebula"], 1).unwrap(), 1);
+    }
+
+    #[test]
+    fn strict_mode_names_the_missing_flag() {
+        let prompter = Prompter::new(false, true);
+        let error = prompter.input("Version", "--star-version", None, "1.0.0").unwrap_err().to_string();
+        assert!(error.contains("--star-version"), "{}", error);
+        assert!(prompter.confirm("Proceed?", "--yes", None, true).is_err());
+        assert!(prompter.select("Type", "--type", None, &["normal"], 0).is_err());
+    }
+
+    #[test]
+    fn unknown_selections_are_rejected() {
+        let error = Prompter::new(true, false).select("Type", "--type", Some("bogus"), &["normal", "nebula"], 0).unwrap_err();
+        assert!(error.to_string().contains("Expected one of: normal, nebula"));
+    }
+
+    #[test]
+    fn flag_pairs() {
+        assert_eq!(flag_pair(true, false), Some(true));
+        assert_eq!(flag_pair(false, true), Some(false));
+        assert_eq!(flag_pair(false, false), None);
+    }
+}
@@ -0,0 +1,145 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use flate2::read::GzDecoder;
+use sha2::{Digest, Sha256};
+use tar::Archive;
+use tempfile::tempdir;
+
+use crate::build_star;
+
+/// What one tarball entry looks like, for comparing two builds.
+#[derive(PartialEq)]
+struct EntrySummary {
+    kind: String,
+    mode: u32,
+    uid: u64,
+    gid: u64,
+    mtime: u64,
+    sha256: String,
+}
+
+/// Build the star in `path` twice in separate directories and check that
+/// both builds produce byte-identical tarballs. `keep_mtimes` builds as
+/// `build-star --keep-mtimes` does.
+pub fn rebuild_check(path: &str, keep_mtimes: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = Path::new(path);
+    let first = tempdir()?;
+    let second = tempdir()?;
+    let galaxies = build_star::target_galaxies(None)?;
+
+    println!("🔁 First build");
+    let a = build_star::build(dir, true, keep_mtimes, first.path(), &galaxies)?;
+    println!("🔁 Second build");
+    let b = build_star::build(dir, true, keep_mtimes, second.path(), &galaxies)?;
+
+    let a_tarballs = tarballs(a);
+    let b_tarballs = tarballs(b);
+    if a_tarballs.is_empty() && b_tarballs.is_empty() {
+        println!("✅ Nothing to package, so nothing to compare.");
+        return Ok(());
+    }
+
+    let mut reproducible = true;
+    for (name, a_tar) in &a_tarballs {
+        let Some(b_tar) = b_tarballs.get(name) else {
+            println!("❌ {} was only packaged by the first build", name);
+            reproducible = false;
+            continue;
+        };
+        reproducible &= compare(name, a_tar, b_tar)?;
+    }
+    for name in b_tarballs.keys().filter(|name| !a_tarballs.contains_key(*name)) {
+        println!("❌ {} was only packaged by the second build", name);
+        reproducible = false;
+    }
+
+    if reproducible {
+        Ok(())
+    } else {
+        Err(format!("❌ Error: {} is not reproducible", dir.display()).into())
+    }
+}
+
+/// The tarball of a build and of each of its split stars, by star name.
+fn tarballs(built: build_star::BuiltStar) -> BTreeMap<String, PathBuf> {
+    let mut found = BTreeMap::new();
+    if let Some(tarball) = built.tarball {
+        found.insert(built.star.name, tarball);
+    }
+    for split in built.splits {
+        found.extend(tarballs(split));
+    }
+    found
+}
+
+/// Compare one star's tarball from both builds, listing the entries that differ.
+fn compare(name: &str, a_tar: &Path, b_tar: &Path) -> Result<bool, Box<dyn std::error::Error>> {
+    let a_sum = nova::archive::sha256_file(a_tar)?;
+    let b_sum = nova::archive::sha256_file(b_tar)?;
+    if a_sum == b_sum {
+        println!("✅ Reproducible: {} has sha256 {} in both builds", name, a_sum);
+        return Ok(true);
+    }
+
+    println!("❌ {} differs: {} vs {}", name, a_sum, b_sum);
+    let a_entries = summarize(a_tar)?;
+    let b_entries = summarize(b_tar)?;
+    let mut differences = 0;
+    for name in a_entries.keys().chain(b_entries.keys().filter(|k| !a_entries.contains_key(*k))) {
+        match (a_entries.get(name), b_entries.get(name)) {
+            (Some(_), None) => println!("  - {} is only in the first build", name),
+            (None, Some(_)) => println!("  - {} is only in the second build", name),
+            (Some(x), Some(y)) if x != y => println!("  - {} differs ({})", name, describe(x, y)),
+            _ => continue,
+        }
+        differences += 1;
+    }
+    if differences == 0 {
+        println!("  - Entries match; the archives differ in order or compression");
+    }
+    Ok(false)
+}
+
+fn summarize(tarball: &Path) -> Result<BTreeMap<String, EntrySummary>, Box<dyn std::error::Error>> {
+    let mut archive = Archive::new(GzDecoder::new(File::open(tarball)?));
+    let mut entries = BTreeMap::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.display().to_string();
+        let header = entry.header();
+        let mut summary = EntrySummary {
+            kind: format!("{:?}", header.entry_type()),
+            mode: header.mode()?,
+            uid: header.uid()?,
+            gid: header.gid()?,
+            mtime: header.mtime()?,
+            sha256: String::new(),
+        };
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut entry, &mut hasher)?;
+        summary.sha256 = format!("{:x}", hasher.finalize());
+        entries.insert(name, summary);
+    }
+    Ok(entries)
+}
+
+fn describe(a: &EntrySummary, b: &EntrySummary) -> String {
+    let mut fields = vec![];
+    if a.kind != b.kind {
+        fields.push(format!("type {} vs {}", a.kind, b.kind));
+    }
+    if a.mode != b.mode {
+        fields.push(format!("mode {:o} vs {:o}", a.mode, b.mode));
+    }
+    if (a.uid, a.gid) != (b.uid, b.gid) {
+        fields.push(format!("owner {}:{} vs {}:{}", a.uid, a.gid, b.uid, b.gid));
+    }
+    if a.mtime != b.mtime {
+        fields.push(format!("mtime {} vs {}", a.mtime, b.mtime));
+    }
+    if a.sha256 != b.sha256 {
+        fields.push("content".to_string());
+    }
+    fields.join(", ")
+}
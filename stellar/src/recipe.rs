@@ -0,0 +1,115 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use cosmos_core::star::{Star, StarSource};
+use nova::{Capability, NovaRuntime};
+
+/// Make every `[[sources]]` entry available locally and verified. Remote
+/// sources are downloaded into `.cache/src/`; a cached copy is reused when
+/// its checksum still matches. Local paths are read in place.
+pub fn fetch_sources(dir: &Path, star: &Star) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let mut fetched = vec![];
+    for source in star.sources.as_deref().unwrap_or_default() {
+        fetched.push(fetch_source(dir, source)?);
+    }
+    Ok(fetched)
+}
+
+fn fetch_source(dir: &Path, source: &StarSource) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let url = source.url.as_str();
+    if !(url.starts_with("http://") || url.starts_with("https://")) {
+        let local = dir.join(url.strip_prefix("file://").unwrap_or(url));
+        if !local.exists() {
+            return Err(format!("❌ Error: Source not found: {}", local.display()).into());
+        }
+        println!("⭐ Local source: {}", local.display());
+        verify(source, &local)?;
+        return Ok(local);
+    }
+
+    let target = dir.join(".cache").join("src").join(source.filename());
+    if target.exists() {
+        match verify(source, &target) {
+            Ok(()) => {
+                println!("📦 Using cached {}", target.display());
+                return Ok(target);
+            }
+            Err(_) => println!("⚠️  Cached {} does not match its checksum, downloading again", target.display()),
+        }
+    }
+
+    fs::create_dir_all(target.parent().unwrap())?;
+    println!("🌐 Fetching {}", url);
+    let bytes = cosmos_transport::fetch_bytes(url).map_err(|e| format!("❌ Error: Failed to download {}: {}", url, e))?;
+    fs::write(&target, bytes)?;
+    verify(source, &target)?;
+    println!("✅ Saved to {}", target.display());
+    Ok(target)
+}
+
+fn verify(source: &StarSource, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let actual = nova::archive::sha256_file(path)?;
+    match &source.sha256 {
+        Some(expected) if !expected.eq_ignore_ascii_case(&actual) => Err(format!(
+            "❌ Error: Checksum mismatch for {}: expected {}, got {}",
+            source.filename(), expected, actual
+        )
+        .into()),
+        Some(_) => Ok(()),
+        None => {
+            println!("⚠️  No sha256 for {}. Add sha256 = \"{}\" to pin it.", source.filename(), actual);
+            Ok(())
+        }
+    }
+}
+
+/// Unpack or copy the fetched sources into `build_dir`.
+fn prepare(star: &Star, fetched: &[PathBuf], build_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    for (source, path) in star.sources.as_deref().unwrap_or_default().iter().zip(fetched) {
+        if source.should_extract() {
+            println!("📦 Extracting {} → {}", source.filename(), build_dir.display());
+            nova::archive::extract(path, build_dir)
+                .map_err(|e| format!("❌ Error: Failed to extract {}: {}", source.filename(), e))?;
+        } else {
+            fs::copy(path, build_dir.join(source.filename()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Run a recipe: fetch and unpack the sources into `build_dir`, then call
+/// `build()` from `install.lua` with `srcdir` set to the build directory and
+/// `destdir` to `destdir`. Returns whether the script also defines `install()`.
+pub fn build_recipe(dir: &Path, star: &Star, build_dir: &Path, destdir: &Path) -> Result<bool, Box<dyn std::error::Error>> {
+    let install_lua = dir.join("install.lua");
+    if !install_lua.exists() {
+        return Err("❌ Error: Stars with [[sources]] need an install.lua with a build() function.".into());
+    }
+
+    let fetched = fetch_sources(dir, star)?;
+    prepare(star, &fetched, build_dir)?;
+    fs::create_dir_all(destdir)?;
+
+    let mut runtime = NovaRuntime::new(build_dir, destdir)
+        .with_capability(Capability::Archive)
+        .with_capability(Capability::Net)
+        .module_dir(&dir.join("nova-lib"))
+        .files_dir(build_dir)
+        .global("srcdir", &build_dir.display().to_string())
+        .global("destdir", &destdir.display().to_string())
+        .metadata("name", &star.name)
+        .metadata("version", &star.version)
+        .metadata("arch", std::env::consts::ARCH)
+        .entry("build");
+    if let Some(description) = &star.description {
+        runtime = runtime.metadata("description", description);
+    }
+
+    println!("🔧 Running build() from {}", install_lua.display());
+    let script = install_lua.to_str().unwrap();
+    let session = runtime.session().map_err(|e| format!("Nova build error: {}", e))?;
+    session.exec_file(script).map_err(|e| format!("Nova build error: {}", e))?;
+    session.call_entry(script).map_err(|e| format!("Nova build error: {}", e))?;
+
+    let install = session.lua().globals().get::<Option<nova::mlua::Function>>("install")?;
+    Ok(install.is_some())
+}
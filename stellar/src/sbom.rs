@@ -0,0 +1,57 @@
+use std::fs;
+use std::path::Path;
+use cosmos_core::sbom::{self, Component, SbomFile, SbomFormat};
+use cosmos_core::star::Star;
+
+/// Every regular file under `files_dir` with its install path and digests.
+fn hash_files(files_dir: &Path) -> Result<Vec<SbomFile>, Box<dyn std::error::Error>> {
+    let mut files = vec![];
+    for entry in walkdir::WalkDir::new(files_dir).min_depth(1).sort_by_file_name() {
+        let entry = entry?;
+        if entry.file_type().is_file() {
+            let relative = entry.path().strip_prefix(files_dir)?;
+            files.push(SbomFile::hash(&format!("/{}", relative.display()), entry.path())?);
+        }
+    }
+    Ok(files)
+}
+
+/// Write an SBOM for a star directory or a built `star.toml`. A directory's
+/// files/ are hashed as they are; a built definition uses its recorded
+/// checksums. Dependencies are listed by name only.
+pub fn sbom(path: &str, format: &str, output: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let format: SbomFormat = format.parse().map_err(|e| format!("❌ Error: {}", e))?;
+    let path = Path::new(path);
+    let (star_path, files_dir) = if path.is_dir() {
+        (path.join("star.toml"), Some(path.join("files")))
+    } else {
+        (path.to_path_buf(), None)
+    };
+    let star_str = fs::read_to_string(&star_path)
+        .map_err(|e| format!("❌ Error: Could not read {}: {}", star_path.display(), e))?;
+    let star: Star = toml::from_str(&star_str)?;
+
+    let mut star_component = Component::from_star(&star);
+    if let Some(files_dir) = files_dir.filter(|dir| dir.is_dir()) {
+        star_component.files = hash_files(&files_dir)?;
+    }
+    let mut components: Vec<Component> = star_component
+        .dependencies
+        .iter()
+        .map(|name| Component { name: name.clone(), ..Component::default() })
+        .collect();
+    components.insert(0, star_component);
+
+    let name = format!("{}-{}", star.name, star.version);
+    let tool = format!("stellar-{}", env!("CARGO_PKG_VERSION"));
+    let document = sbom::render(&name, &tool, &components, format);
+
+    match output {
+        Some(output) => {
+            fs::write(output, document + "\n")?;
+            println!("📝 Wrote SBOM for {} {} to {}", star.name, star.version, output);
+        }
+        None => println!("{}", document),
+    }
+    Ok(())
+}
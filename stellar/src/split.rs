@@ -0,0 +1,236 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::{tempdir, TempDir};
+use cosmos_core::star::{Star, StarSplit};
+use crate::lua_lint;
+
+/// One `[[split]]` output: its star definition and a staging root holding
+/// the `files/` it took from the main build.
+pub struct SplitOutput {
+    pub star: Star,
+    root: TempDir,
+}
+
+impl SplitOutput {
+    pub fn root(&self) -> &Path {
+        self.root.path()
+    }
+
+    pub fn files_dir(&self) -> PathBuf {
+        self.root.path().join("files")
+    }
+}
+
+/// Match one path segment against a pattern with `*` and `?`.
+fn segment_match(pattern: &[char], text: &[char]) -> bool {
+    match (pattern.first(), text.first()) {
+        (None, None) => true,
+        (Some('*'), _) => segment_match(&pattern[1..], text) || (!text.is_empty() && segment_match(pattern, &text[1..])),
+        (Some('?'), Some(_)) => segment_match(&pattern[1..], &text[1..]),
+        (Some(p), Some(t)) if p == t => segment_match(&pattern[1..], &text[1..]),
+        _ => false,
+    }
+}
+
+fn segments_match(pattern: &[&str], path: &[&str]) -> bool {
+    match (pattern.first(), path.first()) {
+        (None, None) => true,
+        (Some(&"**"), _) => segments_match(&pattern[1..], path) || (!path.is_empty() && segments_match(pattern, &path[1..])),
+        (Some(p), Some(t)) => {
+            let p: Vec<char> = p.chars().collect();
+            let t: Vec<char> = t.chars().collect();
+            segment_match(&p, &t) && segments_match(&pattern[1..], &path[1..])
+        }
+        _ => false,
+    }
+}
+
+/// Whether the install path `path` matches `pattern`. `*` and `?` stay within
+/// one directory, `**` spans any number of them, and a pattern naming a
+/// directory matches everything inside it.
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<&str> = pattern.trim_matches('/').split('/').collect();
+    let path: Vec<&str> = path.trim_matches('/').split('/').collect();
+    (1..=path.len()).any(|len| segments_match(&pattern, &path[..len]))
+}
+
+/// The `[[split]]` of `star` that `carve` would move `path` into, if any.
+pub fn claimed_by<'a>(star: &'a Star, path: &str) -> Option<&'a str> {
+    star.split
+        .iter()
+        .flatten()
+        .find(|split| split.files.iter().any(|pattern| glob_match(pattern, path)))
+        .map(|split| split.name.as_str())
+}
+
+/// `copy` and `template` sources in the main star's install script that a
+/// split takes away, as (source, line, split). They would be missing when the
+/// script runs at install time.
+pub fn carved_sources(star: &Star, script: &str) -> Vec<(String, usize, String)> {
+    lua_lint::inspect(script, &HashSet::new())
+        .file_sources
+        .into_iter()
+        .filter_map(|(source, line)| {
+            let split = claimed_by(star, &source)?.to_string();
+            Some((source, line, split))
+        })
+        .collect()
+}
+
+fn split_star(parent: &Star, split: &StarSplit) -> Star {
+    Star {
+        name: split.name.clone(),
+        version: parent.version.clone(),
+        authors: parent.authors.clone(),
+        star_type: Some("normal".to_string()),
+        description: split.description.clone().or_else(|| parent.description.clone()),
+        license: parent.license.clone(),
+        dependencies: split.dependencies.clone(),
+        install_script: None,
+        source: None,
+        checksums: None,
+        users: None,
+        groups: None,
+        directories: None,
+        sources: None,
+        provides: None,
+        static_linked: parent.static_linked,
+        split: None,
+        postprocess: None,
+    }
+}
+
+/// Move the staged files claimed by each `[[split]]` of `parent` out of
+/// `files_dir` into that split's own staging root. When globs overlap, the
+/// first split listed wins; whatever no split claims stays with the parent.
+pub fn carve(parent: &Star, files_dir: &Path) -> Result<Vec<SplitOutput>, Box<dyn std::error::Error>> {
+    let mut outputs = vec![];
+    let Some(splits) = &parent.split else {
+        return Ok(outputs);
+    };
+
+    let mut remaining = vec![];
+    if files_dir.exists() {
+        for entry in walkdir::WalkDir::new(files_dir).min_depth(1).sort_by_file_name() {
+            let entry = entry?;
+            if !entry.file_type().is_dir() {
+                remaining.push(entry.path().strip_prefix(files_dir)?.to_path_buf());
+            }
+        }
+    }
+
+    let mut emptied = vec![];
+    for split in splits {
+        let output = SplitOutput { star: split_star(parent, split), root: tempdir()? };
+        let mut claimed = 0;
+        remaining.retain(|relative| {
+            let path = relative.to_string_lossy();
+            if !split.files.iter().any(|pattern| glob_match(pattern, &path)) {
+                return true;
+            }
+            let dest = output.files_dir().join(relative);
+            let moved = dest.parent().map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| fs::rename(files_dir.join(relative), &dest));
+            match moved {
+                Ok(()) => {
+                    claimed += 1;
+                    emptied.extend(relative.parent().map(Path::to_path_buf));
+                    false
+                }
+                Err(e) => {
+                    println!("⚠️  Could not move /{} to {}: {}", path, split.name, e);
+                    true
+                }
+            }
+        });
+
+        if claimed == 0 {
+            println!("⚠️  Split {} matched no files", split.name);
+        } else {
+            println!("✂️  Split {}: {} file(s)", split.name, claimed);
+        }
+        outputs.push(output);
+    }
+
+    // drop the directories that moving files emptied, but not ones shipped empty on purpose
+    emptied.sort_by_key(|dir: &PathBuf| std::cmp::Reverse(dir.components().count()));
+    for dir in emptied {
+        for ancestor in dir.ancestors().filter(|a| !a.as_os_str().is_empty()) {
+            if fs::remove_dir(files_dir.join(ancestor)).is_err() {
+                break;
+            }
+        }
+    }
+    if files_dir.exists() && fs::read_dir(files_dir)?.next().is_none() {
+        fs::remove_dir(files_dir)?;
+    }
+    Ok(outputs)
+}
+
+/// Pin `"*"` dependencies on other outputs of the same build to exactly the
+/// version they all share (`=x.y.z`), so outputs of one build are always
+/// installed together.
+pub fn pin_siblings(star: &mut Star, siblings: &[String]) {
+    let version = star.version.clone();
+    for (name, constraint) in star.dependencies.iter_mut().flatten() {
+        if siblings.contains(name) && (constraint.is_empty() || constraint == "*") {
+            *constraint = format!("={}", version);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn star(splits: &str) -> Star {
+        toml::from_str(&format!("name = \"foo\"\nversion = \"1.2.0\"\n[authors]\n{}", splits)).unwrap()
+    }
+
+    #[test]
+    fn glob_segments() {
+        assert!(glob_match("usr/include/*.h", "usr/include/foo.h"));
+        assert!(!glob_match("usr/include/*.h", "usr/include/foo/bar.h"));
+        assert!(glob_match("usr/lib/lib?.a", "usr/lib/libz.a"));
+        assert!(!glob_match("usr/lib/lib?.a", "usr/lib/libzz.a"));
+        assert!(glob_match("/usr/lib/", "usr/lib/libz.so"));
+    }
+
+    #[test]
+    fn glob_double_star_and_directories() {
+        assert!(glob_match("usr/**/*.a", "usr/lib/libz.a"));
+        assert!(glob_match("usr/**/*.a", "usr/lib/x86_64/libz.a"));
+        assert!(glob_match("**/*.pc", "usr/lib/pkgconfig/z.pc"));
+        assert!(glob_match("usr/include", "usr/include/zlib/zconf.h"));
+        assert!(!glob_match("usr/include", "usr/includes/zconf.h"));
+        assert!(!glob_match("usr/lib/*.a", "usr/share/libz.a"));
+    }
+
+    #[test]
+    fn first_split_claims_a_path() {
+        let star = star("[[split]]\nname = \"foo-dev\"\nfiles = [\"usr/include\", \"usr/lib/*.a\"]\n[[split]]\nname = \"foo-all\"\nfiles = [\"usr\"]");
+        assert_eq!(claimed_by(&star, "usr/include/foo.h"), Some("foo-dev"));
+        assert_eq!(claimed_by(&star, "usr/bin/foo"), Some("foo-all"));
+        assert_eq!(claimed_by(&star, "etc/foo.conf"), None);
+    }
+
+    #[test]
+    fn carved_copy_sources_are_reported() {
+        let star = star("[[split]]\nname = \"foo-dev\"\nfiles = [\"usr/include\"]");
+        let script = "function install()\n  copy(\"usr/bin/foo\", \"/usr/bin/foo\")\n  copy(\"usr/include/foo.h\", \"/usr/include/foo.h\")\nend\n";
+        assert_eq!(carved_sources(&star, script), vec![("usr/include/foo.h".to_string(), 3, "foo-dev".to_string())]);
+    }
+
+    #[test]
+    fn only_wildcard_sibling_dependencies_are_pinned() {
+        let mut star = star("[dependencies]\nfoo-libs = \"*\"\nfoo-data = \"\"\nmusl = \"*\"\nfoo-dev = \"1.0.0\"");
+        let siblings = ["foo".to_string(), "foo-libs".to_string(), "foo-data".to_string(), "foo-dev".to_string()];
+        pin_siblings(&mut star, &siblings);
+        let deps = star.dependencies.unwrap();
+        assert_eq!(deps["foo-libs"], "=1.2.0");
+        assert_eq!(deps["foo-data"], "=1.2.0");
+        assert_eq!(deps["musl"], "*");
+        assert_eq!(deps["foo-dev"], "1.0.0");
+    }
+}
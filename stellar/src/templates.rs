@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use cosmos_core::star::{Star, StarDirectory, StarGroup, StarSource, StarSplit, StarUser};
+
+use crate::build_star;
+use crate::import::lua;
+
+/// Templates `new-star --template` accepts, in prompt order.
+pub const TEMPLATES: [&str; 7] = ["normal", "static", "library", "config", "service", "recipe", "nebula"];
+
+/// Write the skeleton for `template` into the new star directory `dir` and
+/// fill in the matching `star.toml` fields. Returns a hint for what to add next.
+pub fn apply(template: &str, star: &mut Star, dir: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    let name = star.name.clone();
+    let files = dir.join("files");
+
+    let (script, hint) = match template {
+        "static" => {
+            fs::create_dir_all(files.join("usr/bin"))?;
+            star.static_linked = Some(true);
+            (
+                Some(format!(
+                    "function install()\n  copy(\"usr/bin/{0}\", \"/usr/bin/{0}\")\n  chmod(\"/usr/bin/{0}\", tonumber(\"755\", 8))\nend\n",
+                    name
+                )),
+                format!("Put the statically linked binary at files/usr/bin/{}", name),
+            )
+        }
+        "library" => {
+            fs::create_dir_all(files.join("usr/lib"))?;
+            fs::create_dir_all(files.join("usr/include").join(&name))?;
+            // no install.lua: the files are installed as they are, so the split keeps its share
+            star.install_script = None;
+            star.split = Some(vec![StarSplit {
+                name: format!("{}-dev", name),
+                description: Some(format!("{} headers and linker symlinks", name)),
+                dependencies: Some(HashMap::from([(name.clone(), "*".to_string())])),
+                files: vec![
+                    "/usr/include".to_string(),
+                    "/usr/lib/*.so".to_string(),
+                    "/usr/lib/*.a".to_string(),
+                    "/usr/lib/pkgconfig".to_string(),
+                ],
+            }]);
+            (
+                None,
+                format!("Put lib{0}.so.* in files/usr/lib and headers in files/usr/include/{0}; {0}-dev gets the headers and .so links", name),
+            )
+        }
+        "config" => {
+            let conf = format!("etc/{0}/{0}.conf", name);
+            fs::create_dir_all(files.join(format!("etc/{}", name)))?;
+            fs::write(files.join(&conf), format!("# {} configuration\n", name))?;
+            (
+                Some(format!("function install()\n  -- edited copies are kept; the new version is saved as .cosmos-new\n  template(\"{0}\", \"/{0}\")\nend\n", conf)),
+                format!("Edit files/{}; use {{{{version}}}} and other star fields as needed", conf),
+            )
+        }
+        "service" => {
+            fs::create_dir_all(files.join("usr/bin"))?;
+            fs::create_dir_all(files.join("etc/init.d"))?;
+            fs::create_dir_all(files.join("etc/sv").join(&name))?;
+            let openrc = files.join("etc/init.d").join(&name);
+            fs::write(
+                &openrc,
+                format!(
+                    "#!/sbin/openrc-run\n\ndescription=\"{0}\"\ncommand=\"/usr/bin/{0}\"\ncommand_user=\"{0}:{0}\"\ncommand_background=true\npidfile=\"/run/{0}.pid\"\n\ndepend() {{\n\tneed net\n}}\n",
+                    name
+                ),
+            )?;
+            let runit = files.join("etc/sv").join(&name).join("run");
+            fs::write(&runit, format!("#!/bin/sh\nexec chpst -u {0}:{0} /usr/bin/{0} 2>&1\n", name))?;
+            for script in [&openrc, &runit] {
+                fs::set_permissions(script, fs::Permissions::from_mode(0o755))?;
+            }
+
+            star.groups = Some(vec![StarGroup { name: name.clone(), gid: None }]);
+            star.users = Some(vec![StarUser {
+                name: name.clone(),
+                uid: None,
+                group: Some(name.clone()),
+                home: Some(format!("/var/lib/{}", name)),
+                shell: Some("/sbin/nologin".to_string()),
+                gecos: Some(format!("{} daemon", name)),
+                groups: None,
+            }]);
+            star.directories = Some(vec![StarDirectory {
+                path: format!("/var/lib/{}", name),
+                mode: Some(0o750),
+                owner: Some(format!("{0}:{0}", name)),
+            }]);
+            (
+                Some(format!(
+                    "function install()\n  copy(\"usr/bin/{0}\", \"/usr/bin/{0}\")\n  chmod(\"/usr/bin/{0}\", tonumber(\"755\", 8))\n\n  \
+                     -- both definitions ship; service_enable links the one for the root's init system\n  \
+                     copy(\"etc/init.d/{0}\", \"/etc/init.d/{0}\")\n  chmod(\"/etc/init.d/{0}\", tonumber(\"755\", 8))\n  \
+                     copy(\"etc/sv/{0}/run\", \"/etc/sv/{0}/run\")\n  chmod(\"/etc/sv/{0}/run\", tonumber(\"755\", 8))\n  \
+                     service_enable(\"{0}\")\nend\n",
+                    name
+                )),
+                format!("Put the daemon at files/usr/bin/{}; it runs as the {} user from the [[users]] table", name, name),
+            )
+        }
+        "recipe" => {
+            let unpacked = format!("{}-{}", name, star.version);
+            star.sources = Some(vec![StarSource {
+                url: format!("https://example.org/{}.tar.gz", unpacked),
+                sha256: None,
+                filename: None,
+                extract: None,
+            }]);
+            (
+                Some(format!(
+                    "function build()\n  run(\"make\", \"-C\", \"{0}\", \"PREFIX=/usr\")\n  \
+                     run(\"make\", \"-C\", \"{0}\", \"PREFIX=/usr\", \"DESTDIR=\" .. destdir, \"install\")\nend\n",
+                    unpacked
+                )),
+                "Set the [[sources]] url; build-star prints the sha256 to pin".to_string(),
+            )
+        }
+        "nebula" => {
+            star.install_script = None;
+            (None, "Nebulas are metapackages: list their stars as dependencies".to_string())
+        }
+        _ => {
+            fs::create_dir_all(&files)?;
+            (
+                Some("function install()\n  -- copy(\"bin/tool\", \"/usr/bin/tool\")\nend\n".to_string()),
+                "Add files/ and edit install.lua".to_string(),
+            )
+        }
+    };
+
+    if let Some(script) = script {
+        fs::write(dir.join("install.lua"), script)?;
+    }
+    Ok(hint)
+}
+
+/// Copy the tree at `source` into `dir`/files and write an install.lua that
+/// installs every entry with its mode. Returns the number of files copied.
+pub fn from_dir(source: &Path, dir: &Path) -> Result<usize, Box<dyn std::error::Error>> {
+    let files = dir.join("files");
+    build_star::copy_tree(source, &files)?;
+
+    let mut calls = vec![];
+    let mut copied = 0;
+    for entry in walkdir::WalkDir::new(&files).min_depth(1).sort_by_file_name() {
+        let entry = entry?;
+        let relative = entry.path().strip_prefix(&files)?.to_string_lossy().to_string();
+        let dest = lua(&format!("/{}", relative));
+        let metadata = entry.path().symlink_metadata()?;
+        let mode = metadata.permissions().mode() & 0o7777;
+
+        if metadata.file_type().is_symlink() {
+            let target = fs::read_link(entry.path())?.to_string_lossy().to_string();
+            calls.push(format!("symlink({}, {})", lua(&target), dest));
+        } else if metadata.is_dir() {
+            let empty = fs::read_dir(entry.path())?.next().is_none();
+            if empty || mode != 0o755 {
+                calls.push(format!("mkdir({})", dest));
+            }
+            if mode != 0o755 {
+                calls.push(format!("chmod({}, tonumber(\"{:o}\", 8))", dest, mode));
+            }
+        } else {
+            calls.push(format!("copy({}, {})", lua(&relative), dest));
+            calls.push(format!("chmod({}, tonumber(\"{:o}\", 8))", dest, mode));
+            copied += 1;
+        }
+    }
+
+    let mut script = format!("-- Generated by `stellar new-star --from {}`.\n\nfunction install()\n", source.display());
+    for call in calls {
+        script.push_str(&format!("  {}\n", call));
+    }
+    script.push_str("end\n");
+    fs::write(dir.join("install.lua"), script)?;
+    Ok(copied)
+}
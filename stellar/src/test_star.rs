@@ -0,0 +1,226 @@
+use std::collections::HashMap;
+use std::fs;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use tempfile::tempdir;
+use cosmos_core::config::Config;
+use cosmos_core::galaxy::Galaxy;
+use cosmos_core::installer::{install_star, uninstall_star};
+use cosmos_universe::{SystemInfo, Universe};
+use nova::mlua::Error as LuaError;
+use nova::NovaRuntime;
+
+use crate::build_star;
+
+/// Everything passed.
+pub const EXIT_PASSED: i32 = 0;
+/// The star built and installed, but an assertion failed or files were left behind.
+pub const EXIT_FAILED: i32 = 1;
+/// The star could not be built, installed or uninstalled at all.
+pub const EXIT_ERROR: i32 = 2;
+
+/// Build the star in `path`, install it into a fresh root through the real
+/// installer, run its `test.lua`, uninstall it and look for leftovers.
+/// Returns the process exit code.
+pub fn test_star(path: &str) -> i32 {
+    match run(Path::new(path)) {
+        Ok(problems) if problems.is_empty() => {
+            println!("✅ All checks passed");
+            EXIT_PASSED
+        }
+        Ok(problems) => {
+            for problem in &problems {
+                eprintln!("❌ {}", problem);
+            }
+            eprintln!("❌ {} problem(s) found", problems.len());
+            EXIT_FAILED
+        }
+        Err(e) => {
+            // build errors already carry their own prefix
+            let message = e.to_string();
+            if message.starts_with('❌') {
+                eprintln!("{}", message);
+            } else {
+                eprintln!("❌ Error: {}", message);
+            }
+            EXIT_ERROR
+        }
+    }
+}
+
+fn run(dir: &Path) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let work = tempdir()?;
+    let galaxy_dir = work.path().join("galaxy");
+    let root = work.path().join("root");
+    fs::create_dir_all(&root)?;
+
+    // dependencies come from the host's galaxies, read from their local copies only
+    let host_galaxies = build_star::target_galaxies(None)?;
+
+    println!("🧪 Building star in a scratch galaxy");
+    let built = build_star::build(dir, true, false, &galaxy_dir.join("packages"), &host_galaxies)?;
+    if built.tarball.is_none() {
+        return Err("star has no files or install script, so there is nothing to install".into());
+    }
+    let star = built.star;
+    let mut problems = vec![];
+
+    let mut galaxy = Galaxy::new("stellar-test", galaxy_dir.to_str().unwrap());
+    galaxy.add_star(star.clone());
+
+    let mut galaxies = vec![galaxy];
+    galaxies.extend(host_galaxies);
+
+    let config = Config {
+        galaxies: HashMap::new(),
+        install_dir: root.display().to_string(),
+        cache_dir: work.path().join("cache").display().to_string(),
+        init_system: None,
+    };
+    let mut universe = Universe {
+        system: SystemInfo {
+            arch: std::env::consts::ARCH.to_string(),
+            version: "0.1.0".to_string(),
+        },
+        installed: HashMap::new(),
+    };
+
+    println!("🧪 Installing {} {} into {}", star.name, star.version, root.display());
+    install_star(&star, &galaxies[0], &mut universe, &galaxies, &config, true)?;
+
+    let test_script = dir.join("test.lua");
+    if test_script.exists() {
+        println!("🧪 Running {}", test_script.display());
+        if let Err(e) = test_runtime(dir, &root).run(test_script.to_str().unwrap()) {
+            problems.push(format!("test.lua failed:\n{}", e));
+        }
+    } else {
+        println!("⚠️  No test.lua found. Only checking install and uninstall.");
+    }
+
+    println!("🧪 Uninstalling {}", star.name);
+    // the root is thrown away anyway, but a clean uninstall is part of the test
+    let installed: Vec<String> = universe.installed.keys().cloned().collect();
+    for name in installed {
+        uninstall_star(&name, &mut universe, &root, true)?;
+    }
+
+    for leftover in leftovers(&root)? {
+        problems.push(format!("Left behind after uninstall: {}", leftover));
+    }
+
+    Ok(problems)
+}
+
+/// The account databases stay around after their entries are removed, so
+/// they never count as leftovers.
+const ACCOUNT_FILES: [&str; 3] = ["/etc/passwd", "/etc/group", "/etc/shadow"];
+
+fn leftovers(root: &Path) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut found = vec![];
+    for entry in walkdir::WalkDir::new(root).min_depth(1).sort_by_file_name() {
+        let entry = entry?;
+        if entry.file_type().is_dir() {
+            continue;
+        }
+        let path = format!("/{}", entry.path().strip_prefix(root)?.display());
+        if !ACCOUNT_FILES.contains(&path.as_str()) {
+            found.push(path);
+        }
+    }
+    Ok(found)
+}
+
+/// Resolve a path from `test.lua` inside the test root.
+fn in_root(root: &Path, path: &str) -> PathBuf {
+    root.join(path.trim_start_matches('/'))
+}
+
+fn fail(message: String) -> LuaError {
+    LuaError::external(message)
+}
+
+/// A runtime for `test.lua`: the usual file builtins plus assertions about
+/// the installed root. `test()` is called if the script defines one.
+fn test_runtime(dir: &Path, root: &Path) -> NovaRuntime {
+    let file_root = root.to_path_buf();
+    let dir_root = root.to_path_buf();
+    let mode_root = root.to_path_buf();
+    let link_root = root.to_path_buf();
+    let owner_root = root.to_path_buf();
+    let contains_root = root.to_path_buf();
+    let user_root = root.to_path_buf();
+    let group_root = root.to_path_buf();
+
+    NovaRuntime::new(dir, root)
+        .entry("test")
+        .allow_missing_entry()
+        .builtin("assert_file", move |_, path: String| {
+            if in_root(&file_root, &path).is_file() {
+                Ok(())
+            } else {
+                Err(fail(format!("{} is not a file", path)))
+            }
+        })
+        .builtin("assert_dir", move |_, path: String| {
+            if in_root(&dir_root, &path).is_dir() {
+                Ok(())
+            } else {
+                Err(fail(format!("{} is not a directory", path)))
+            }
+        })
+        .builtin("assert_mode", move |_, (path, mode): (String, u32)| {
+            let actual = fs::symlink_metadata(in_root(&mode_root, &path))?.permissions().mode() & 0o7777;
+            if actual == mode {
+                Ok(())
+            } else {
+                Err(fail(format!("{} has mode {:o}, expected {:o}", path, actual, mode)))
+            }
+        })
+        .builtin("assert_symlink", move |_, (path, target): (String, Option<String>)| {
+            let actual = fs::read_link(in_root(&link_root, &path))
+                .map_err(|_| fail(format!("{} is not a symlink", path)))?;
+            match target {
+                Some(target) if actual != Path::new(&target) => Err(fail(format!(
+                    "{} points to {}, expected {}", path, actual.display(), target
+                ))),
+                _ => Ok(()),
+            }
+        })
+        .builtin("assert_owner", move |_, (path, user, group): (String, String, Option<String>)| {
+            let metadata = fs::symlink_metadata(in_root(&owner_root, &path))?;
+            let uid = nova::users::resolve_uid(&owner_root, &user)?;
+            if metadata.uid() != uid {
+                return Err(fail(format!("{} is owned by uid {}, expected {} ({})", path, metadata.uid(), user, uid)));
+            }
+            if let Some(group) = group {
+                let gid = nova::users::resolve_gid(&owner_root, &group)?;
+                if metadata.gid() != gid {
+                    return Err(fail(format!("{} has gid {}, expected {} ({})", path, metadata.gid(), group, gid)));
+                }
+            }
+            Ok(())
+        })
+        .builtin("assert_contains", move |_, (path, text): (String, String)| {
+            let content = fs::read_to_string(in_root(&contains_root, &path))?;
+            if content.contains(&text) {
+                Ok(())
+            } else {
+                Err(fail(format!("{} does not contain {:?}", path, text)))
+            }
+        })
+        .builtin("assert_user", move |_, name: String| {
+            if nova::users::user_exists(&user_root, &name)? {
+                Ok(())
+            } else {
+                Err(fail(format!("user {} does not exist", name)))
+            }
+        })
+        .builtin("assert_group", move |_, name: String| {
+            if nova::users::group_exists(&group_root, &name)? {
+                Ok(())
+            } else {
+                Err(fail(format!("group {} does not exist", name)))
+            }
+        })
+}
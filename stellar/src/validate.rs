@@ -1,9 +1,19 @@
+use std::collections::HashSet;
 use std::fs;
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
+use cosmos_core::galaxy::Galaxy;
+use cosmos_core::resolver::{calculate_checksum, satisfies_constraint};
 use cosmos_core::star::Star;
+use nova::mlua::{Lua, Value};
+use nova::{Capability, NovaRuntime};
 use semver::Version;
+use tempfile::tempdir;
 
-pub fn validate(path: &str) -> Result<(), Box<dyn std::error::Error>> {
+use crate::build_star;
+use crate::lua_lint;
+use crate::split;
+
+pub fn validate(path: &str, galaxy: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
     let dir = Path::new(path);
     let star_path = dir.join("star.toml");
     let star_str = fs::read_to_string(&star_path)?;
@@ -29,12 +39,291 @@ pub fn validate(path: &str) -> Result<(), Box<dyn std::error::Error>> {
         return Err("❌ Error: The `authors` field is required and cannot be empty.".into());
     }
 
+    validate_declarations(&star)?;
+
     let lua = dir.join("install.lua");
     let sh = dir.join("install.sh");
     if lua.exists() && sh.exists() {
         return Err("❌ Error: Both install.lua and install.sh exist. Please remove one.".into());
     }
 
+    let mut problems = vec![];
+    let files_dir = dir.join("files");
+    let is_recipe = star.sources.as_ref().is_some_and(|sources| !sources.is_empty());
+    validate_sources(&star)?;
+
+    if matches!(star.star_type.as_deref(), Some("nebula") | Some("meta")) && (files_dir.exists() || is_recipe) {
+        problems.push("Nebula and meta stars cannot have files/ or [[sources]].".to_string());
+    }
+    if lua.exists() {
+        // a recipe's build() copies from the build directory, not files/
+        problems.extend(lint_script(&lua, dir, if is_recipe { None } else { Some(&files_dir) })?);
+        for (source, line, split) in split::carved_sources(&star, &fs::read_to_string(&lua)?) {
+            problems.push(format!("install.lua:{}: '{}' is moved into split '{}', so install.lua cannot copy it", line, source, split));
+        }
+    }
+    problems.extend(check_dependencies(&star, &build_star::target_galaxies(galaxy)?));
+    problems.extend(check_checksums(&star, &files_dir));
+    if files_dir.exists() {
+        problems.extend(check_symlinks(&files_dir)?);
+    }
+
+    if !problems.is_empty() {
+        for problem in &problems {
+            eprintln!("❌ {}", problem);
+        }
+        return Err(format!("❌ Error: {} problem(s) found in {}-{}", problems.len(), star.name, star.version).into());
+    }
+
     println!("✅ Valid star: {}-{}", star.name, star.version);
     Ok(())
 }
+
+fn validate_declarations(star: &Star) -> Result<(), Box<dyn std::error::Error>> {
+    let mut groups = HashSet::new();
+    for group in star.groups.iter().flatten() {
+        if !nova::users::is_valid_name(&group.name) {
+            return Err(format!("❌ Error: Invalid group name '{}'.", group.name).into());
+        }
+        if !groups.insert(group.name.as_str()) {
+            return Err(format!("❌ Error: Group '{}' is declared twice.", group.name).into());
+        }
+    }
+
+    let mut users = HashSet::new();
+    for user in star.users.iter().flatten() {
+        if !nova::users::is_valid_name(&user.name) {
+            return Err(format!("❌ Error: Invalid user name '{}'.", user.name).into());
+        }
+        if !users.insert(user.name.as_str()) {
+            return Err(format!("❌ Error: User '{}' is declared twice.", user.name).into());
+        }
+        if let Some(group) = &user.group {
+            if !groups.contains(group.as_str()) {
+                println!("⚠️  User '{}' uses group '{}', which this star does not declare.", user.name, group);
+            }
+        }
+    }
+
+    let mut paths = HashSet::new();
+    for dir in star.directories.iter().flatten() {
+        if !dir.path.starts_with('/') {
+            return Err(format!("❌ Error: Directory path '{}' must be absolute.", dir.path).into());
+        }
+        if dir.path.split('/').any(|part| part == "..") {
+            return Err(format!("❌ Error: Directory path '{}' must not contain '..'.", dir.path).into());
+        }
+        if !paths.insert(dir.path.as_str()) {
+            return Err(format!("❌ Error: Directory '{}' is declared twice.", dir.path).into());
+        }
+        if let Some(mode) = dir.mode {
+            if mode > 0o7777 {
+                return Err(format!("❌ Error: Invalid mode {:o} for '{}'.", mode, dir.path).into());
+            }
+            if mode & 0o002 != 0 && mode & 0o1000 == 0 {
+                println!("⚠️  Directory '{}' is world-writable without the sticky bit.", dir.path);
+            }
+        }
+        if let Some((user, group)) = dir.owner_parts() {
+            if user != "root" && !users.contains(user) {
+                println!("⚠️  Directory '{}' is owned by '{}', which this star does not declare.", dir.path, user);
+            }
+            if let Some(group) = group {
+                if group != "root" && !groups.contains(group) && !users.contains(group) {
+                    println!("⚠️  Directory '{}' uses group '{}', which this star does not declare.", dir.path, group);
+                }
+            }
+        }
+    }
+
+    let mut outputs = HashSet::from([star.name.as_str()]);
+    for split in star.split.iter().flatten() {
+        if split.name.trim().is_empty() {
+            return Err("❌ Error: A [[split]] entry has an empty `name`.".into());
+        }
+        if !outputs.insert(split.name.as_str()) {
+            return Err(format!("❌ Error: Split '{}' reuses the name of another output.", split.name).into());
+        }
+        if split.files.is_empty() {
+            return Err(format!("❌ Error: Split '{}' has no `files` globs.", split.name).into());
+        }
+    }
+
+    if let Some(postprocess) = &star.postprocess {
+        let debug = format!("{}-debug", star.name);
+        if postprocess.debug() && outputs.contains(debug.as_str()) {
+            return Err(format!("❌ Error: Split '{}' clashes with the debug star of [postprocess].", debug).into());
+        }
+        if postprocess.debug == Some(true) && !postprocess.strip() {
+            println!("⚠️  [postprocess] keeps debug info, but `strip = false` means there is none to keep.");
+        }
+    }
+
+    Ok(())
+}
+
+/// Every `[[sources]]` entry needs a URL and a file name of its own.
+fn validate_sources(star: &Star) -> Result<(), Box<dyn std::error::Error>> {
+    let mut filenames = HashSet::new();
+    for source in star.sources.iter().flatten() {
+        if source.url.trim().is_empty() {
+            return Err("❌ Error: A [[sources]] entry has an empty `url`.".into());
+        }
+        if !filenames.insert(source.filename()) {
+            return Err(format!("❌ Error: Two sources are saved as '{}'. Set `filename` on one of them.", source.filename()).into());
+        }
+        if source.sha256.is_none() {
+            println!("⚠️  Source '{}' has no sha256, so it cannot be verified.", source.url);
+        }
+    }
+    Ok(())
+}
+
+/// Globals a Nova script can rely on: the builtins of every capability plus the Lua standard library.
+fn known_globals(dir: &Path) -> Result<HashSet<String>, Box<dyn std::error::Error>> {
+    let root = tempdir()?;
+    let session = NovaRuntime::new(dir, root.path())
+        .with_capability(Capability::Users)
+        .with_capability(Capability::Services)
+        .with_capability(Capability::Archive)
+        .with_capability(Capability::Net)
+        .global("srcdir", "")
+        .global("destdir", "")
+        .session()?;
+    let mut known = HashSet::new();
+    for pair in session.lua().globals().pairs::<Value, Value>() {
+        if let (Value::String(name), _) = pair? {
+            known.insert(name.to_str()?.to_string());
+        }
+    }
+    Ok(known)
+}
+
+/// Syntax errors, calls to functions nothing defines, and `copy`/`template`
+/// sources missing from `files_dir`.
+fn lint_script(script: &Path, dir: &Path, files_dir: Option<&Path>) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let code = fs::read_to_string(script)?;
+    let name = script.file_name().unwrap_or_default().to_string_lossy().to_string();
+
+    if let Err(e) = Lua::new().load(&code).set_name(format!("@{}", name)).into_function() {
+        return Ok(vec![e.to_string()]);
+    }
+
+    let report = lua_lint::inspect(&code, &known_globals(dir)?);
+    let mut problems = vec![];
+    for (function, line) in &report.undefined {
+        problems.push(format!("{}:{}: call to undefined function '{}'", name, line, function));
+    }
+    if let Some(files_dir) = files_dir {
+        for (source, line) in &report.file_sources {
+            if !files_dir.join(source.trim_start_matches('/')).exists() {
+                problems.push(format!("{}:{}: '{}' does not exist under files/", name, line, source));
+            }
+        }
+    }
+    Ok(problems)
+}
+
+/// Every constraint must be `*`, a version or an exact `=version`, and some galaxy must have a star that meets it.
+fn check_dependencies(star: &Star, galaxies: &[Galaxy]) -> Vec<String> {
+    let mut problems = vec![];
+    if galaxies.is_empty() && star.dependencies.as_ref().is_some_and(|deps| !deps.is_empty()) {
+        println!("⚠️  No galaxy to check dependencies against. Pass --galaxy <dir>.");
+    }
+    let mut dependencies: Vec<_> = star.dependencies.iter().flatten().collect();
+    dependencies.sort();
+    for (name, constraint) in dependencies {
+        let any_version = constraint.is_empty() || constraint == "*";
+        if !any_version && Version::parse(constraint.strip_prefix('=').unwrap_or(constraint)).is_err() {
+            problems.push(format!("Dependency {} has an invalid version constraint '{}'.", name, constraint));
+            continue;
+        }
+        if galaxies.is_empty() {
+            continue;
+        }
+        let candidates: Vec<&Star> = galaxies.iter().filter_map(|galaxy| galaxy.get_star(name)).collect();
+        if candidates.is_empty() {
+            problems.push(format!("Dependency {} is not in any galaxy.", name));
+        } else if !any_version && !candidates.iter().any(|c| satisfies_constraint(&c.version, constraint).unwrap_or(false)) {
+            let versions: Vec<&str> = candidates.iter().map(|c| c.version.as_str()).collect();
+            problems.push(format!("Dependency {} needs {}, but galaxies only have {}.", name, constraint, versions.join(", ")));
+        }
+    }
+    problems
+}
+
+/// Recorded checksums must match the files as they are now.
+fn check_checksums(star: &Star, files_dir: &Path) -> Vec<String> {
+    let mut entries: Vec<_> = star.checksums.iter().flatten().collect();
+    entries.sort();
+    let mut problems = vec![];
+    for (path, expected) in entries {
+        match calculate_checksum(&files_dir.join(path)) {
+            Ok(actual) if &actual == expected => {}
+            Ok(_) => problems.push(format!("Checksum for {} does not match the file.", path)),
+            Err(_) => problems.push(format!("Checksum is recorded for {}, but the file is missing.", path)),
+        }
+    }
+    problems
+}
+
+/// Resolve `target` lexically from `base`, both relative to the install root.
+/// Returns `None` if it climbs above the root.
+fn resolve_within(base: &Path, target: &Path) -> Option<PathBuf> {
+    let mut resolved = PathBuf::new();
+    for component in base.join(target).components() {
+        match component {
+            Component::ParentDir if !resolved.pop() => return None,
+            Component::Normal(part) => resolved.push(part),
+            _ => {}
+        }
+    }
+    Some(resolved)
+}
+
+/// Absolute symlinks resolve against the host's `/` when installing with
+/// `--root`, and relative ones must not climb out of the root.
+fn check_symlinks(files_dir: &Path) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut problems = vec![];
+    for entry in walkdir::WalkDir::new(files_dir).sort_by_file_name() {
+        let entry = entry?;
+        if !entry.path_is_symlink() {
+            continue;
+        }
+        let relative = entry.path().strip_prefix(files_dir)?;
+        let target = fs::read_link(entry.path())?;
+        if target.is_absolute() {
+            let shipped = files_dir.join(target.strip_prefix("/")?).symlink_metadata().is_ok();
+            println!(
+                "⚠️  /{} is an absolute symlink to {}{}. Use a relative target so it resolves inside the install root.",
+                relative.display(),
+                target.display(),
+                if shipped { "" } else { ", which this star does not ship" }
+            );
+        } else if resolve_within(relative.parent().unwrap_or(Path::new("")), &target).is_none() {
+            problems.push(format!("/{} points outside the install root ({}).", relative.display(), target.display()));
+        }
+    }
+    Ok(problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn star(sources: &str) -> Star {
+        toml::from_str(&format!("name = \"hello\"\nversion = \"1.0.0\"\n{}\n[authors]", sources)).unwrap()
+    }
+
+    #[test]
+    fn sources_need_a_url_and_distinct_file_names() {
+        assert!(validate_sources(&star("")).is_ok());
+        assert!(validate_sources(&star("[[sources]]\nurl = \"https://example.org/hello-1.0.tar.gz\"")).is_ok());
+        assert!(validate_sources(&star("[[sources]]\nurl = \" \"")).is_err());
+
+        let clash = "[[sources]]\nurl = \"https://a.example/hello.tar.gz\"\n[[sources]]\nurl = \"https://b.example/hello.tar.gz\"";
+        assert!(validate_sources(&star(clash)).unwrap_err().to_string().contains("hello.tar.gz"));
+        let renamed = format!("{}\nfilename = \"hello-b.tar.gz\"", clash);
+        assert!(validate_sources(&star(&renamed)).is_ok());
+    }
+}